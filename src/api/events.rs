@@ -0,0 +1,203 @@
+use axum::extract::{Query, State};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::ApiError;
+use crate::database;
+use crate::database::events::{EventLog, EventsDocument, EventsQuery};
+use crate::rpc::decode::DecodedMessage;
+use crate::IndexerContext;
+
+const DEFAULT_PAGE_LIMIT: i64 = 100;
+const MAX_PAGE_LIMIT: i64 = 1000;
+
+#[derive(Deserialize, Debug)]
+pub struct EventsParams {
+    pub chain_id: Option<String>,
+    pub key: Option<String>,
+    pub contract_address: Option<String>,
+    pub tx_hash: Option<String>,
+    pub success: Option<bool>,
+    pub from_height: Option<u64>,
+    pub to_height: Option<u64>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct EventResponse {
+    pub id: String,
+    #[serde(rename = "chainId")]
+    pub chain_id: String,
+    #[serde(rename = "blockHeight")]
+    pub block_height: u64,
+    #[serde(rename = "blockTime")]
+    pub block_time: Option<String>,
+    #[serde(rename = "blockHash")]
+    pub block_hash: Option<String>,
+    #[serde(rename = "proposerAddress")]
+    pub proposer_address: Option<String>,
+    #[serde(rename = "txHash")]
+    pub tx_hash: String,
+    #[serde(rename = "transactionId")]
+    pub transaction_id: Option<String>,
+    pub key: String,
+    pub logs: Vec<EventLog>,
+    #[serde(rename = "fullLogs")]
+    pub full_logs: Vec<EventLog>,
+    #[serde(rename = "failedMessage")]
+    pub failed_message: Option<DecodedMessage>,
+    pub success: bool,
+    pub code: i64,
+    pub codespace: String,
+    pub log: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+impl From<EventsDocument> for EventResponse {
+    fn from(event: EventsDocument) -> Self {
+        EventResponse {
+            id: event._id.to_hex(),
+            chain_id: event.chain_id,
+            block_height: event.block_height,
+            block_time: event
+                .block_time
+                .and_then(|block_time| block_time.try_to_rfc3339_string().ok()),
+            block_hash: event.block_hash,
+            proposer_address: event.proposer_address,
+            tx_hash: event.tx_hash,
+            transaction_id: event.transaction_id.map(|id| id.to_hex()),
+            key: event.key,
+            logs: event.logs,
+            full_logs: event.full_logs,
+            failed_message: event.failed_message,
+            success: event.success,
+            code: event.code,
+            codespace: event.codespace,
+            log: event.log,
+            created_at: event.created_at.try_to_rfc3339_string().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct EventsPage {
+    pub events: Vec<EventResponse>,
+    #[serde(rename = "nextCursor")]
+    pub next_cursor: Option<String>,
+}
+
+/// Cursors are opaque to clients but encode the `blockHeight` and `_id` of the last event
+/// returned, matching the sort order of the query.
+pub(crate) fn encode_cursor(event: &EventsDocument) -> String {
+    format!("{}-{}", event.block_height, event._id.to_hex())
+}
+
+pub(crate) fn decode_cursor(cursor: &str) -> Result<(u64, mongodb::bson::oid::ObjectId), ApiError> {
+    let (height, id) = cursor
+        .split_once('-')
+        .ok_or(ApiError::bad_request("Invalid cursor"))?;
+    let height = height
+        .parse::<u64>()
+        .map_err(|_| ApiError::bad_request("Invalid cursor"))?;
+    let id = mongodb::bson::oid::ObjectId::parse_str(id)
+        .map_err(|_| ApiError::bad_request("Invalid cursor"))?;
+
+    Ok((height, id))
+}
+
+pub async fn list_events(
+    State(context): State<Arc<IndexerContext>>,
+    Query(params): Query<EventsParams>,
+) -> Result<Json<EventsPage>, ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    if !(1..=MAX_PAGE_LIMIT).contains(&limit) {
+        return Err(ApiError::bad_request(format!(
+            "limit must be between 1 and {}",
+            MAX_PAGE_LIMIT
+        )));
+    }
+
+    let after = match params.cursor.as_deref() {
+        Some(cursor) => Some(decode_cursor(cursor)?),
+        None => None,
+    };
+
+    let query = EventsQuery {
+        chain_id: params.chain_id,
+        keys: params.key.into_iter().collect(),
+        contract_address: params.contract_address,
+        tx_hash: params.tx_hash,
+        success: params.success,
+        from_height: params.from_height,
+        to_height: params.to_height,
+        attributes: Vec::new(),
+        descending: false,
+        after,
+        limit,
+    };
+
+    let events = database::events::fetch_events(context, &query).await?;
+    let next_cursor = if events.len() as i64 == limit {
+        events.last().map(encode_cursor)
+    } else {
+        None
+    };
+
+    Ok(Json(EventsPage {
+        events: events.into_iter().map(EventResponse::from).collect(),
+        next_cursor,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(block_height: u64) -> EventsDocument {
+        EventsDocument {
+            _id: mongodb::bson::oid::ObjectId::new(),
+            chain_id: "neutron-1".to_string(),
+            block_height,
+            block_time: None,
+            block_hash: None,
+            proposer_address: None,
+            tx_hash: "ABC".to_string(),
+            transaction_id: None,
+            key: "swap".to_string(),
+            logs: Vec::new(),
+            full_logs: Vec::new(),
+            failed_message: None,
+            success: true,
+            code: 0,
+            codespace: String::new(),
+            log: String::new(),
+            created_at: mongodb::bson::DateTime::now(),
+        }
+    }
+
+    #[test]
+    fn decodes_the_encoded_cursor() {
+        let event = event(150);
+
+        let (height, id) = decode_cursor(&encode_cursor(&event)).ok().unwrap();
+        assert_eq!((height, id), (150, event._id));
+    }
+
+    #[test]
+    fn rejects_invalid_cursors() {
+        let id = mongodb::bson::oid::ObjectId::new().to_hex();
+
+        for cursor in [
+            String::new(),
+            "150".to_string(),
+            format!("height-{}", id),
+            format!("-1-{}", id),
+            "150-notanobjectid".to_string(),
+        ] {
+            assert!(decode_cursor(&cursor).is_err(), "{}", cursor);
+        }
+    }
+}
@@ -0,0 +1,550 @@
+use async_graphql::dynamic::{
+    Enum, Field, FieldFuture, FieldValue, InputObject, InputValue, Object, Scalar, Schema,
+    SchemaError, TypeRef,
+};
+use async_graphql::http::GraphiQLSource;
+use async_graphql::Value;
+use axum::extract::Extension;
+use axum::response::{Html, IntoResponse};
+use axum::Json;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use super::events::{decode_cursor, encode_cursor};
+use crate::database;
+use crate::database::events::{EventLog, EventsDocument, EventsQuery};
+use crate::event_matcher::matcher_config::{MatcherConfig, MatcherEvent};
+use crate::IndexerContext;
+
+const DEFAULT_PAGE_LIMIT: i64 = 100;
+const MAX_PAGE_LIMIT: i64 = 1000;
+
+/// Heights don't fit the 32-bit graphql `Int`, they are serialized as strings.
+const BIG_INT: &str = "BigInt";
+
+/// Types of the schema that matcher types must not shadow.
+const RESERVED_TYPE_NAMES: [&str; 10] = [
+    "Query",
+    "EventLog",
+    "AttributeFilter",
+    "SortOrder",
+    BIG_INT,
+    TypeRef::STRING,
+    TypeRef::INT,
+    TypeRef::FLOAT,
+    TypeRef::BOOLEAN,
+    TypeRef::ID,
+];
+
+const EVENT_FIELDS: [&str; 17] = [
+    "id",
+    "chainId",
+    "blockHeight",
+    "blockTime",
+    "blockHash",
+    "proposerAddress",
+    "txHash",
+    "transactionId",
+    "key",
+    "logs",
+    "fullLogs",
+    "failedMessage",
+    "success",
+    "code",
+    "codespace",
+    "log",
+    "createdAt",
+];
+
+struct EventsConnection {
+    events: Vec<EventsDocument>,
+    next_cursor: Option<String>,
+}
+
+fn words(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+/// `example_event` -> `ExampleEvent`
+pub fn type_name(key: &str) -> String {
+    words(key).iter().map(|word| capitalize(word)).collect()
+}
+
+/// `_contract_address` -> `contractAddress`
+pub fn field_name(key: &str) -> String {
+    let words = words(key);
+    let mut name = String::new();
+    for (index, word) in words.iter().enumerate() {
+        if index == 0 {
+            name.push_str(word);
+        } else {
+            name.push_str(&capitalize(word));
+        }
+    }
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+fn connection_type_name(key: &str) -> String {
+    format!("{}Connection", type_name(key))
+}
+
+/// Attribute keys exposed as typed fields of a matcher type.
+fn attribute_keys(event: &MatcherEvent) -> BTreeSet<String> {
+    event
+        .patterns
+        .iter()
+        .map(|pattern| pattern.key.to_owned())
+        .chain(event.fields.iter().cloned())
+        .collect()
+}
+
+/// Rejects matchers that `build_schema` can't map to distinct graphql names: keys without a
+/// valid type name, keys whose types clash with each other or with the types of the schema,
+/// and attribute keys whose field names collide. Attributes named like an event field, e.g.
+/// `key`, stay readable from `logs` only.
+pub fn validate_names(matcher_config: &MatcherConfig) -> Result<(), anyhow::Error> {
+    let mut type_keys: HashMap<String, &str> = HashMap::new();
+
+    for event in matcher_config.events.iter() {
+        let name = type_name(&event.key);
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(anyhow::anyhow!(
+                "Matcher key {} isn't a valid graphql type name once normalized to: {:?}",
+                event.key,
+                name
+            ));
+        }
+
+        for name in [name, connection_type_name(&event.key)] {
+            if RESERVED_TYPE_NAMES.contains(&name.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "Matcher key {} clashes with the graphql type {}",
+                    event.key,
+                    name
+                ));
+            }
+            if let Some(other_key) = type_keys.insert(name.to_owned(), &event.key) {
+                return Err(anyhow::anyhow!(
+                    "Matcher keys {} and {} both map to the graphql type {}",
+                    other_key,
+                    event.key,
+                    name
+                ));
+            }
+        }
+
+        let mut field_keys: HashMap<String, String> = HashMap::new();
+        for attribute_key in attribute_keys(event) {
+            let name = field_name(&attribute_key);
+            if name.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Attribute {:?} of matcher {} isn't a valid graphql field name",
+                    attribute_key,
+                    event.key
+                ));
+            }
+            if let Some(other_key) = field_keys.insert(name.to_owned(), attribute_key.to_owned()) {
+                return Err(anyhow::anyhow!(
+                    "Attributes {} and {} of matcher {} both map to the graphql field {}",
+                    other_key,
+                    attribute_key,
+                    event.key,
+                    name
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn event_value(ctx: &async_graphql::dynamic::ResolverContext) -> Option<EventsDocument> {
+    ctx.parent_value
+        .try_downcast_ref::<EventsDocument>()
+        .ok()
+        .cloned()
+}
+
+fn scalar_field(name: &str, type_ref: TypeRef, resolve: fn(&EventsDocument) -> Value) -> Field {
+    Field::new(name, type_ref, move |ctx| {
+        FieldFuture::new(async move {
+            Ok(event_value(&ctx).map(|event| FieldValue::value(resolve(&event))))
+        })
+    })
+}
+
+fn logs_field(name: &str, resolve: fn(&EventsDocument) -> &Vec<EventLog>) -> Field {
+    Field::new(name, TypeRef::named_nn_list_nn("EventLog"), move |ctx| {
+        FieldFuture::new(async move {
+            let event = event_value(&ctx).ok_or("Missing event")?;
+            Ok(Some(FieldValue::list(
+                resolve(&event).iter().cloned().map(FieldValue::owned_any),
+            )))
+        })
+    })
+}
+
+fn attribute_field(name: &str, attribute_key: String) -> Field {
+    Field::new(name, TypeRef::named(TypeRef::STRING), move |ctx| {
+        let attribute_key = attribute_key.to_owned();
+        FieldFuture::new(async move {
+            Ok(event_value(&ctx).and_then(|event| {
+                event
+                    .logs
+                    .iter()
+                    .find(|log| log.key == attribute_key)
+                    .map(|log| FieldValue::value(log.value.to_owned()))
+            }))
+        })
+    })
+}
+
+fn event_object(event: &MatcherEvent) -> Object {
+    let mut object = Object::new(type_name(&event.key))
+        .description(event.name.to_owned())
+        .field(scalar_field(
+            "id",
+            TypeRef::named_nn(TypeRef::ID),
+            |event| Value::from(event._id.to_hex()),
+        ))
+        .field(scalar_field(
+            "chainId",
+            TypeRef::named_nn(TypeRef::STRING),
+            |event| Value::from(event.chain_id.to_owned()),
+        ))
+        .field(scalar_field(
+            "blockHeight",
+            TypeRef::named_nn(BIG_INT),
+            |event| Value::from(event.block_height.to_string()),
+        ))
+        .field(scalar_field(
+            "blockTime",
+            TypeRef::named(TypeRef::STRING),
+            |event| {
+                event
+                    .block_time
+                    .and_then(|block_time| block_time.try_to_rfc3339_string().ok())
+                    .map(Value::from)
+                    .unwrap_or(Value::Null)
+            },
+        ))
+        .field(scalar_field(
+            "blockHash",
+            TypeRef::named(TypeRef::STRING),
+            |event| Value::from(event.block_hash.to_owned()),
+        ))
+        .field(scalar_field(
+            "proposerAddress",
+            TypeRef::named(TypeRef::STRING),
+            |event| Value::from(event.proposer_address.to_owned()),
+        ))
+        .field(scalar_field(
+            "txHash",
+            TypeRef::named_nn(TypeRef::STRING),
+            |event| Value::from(event.tx_hash.to_owned()),
+        ))
+        .field(scalar_field(
+            "transactionId",
+            TypeRef::named(TypeRef::ID),
+            |event| Value::from(event.transaction_id.map(|id| id.to_hex())),
+        ))
+        .field(scalar_field(
+            "key",
+            TypeRef::named_nn(TypeRef::STRING),
+            |event| Value::from(event.key.to_owned()),
+        ))
+        .field(logs_field("logs", |event| &event.logs))
+        .field(logs_field("fullLogs", |event| &event.full_logs))
+        .field(scalar_field(
+            "failedMessage",
+            TypeRef::named(TypeRef::STRING),
+            |event| {
+                event
+                    .failed_message
+                    .as_ref()
+                    .and_then(|message| serde_json::to_string(message).ok())
+                    .map(Value::from)
+                    .unwrap_or(Value::Null)
+            },
+        ))
+        .field(scalar_field(
+            "success",
+            TypeRef::named_nn(TypeRef::BOOLEAN),
+            |event| Value::from(event.success),
+        ))
+        .field(scalar_field(
+            "code",
+            TypeRef::named_nn(TypeRef::INT),
+            |event| Value::from(event.code),
+        ))
+        .field(scalar_field(
+            "codespace",
+            TypeRef::named_nn(TypeRef::STRING),
+            |event| Value::from(event.codespace.to_owned()),
+        ))
+        .field(scalar_field(
+            "log",
+            TypeRef::named_nn(TypeRef::STRING),
+            |event| Value::from(event.log.to_owned()),
+        ))
+        .field(scalar_field(
+            "createdAt",
+            TypeRef::named_nn(TypeRef::STRING),
+            |event| Value::from(event.created_at.try_to_rfc3339_string().unwrap_or_default()),
+        ));
+
+    for attribute_key in attribute_keys(event) {
+        let name = field_name(&attribute_key);
+        if EVENT_FIELDS.contains(&name.as_str()) {
+            continue;
+        }
+        object = object.field(attribute_field(&name, attribute_key));
+    }
+
+    object
+}
+
+fn connection_object(event: &MatcherEvent) -> Object {
+    let node_type = type_name(&event.key);
+
+    Object::new(connection_type_name(&event.key))
+        .field(Field::new(
+            "nodes",
+            TypeRef::named_nn_list_nn(node_type),
+            |ctx| {
+                FieldFuture::new(async move {
+                    let connection = ctx.parent_value.try_downcast_ref::<EventsConnection>()?;
+                    Ok(Some(FieldValue::list(
+                        connection.events.iter().cloned().map(FieldValue::owned_any),
+                    )))
+                })
+            },
+        ))
+        .field(Field::new(
+            "nextCursor",
+            TypeRef::named(TypeRef::STRING),
+            |ctx| {
+                FieldFuture::new(async move {
+                    let connection = ctx.parent_value.try_downcast_ref::<EventsConnection>()?;
+                    Ok(connection.next_cursor.to_owned().map(FieldValue::value))
+                })
+            },
+        ))
+}
+
+fn query_field(event: &MatcherEvent) -> Field {
+    let event_key = event.key.to_owned();
+
+    Field::new(
+        field_name(&event.key),
+        TypeRef::named_nn(connection_type_name(&event.key)),
+        move |ctx| {
+            let event_key = event_key.to_owned();
+            FieldFuture::new(async move {
+                let context = ctx.data::<Arc<IndexerContext>>()?.clone();
+
+                let limit = match ctx.args.get("first") {
+                    Some(first) => first.i64()?,
+                    None => DEFAULT_PAGE_LIMIT,
+                };
+                if !(1..=MAX_PAGE_LIMIT).contains(&limit) {
+                    return Err(format!("first must be between 1 and {}", MAX_PAGE_LIMIT).into());
+                }
+
+                let mut attributes = Vec::new();
+                if let Some(filters) = ctx.args.get("where") {
+                    for filter in filters.list()?.iter() {
+                        let filter = filter.object()?;
+                        attributes.push((
+                            filter.try_get("key")?.string()?.to_owned(),
+                            filter.try_get("value")?.string()?.to_owned(),
+                        ));
+                    }
+                }
+
+                let string_arg = |name: &str| -> async_graphql::Result<Option<String>> {
+                    match ctx.args.get(name) {
+                        Some(value) => Ok(Some(value.string()?.to_owned())),
+                        None => Ok(None),
+                    }
+                };
+                let height_arg = |name: &str| -> async_graphql::Result<Option<u64>> {
+                    match ctx.args.get(name) {
+                        Some(value) => Ok(Some(parse_big_int(value.as_value())?)),
+                        None => Ok(None),
+                    }
+                };
+
+                let after = match string_arg("after")? {
+                    Some(cursor) => {
+                        Some(decode_cursor(&cursor).map_err(|_| "Invalid cursor".to_string())?)
+                    }
+                    None => None,
+                };
+                let descending = match ctx.args.get("sort") {
+                    Some(sort) => sort.enum_name()? == "DESC",
+                    None => false,
+                };
+
+                let query = EventsQuery {
+                    chain_id: string_arg("chainId")?,
+                    keys: vec![event_key],
+                    contract_address: string_arg("contractAddress")?,
+                    tx_hash: string_arg("txHash")?,
+                    success: match ctx.args.get("success") {
+                        Some(success) => Some(success.boolean()?),
+                        None => None,
+                    },
+                    from_height: height_arg("fromHeight")?,
+                    to_height: height_arg("toHeight")?,
+                    attributes,
+                    descending,
+                    after,
+                    limit,
+                };
+
+                let events = database::events::fetch_events(context, &query).await?;
+                let next_cursor = if events.len() as i64 == limit {
+                    events.last().map(encode_cursor)
+                } else {
+                    None
+                };
+
+                Ok(Some(FieldValue::owned_any(EventsConnection {
+                    events,
+                    next_cursor,
+                })))
+            })
+        },
+    )
+    .description(format!("Events matched by the `{}` matcher", event.key))
+    .argument(InputValue::new("chainId", TypeRef::named(TypeRef::STRING)))
+    .argument(InputValue::new(
+        "contractAddress",
+        TypeRef::named(TypeRef::STRING),
+    ))
+    .argument(InputValue::new("txHash", TypeRef::named(TypeRef::STRING)))
+    .argument(InputValue::new("success", TypeRef::named(TypeRef::BOOLEAN)))
+    .argument(InputValue::new("fromHeight", TypeRef::named(BIG_INT)))
+    .argument(InputValue::new("toHeight", TypeRef::named(BIG_INT)))
+    .argument(InputValue::new(
+        "where",
+        TypeRef::named_nn_list("AttributeFilter"),
+    ))
+    .argument(
+        InputValue::new("sort", TypeRef::named_nn("SortOrder"))
+            .default_value(Value::Enum(async_graphql::Name::new("ASC"))),
+    )
+    .argument(
+        InputValue::new("first", TypeRef::named_nn(TypeRef::INT))
+            .default_value(Value::from(DEFAULT_PAGE_LIMIT)),
+    )
+    .argument(InputValue::new("after", TypeRef::named(TypeRef::STRING)))
+}
+
+/// Matchers of every chain, the first chain's matcher wins when chains share a key or keys
+/// that map to the same graphql type.
+pub fn merge_matchers(contexts: &[Arc<IndexerContext>]) -> MatcherConfig {
+    let mut matcher_config = contexts[0].matcher_config.clone();
+    for context in contexts.iter().skip(1) {
+        for event in context.matcher_config.events.iter() {
+            if !matcher_config
+                .events
+                .iter()
+                .any(|other| type_name(&other.key) == type_name(&event.key))
+            {
+                matcher_config.events.push(event.clone());
+            }
+        }
+    }
+
+    matcher_config
+}
+
+/// Builds a schema with one object type and one query field per matcher event, so clients can
+/// select the attributes extracted by each matcher as typed fields.
+pub fn build_schema(
+    context: Arc<IndexerContext>,
+    matcher_config: &MatcherConfig,
+) -> Result<Schema, SchemaError> {
+    let event_log = Object::new("EventLog")
+        .field(Field::new(
+            "key",
+            TypeRef::named_nn(TypeRef::STRING),
+            |ctx| {
+                FieldFuture::new(async move {
+                    let log = ctx.parent_value.try_downcast_ref::<EventLog>()?;
+                    Ok(Some(FieldValue::value(log.key.to_owned())))
+                })
+            },
+        ))
+        .field(Field::new(
+            "value",
+            TypeRef::named_nn(TypeRef::STRING),
+            |ctx| {
+                FieldFuture::new(async move {
+                    let log = ctx.parent_value.try_downcast_ref::<EventLog>()?;
+                    Ok(Some(FieldValue::value(log.value.to_owned())))
+                })
+            },
+        ));
+    let attribute_filter = InputObject::new("AttributeFilter")
+        .field(InputValue::new("key", TypeRef::named_nn(TypeRef::STRING)))
+        .field(InputValue::new("value", TypeRef::named_nn(TypeRef::STRING)));
+    let sort_order = Enum::new("SortOrder").item("ASC").item("DESC");
+    let big_int = Scalar::new(BIG_INT)
+        .description("A 64-bit unsigned integer serialized as a string, inputs can be numbers")
+        .validator(|value| parse_big_int(value).is_ok());
+
+    let mut query = Object::new("Query");
+    let mut schema = Schema::build("Query", None, None)
+        .register(event_log)
+        .register(attribute_filter)
+        .register(sort_order)
+        .register(big_int);
+
+    for event in matcher_config.events.iter() {
+        query = query.field(query_field(event));
+        schema = schema
+            .register(event_object(event))
+            .register(connection_object(event));
+    }
+
+    schema.register(query).data(context).finish()
+}
+
+fn parse_big_int(value: &Value) -> Result<u64, String> {
+    match value {
+        Value::String(value) => value.parse::<u64>().map_err(|err| err.to_string()),
+        Value::Number(value) => value
+            .as_u64()
+            .ok_or_else(|| format!("{} isn't an unsigned integer", value)),
+        value => Err(format!("{} isn't an unsigned integer", value)),
+    }
+}
+
+pub async fn graphql_handler(
+    Extension(schema): Extension<Schema>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(request).await)
+}
+
+pub async fn graphiql() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
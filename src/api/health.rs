@@ -0,0 +1,156 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use mongodb::bson::doc;
+use serde::Serialize;
+use std::sync::Arc;
+
+use super::Chains;
+use crate::IndexerContext;
+
+#[derive(Serialize, Debug)]
+pub struct LivenessResponse {
+    pub alive: bool,
+    /// Of the chain that ticked the longest ago.
+    #[serde(rename = "millisSinceLastTick")]
+    pub millis_since_last_tick: u64,
+    pub chains: Vec<ChainLiveness>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChainLiveness {
+    #[serde(rename = "chainId")]
+    pub chain_id: String,
+    pub alive: bool,
+    #[serde(rename = "millisSinceLastTick")]
+    pub millis_since_last_tick: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub database: bool,
+    /// Every block source is reachable.
+    pub rpc: bool,
+    /// Of the first chain that isn't ready, or the first chain.
+    #[serde(rename = "blockLag")]
+    pub block_lag: u64,
+    #[serde(rename = "maxBlockLag")]
+    pub max_block_lag: u64,
+    pub chains: Vec<ChainReadiness>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChainReadiness {
+    #[serde(rename = "chainId")]
+    pub chain_id: String,
+    pub ready: bool,
+    pub rpc: bool,
+    #[serde(rename = "blockLag")]
+    pub block_lag: u64,
+    #[serde(rename = "maxBlockLag")]
+    pub max_block_lag: u64,
+}
+
+fn status_code(healthy: bool) -> StatusCode {
+    if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+/// Alive as long as the loop of every chain ticked within its `liveness_max_tick_age`.
+pub async fn liveness(
+    Extension(chains): Extension<Chains>,
+) -> (StatusCode, Json<LivenessResponse>) {
+    let chains: Vec<ChainLiveness> = chains
+        .0
+        .iter()
+        .map(|context| {
+            let millis_since_last_tick = context.loop_state.millis_since_last_tick();
+            ChainLiveness {
+                chain_id: context.indexer_config.chain_id.to_owned(),
+                alive: millis_since_last_tick <= context.indexer_config.liveness_max_tick_age,
+                millis_since_last_tick,
+            }
+        })
+        .collect();
+    let alive = chains.iter().all(|chain| chain.alive);
+    let millis_since_last_tick = chains
+        .iter()
+        .map(|chain| chain.millis_since_last_tick)
+        .max()
+        .unwrap_or(0);
+
+    (
+        status_code(alive),
+        Json(LivenessResponse {
+            alive,
+            millis_since_last_tick,
+            chains,
+        }),
+    )
+}
+
+async fn chain_readiness(context: Arc<IndexerContext>) -> ChainReadiness {
+    let rpc = context
+        .loop_state
+        .source_probe
+        .get_or_probe(async { context.source.head_height(context.clone()).await.is_ok() })
+        .await;
+    let block_lag = context.loop_state.block_lag();
+    let standby = context.lease.as_ref().is_some_and(|lease| !lease.is_held());
+    let max_block_lag =
+        context.indexer_config.readiness_max_block_lag + context.indexer_config.confirmation_depth;
+
+    ChainReadiness {
+        chain_id: context.indexer_config.chain_id.to_owned(),
+        // A standby replica doesn't index, its lag is the leader's concern.
+        ready: rpc && (block_lag <= max_block_lag || standby),
+        rpc,
+        block_lag,
+        max_block_lag,
+    }
+}
+
+/// Ready when the database and the block source of every chain are reachable and every chain
+/// is within `readiness_max_block_lag` blocks of its head, not counting the confirmation depth.
+/// Reachability is probed at most every few seconds.
+pub async fn readiness(
+    State(context): State<Arc<IndexerContext>>,
+    Extension(chains): Extension<Chains>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let database = context
+        .loop_state
+        .database_probe
+        .get_or_probe(async {
+            context
+                .database
+                .run_command(doc! { "ping": 1 }, None)
+                .await
+                .is_ok()
+        })
+        .await;
+    let chains = futures::future::join_all(chains.0.iter().cloned().map(chain_readiness)).await;
+    let rpc = chains.iter().all(|chain| chain.rpc);
+    let ready = database && chains.iter().all(|chain| chain.ready);
+    let (block_lag, max_block_lag) = chains
+        .iter()
+        .find(|chain| !chain.ready)
+        .or(chains.first())
+        .map(|chain| (chain.block_lag, chain.max_block_lag))
+        .unwrap_or_default();
+
+    (
+        status_code(ready),
+        Json(ReadinessResponse {
+            ready,
+            database,
+            rpc,
+            block_lag,
+            max_block_lag,
+            chains,
+        }),
+    )
+}
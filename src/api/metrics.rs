@@ -0,0 +1,18 @@
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use std::sync::Arc;
+
+use super::ApiError;
+use crate::IndexerContext;
+
+pub async fn export_metrics(
+    State(context): State<Arc<IndexerContext>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let body = context
+        .metrics
+        .encode()
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+
+    Ok(([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], body))
+}
@@ -0,0 +1,105 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::error;
+
+use crate::IndexerContext;
+
+pub mod events;
+pub mod graphql;
+pub mod health;
+pub mod metrics;
+pub mod status;
+pub mod subscriptions;
+pub mod transactions;
+
+/// Every chain indexed by the process, the router state is the first one.
+#[derive(Clone)]
+pub struct Chains(pub Vec<Arc<IndexerContext>>);
+
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        ApiError {
+            status: StatusCode::NOT_FOUND,
+            message: message.into(),
+        }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        ApiError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<mongodb::error::Error> for ApiError {
+    fn from(err: mongodb::error::Error) -> Self {
+        ApiError::internal(err.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+/// Metrics and probes are always served, the read api only when `api_enabled`.
+pub fn router(contexts: Vec<Arc<IndexerContext>>) -> Router {
+    let context = contexts[0].clone();
+    let mut router = Router::new()
+        .route("/metrics", get(metrics::export_metrics))
+        .route("/healthz", get(health::liveness))
+        .route("/readyz", get(health::readiness));
+
+    if context.indexer_config.api_enabled {
+        router = router
+            .route("/events", get(events::list_events))
+            .route("/events/subscribe", get(subscriptions::subscribe_events))
+            .route("/transactions/:tx_hash", get(transactions::get_transaction))
+            .route("/status", get(status::list_status));
+
+        match graphql::build_schema(context.clone(), &graphql::merge_matchers(&contexts)) {
+            Ok(schema) => {
+                router = router
+                    .route(
+                        "/graphql",
+                        get(graphql::graphiql).post(graphql::graphql_handler),
+                    )
+                    .layer(Extension(schema));
+            }
+            Err(err) => error!("Failed to build graphql schema: {}", err),
+        }
+    }
+
+    router
+        .layer(Extension(Chains(contexts)))
+        .with_state(context)
+}
+
+pub async fn serve(contexts: Vec<Arc<IndexerContext>>) -> Result<(), anyhow::Error> {
+    let address: SocketAddr = contexts[0].indexer_config.api_address.parse()?;
+
+    axum::Server::bind(&address)
+        .serve(router(contexts).into_make_service())
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use axum::extract::State;
+use axum::{Extension, Json};
+use serde::Serialize;
+use std::sync::Arc;
+
+use super::{ApiError, Chains};
+use crate::database;
+use crate::IndexerContext;
+
+#[derive(Serialize, Debug)]
+pub struct StatusResponse {
+    #[serde(rename = "chainId")]
+    pub chain_id: String,
+    #[serde(rename = "indexedHeight")]
+    pub indexed_height: u64,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+    /// Head and lag of the chains indexed by this process.
+    #[serde(rename = "headHeight")]
+    pub head_height: Option<u64>,
+    #[serde(rename = "blockLag")]
+    pub block_lag: Option<u64>,
+    /// Whether this replica holds the lease, for chains indexed with leader election.
+    pub leader: Option<bool>,
+}
+
+pub async fn list_status(
+    State(context): State<Arc<IndexerContext>>,
+    Extension(chains): Extension<Chains>,
+) -> Result<Json<Vec<StatusResponse>>, ApiError> {
+    let statuses = database::stream_status::fetch_all_indexer_statuses(context).await?;
+
+    Ok(Json(
+        statuses
+            .into_iter()
+            .map(|status| {
+                let chain = chains
+                    .0
+                    .iter()
+                    .find(|context| context.indexer_config.chain_id == status.chain_id);
+                let loop_state = chain.map(|context| &context.loop_state);
+
+                StatusResponse {
+                    head_height: loop_state.map(|loop_state| loop_state.head_height()),
+                    block_lag: loop_state.map(|loop_state| loop_state.block_lag()),
+                    leader: chain
+                        .and_then(|context| context.lease.as_ref())
+                        .map(|lease| lease.is_held()),
+                    chain_id: status.chain_id,
+                    indexed_height: status.indexed_height,
+                    updated_at: status
+                        .updated_at
+                        .try_to_rfc3339_string()
+                        .unwrap_or_default(),
+                }
+            })
+            .collect(),
+    ))
+}
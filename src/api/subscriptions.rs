@@ -0,0 +1,275 @@
+use async_trait::async_trait;
+use axum::extract::{Query, State};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::Extension;
+use futures::stream::{self, Stream};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use super::events::{encode_cursor, EventResponse};
+use super::{ApiError, Chains};
+use crate::database;
+use crate::database::events::{EventsDocument, EventsQuery};
+use crate::reorg::Rollback;
+use crate::{IndexerContext, StreamMessage};
+
+const REPLAY_PAGE_LIMIT: i64 = 500;
+const SUBSCRIBER_BUFFER_SIZE: usize = 256;
+
+/// Filters of a single subscriber, parsed from
+/// `?keys=a,b&chain_id=C&from_height=N&attr.<key>=<value>`. Every chain matches without
+/// `chain_id`.
+#[derive(Debug, Clone)]
+pub struct SubscriptionFilter {
+    pub chain_id: Option<String>,
+    pub keys: Vec<String>,
+    pub attributes: Vec<(String, String)>,
+    pub from_height: Option<u64>,
+}
+
+impl SubscriptionFilter {
+    pub fn from_params(params: HashMap<String, String>) -> Result<Self, ApiError> {
+        let mut filter = SubscriptionFilter {
+            chain_id: None,
+            keys: Vec::new(),
+            attributes: Vec::new(),
+            from_height: None,
+        };
+
+        for (name, value) in params {
+            if name == "chain_id" {
+                filter.chain_id = Some(value);
+            } else if name == "keys" {
+                filter.keys = value
+                    .split(',')
+                    .filter(|key| !key.is_empty())
+                    .map(|key| key.to_owned())
+                    .collect();
+            } else if name == "from_height" {
+                filter.from_height = Some(
+                    value
+                        .parse()
+                        .map_err(|_| ApiError::bad_request("Invalid from_height"))?,
+                );
+            } else if let Some(attribute) = name.strip_prefix("attr.") {
+                filter.attributes.push((attribute.to_owned(), value));
+            }
+        }
+
+        if filter.keys.is_empty() {
+            return Err(ApiError::bad_request("At least one key is required"));
+        }
+
+        Ok(filter)
+    }
+
+    pub fn matches_chain(&self, chain_id: &str) -> bool {
+        self.chain_id
+            .as_ref()
+            .is_none_or(|filter_chain_id| filter_chain_id == chain_id)
+    }
+
+    pub fn matches(&self, event: &EventsDocument) -> bool {
+        self.matches_chain(&event.chain_id)
+            && self.keys.contains(&event.key)
+            && self.attributes.iter().all(|(key, value)| {
+                event
+                    .logs
+                    .iter()
+                    .any(|log| log.key == *key && log.value == *value)
+            })
+    }
+}
+
+/// Where subscriptions replay stored events from. The api reads the database, tests can keep
+/// the events in memory.
+#[async_trait]
+pub trait EventHistory: Send + Sync {
+    /// Last height of `chain` whose events were all published.
+    async fn committed_height(&self, chain: Arc<IndexerContext>) -> Result<u64, anyhow::Error>;
+
+    /// A page of stored events, sorted like `/events` sorts them.
+    async fn events(
+        &self,
+        context: Arc<IndexerContext>,
+        query: &EventsQuery,
+    ) -> Result<Vec<EventsDocument>, anyhow::Error>;
+}
+
+/// The events and stream status collections.
+pub struct StoredEvents;
+
+#[async_trait]
+impl EventHistory for StoredEvents {
+    async fn committed_height(&self, chain: Arc<IndexerContext>) -> Result<u64, anyhow::Error> {
+        Ok(database::stream_status::fetch_indexed_height(chain).await?)
+    }
+
+    async fn events(
+        &self,
+        context: Arc<IndexerContext>,
+        query: &EventsQuery,
+    ) -> Result<Vec<EventsDocument>, anyhow::Error> {
+        Ok(database::events::fetch_events(context, query).await?)
+    }
+}
+
+/// What a subscriber is sent, replayed and live events alike.
+#[derive(Debug, Clone)]
+pub enum SubscriptionMessage {
+    Event(Box<EventsDocument>),
+    Rollback(Rollback),
+    /// The subscriber fell behind the stream and missed this many messages.
+    Lagged(u64),
+}
+
+fn sse_event(message: SubscriptionMessage) -> Event {
+    match message {
+        SubscriptionMessage::Event(event) => Event::default()
+            .event(&event.key)
+            .id(encode_cursor(&event))
+            .json_data(EventResponse::from(*event))
+            .unwrap_or_default(),
+        SubscriptionMessage::Rollback(rollback) => Event::default()
+            .event("rollback")
+            .json_data(&rollback)
+            .unwrap_or_default(),
+        SubscriptionMessage::Lagged(skipped) => {
+            Event::default().event("lagged").data(skipped.to_string())
+        }
+    }
+}
+
+/// Replays stored events from `from_height` and then forwards live events and rollbacks. The
+/// committed heights are read before subscribing to the stream: an event at or below them was
+/// published before the subscription and only comes from the replay, an event above them may
+/// come from both and is remembered to skip its live copy. Events saved between the two reads
+/// are above the committed height and still replayed, so none is missed or sent twice.
+pub async fn forward_events(
+    context: Arc<IndexerContext>,
+    chains: Chains,
+    history: Arc<dyn EventHistory>,
+    filter: SubscriptionFilter,
+    sender: mpsc::Sender<SubscriptionMessage>,
+) {
+    let mut committed_heights = HashMap::new();
+    if filter.from_height.is_some() {
+        for chain in chains.0.iter() {
+            let chain_id = &chain.indexer_config.chain_id;
+            if filter.matches_chain(chain_id) {
+                let committed_height = history.committed_height(chain.clone()).await.unwrap_or(0);
+                committed_heights.insert(chain_id.to_owned(), committed_height);
+            }
+        }
+    }
+    let mut receiver = context.event_stream.subscribe();
+    let mut replayed = HashSet::new();
+
+    if let Some(from_height) = filter.from_height {
+        let mut query = EventsQuery {
+            chain_id: filter.chain_id.clone(),
+            keys: filter.keys.clone(),
+            attributes: filter.attributes.clone(),
+            from_height: Some(from_height),
+            limit: REPLAY_PAGE_LIMIT,
+            ..Default::default()
+        };
+
+        loop {
+            let events = match history.events(context.clone(), &query).await {
+                Ok(events) => events,
+                Err(err) => {
+                    warn!("Failed to replay events for subscription: {}", err);
+                    return;
+                }
+            };
+
+            for event in events.iter() {
+                let committed_height = committed_heights.get(&event.chain_id).copied();
+                if event.block_height > committed_height.unwrap_or(0) {
+                    replayed.insert(event._id);
+                }
+                let message = SubscriptionMessage::Event(Box::new(event.clone()));
+                if sender.send(message).await.is_err() {
+                    return;
+                }
+            }
+
+            match events.last() {
+                Some(last) if events.len() as i64 == REPLAY_PAGE_LIMIT => {
+                    query.after = Some((last.block_height, last._id));
+                }
+                _ => break,
+            }
+        }
+    }
+
+    loop {
+        let message = match receiver.recv().await {
+            Ok(StreamMessage::Event(event)) => {
+                if !filter.matches(&event) || replayed.remove(&event._id) {
+                    continue;
+                }
+                SubscriptionMessage::Event(event)
+            }
+            Ok(StreamMessage::Rollback(rollback)) => {
+                if !filter.matches_chain(&rollback.chain_id) {
+                    continue;
+                }
+                SubscriptionMessage::Rollback(rollback)
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Subscriber lagged behind, skipped {} events", skipped);
+                SubscriptionMessage::Lagged(skipped)
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if sender.send(message).await.is_err() {
+            debug!("Subscriber disconnected");
+            return;
+        }
+    }
+}
+
+/// Every chain publishes to the same event stream, so any context serves the subscription.
+pub async fn subscribe_events(
+    State(context): State<Arc<IndexerContext>>,
+    Extension(chains): Extension<Chains>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let filter = SubscriptionFilter::from_params(params)?;
+    if let Some(chain_id) = filter.chain_id.as_ref() {
+        if !chains
+            .0
+            .iter()
+            .any(|chain| chain.indexer_config.chain_id == *chain_id)
+        {
+            return Err(ApiError::not_found(format!(
+                "Unknown chain_id: {}",
+                chain_id
+            )));
+        }
+    }
+    let (sender, receiver) = mpsc::channel(SUBSCRIBER_BUFFER_SIZE);
+
+    tokio::spawn(forward_events(
+        context,
+        chains,
+        Arc::new(StoredEvents),
+        filter,
+        sender,
+    ));
+
+    let stream = stream::unfold(receiver, |mut receiver| async move {
+        receiver
+            .recv()
+            .await
+            .map(|message| (Ok(sse_event(message)), receiver))
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
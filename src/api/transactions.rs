@@ -0,0 +1,67 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::ApiError;
+use crate::database;
+use crate::database::transactions::TransactionDocument;
+use crate::rpc::decode::DecodedTx;
+use crate::IndexerContext;
+
+#[derive(Deserialize, Debug)]
+pub struct TransactionParams {
+    pub chain_id: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TransactionResponse {
+    pub id: String,
+    #[serde(rename = "chainId")]
+    pub chain_id: String,
+    #[serde(rename = "blockHeight")]
+    pub block_height: u64,
+    #[serde(rename = "txHash")]
+    pub tx_hash: String,
+    pub index: u64,
+    #[serde(flatten)]
+    pub body: DecodedTx,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+impl From<TransactionDocument> for TransactionResponse {
+    fn from(transaction: TransactionDocument) -> Self {
+        TransactionResponse {
+            id: transaction._id.to_hex(),
+            chain_id: transaction.chain_id,
+            block_height: transaction.block_height,
+            tx_hash: transaction.tx_hash,
+            index: transaction.index,
+            body: transaction.body,
+            created_at: transaction
+                .created_at
+                .try_to_rfc3339_string()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Stored tx of a matched event, defaults to the indexed chain.
+pub async fn get_transaction(
+    State(context): State<Arc<IndexerContext>>,
+    Path(tx_hash): Path<String>,
+    Query(params): Query<TransactionParams>,
+) -> Result<Json<TransactionResponse>, ApiError> {
+    let chain_id = params
+        .chain_id
+        .unwrap_or(context.indexer_config.chain_id.to_owned());
+
+    match database::transactions::fetch_transaction(context, &chain_id, &tx_hash).await? {
+        Some(transaction) => Ok(Json(TransactionResponse::from(transaction))),
+        None => Err(ApiError::not_found(format!(
+            "Transaction not found: {}",
+            tx_hash
+        ))),
+    }
+}
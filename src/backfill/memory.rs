@@ -0,0 +1,74 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::ChunkStore;
+use crate::IndexerContext;
+
+/// Completed chunks kept in memory, for running backfills in tests.
+#[derive(Default)]
+pub struct MemoryChunkStore {
+    /// Heights of the completed chunks of each chain.
+    chunks: Mutex<HashMap<String, Vec<(u64, u64)>>>,
+}
+
+impl MemoryChunkStore {
+    pub fn new() -> Self {
+        MemoryChunkStore::default()
+    }
+}
+
+#[async_trait]
+impl ChunkStore for MemoryChunkStore {
+    async fn completed_chunks(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<(u64, u64)>, anyhow::Error> {
+        Ok(self
+            .chunks
+            .lock()
+            .unwrap()
+            .get(&context.indexer_config.chain_id)
+            .into_iter()
+            .flatten()
+            .filter(|(from, to)| *from <= to_height && *to >= from_height)
+            .copied()
+            .collect())
+    }
+
+    async fn save_completed_chunk(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<(), anyhow::Error> {
+        self.chunks
+            .lock()
+            .unwrap()
+            .entry(context.indexer_config.chain_id.clone())
+            .or_default()
+            .push((from_height, to_height));
+
+        Ok(())
+    }
+
+    async fn delete_chunks(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<(), anyhow::Error> {
+        if let Some(chunks) = self
+            .chunks
+            .lock()
+            .unwrap()
+            .get_mut(&context.indexer_config.chain_id)
+        {
+            chunks.retain(|(from, to)| *from < from_height || *to > to_height);
+        }
+
+        Ok(())
+    }
+}
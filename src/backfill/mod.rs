@@ -0,0 +1,257 @@
+use async_trait::async_trait;
+use futures::{Future, StreamExt};
+use std::sync::Arc;
+use tracing::{error, info, instrument};
+
+use crate::database;
+use crate::helpers::AbortOnDrop;
+use crate::pipeline;
+use crate::IndexerContext;
+
+pub mod memory;
+
+pub use memory::MemoryChunkStore;
+
+/// Where the completed chunks of backfills are kept, so a retry skips them.
+#[async_trait]
+pub trait ChunkStore: Send + Sync {
+    /// Heights of the completed chunks of the chain overlapping `from_height..=to_height`.
+    async fn completed_chunks(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<(u64, u64)>, anyhow::Error>;
+
+    async fn save_completed_chunk(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<(), anyhow::Error>;
+
+    /// Forgets the completed chunks of the chain within `from_height..=to_height`.
+    async fn delete_chunks(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<(), anyhow::Error>;
+}
+
+/// Chunks in the database of the indexer.
+pub struct DatabaseChunkStore;
+
+#[async_trait]
+impl ChunkStore for DatabaseChunkStore {
+    async fn completed_chunks(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<(u64, u64)>, anyhow::Error> {
+        let chunks =
+            database::backfill_chunks::fetch_completed_chunks(context, from_height, to_height)
+                .await?;
+
+        Ok(chunks
+            .into_iter()
+            .map(|chunk| (chunk.from_height, chunk.to_height))
+            .collect())
+    }
+
+    async fn save_completed_chunk(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<(), anyhow::Error> {
+        Ok(
+            database::backfill_chunks::save_completed_chunk(context, from_height, to_height)
+                .await?,
+        )
+    }
+
+    async fn delete_chunks(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<(), anyhow::Error> {
+        Ok(
+            database::backfill_chunks::delete_chunks_in_range(context, from_height, to_height)
+                .await?,
+        )
+    }
+}
+
+/// Splits `from_height..=to_height` into chunks aligned to multiples of `chunk_size`, so the
+/// same heights always map to the same chunks whatever range a backfill is started with.
+pub fn split_chunks(from_height: u64, to_height: u64, chunk_size: u64) -> Vec<(u64, u64)> {
+    let chunk_size = chunk_size.max(1);
+    let mut chunks = Vec::new();
+    let mut chunk_from = from_height;
+
+    while chunk_from <= to_height {
+        let chunk_to = ((chunk_from / chunk_size + 1) * chunk_size - 1).min(to_height);
+        chunks.push((chunk_from, chunk_to));
+        chunk_from = chunk_to + 1;
+    }
+
+    chunks
+}
+
+/// Indexes one chunk in batches of `block_lag_batch_size`. Events left by a previous attempt
+/// that crashed halfway are dropped first so they aren't stored twice.
+#[instrument(name = "backfill_chunk", skip(context))]
+pub async fn backfill_chunk(context: Arc<IndexerContext>, from_height: u64, to_height: u64) {
+    database::events::delete_events_in_range(context.clone(), from_height, to_height)
+        .await
+        .unwrap();
+
+    let batch_size = context.indexer_config.block_lag_batch_size.max(1);
+    let mut from_block_height = from_height;
+    while from_block_height <= to_height {
+        let to_block_height = (from_block_height + batch_size - 1).min(to_height);
+        index_batch(context.clone(), from_block_height, to_block_height).await;
+        from_block_height = to_block_height + 1;
+    }
+}
+
+/// Matches and stores a batch of a chunk like the live loop does, without committing heights.
+#[instrument(name = "batch", skip(context), fields(chain_id = %context.indexer_config.chain_id))]
+async fn index_batch(context: Arc<IndexerContext>, from_height: u64, to_height: u64) {
+    let txs = context
+        .source
+        .txs(context.clone(), from_height, to_height)
+        .await
+        .unwrap();
+
+    let (events, headers, transactions) =
+        pipeline::match_txs(context.clone(), from_height, to_height, Vec::new(), &txs).await;
+    pipeline::store_events(
+        context,
+        from_height,
+        to_height,
+        events,
+        headers,
+        transactions,
+    )
+    .await;
+}
+
+/// Indexes `from_height..=to_height` with `backfill_chunk`, see `backfill_chunks`. The chunks
+/// are kept until the live loop committed the range.
+pub async fn backfill_range(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+) -> Result<(), anyhow::Error> {
+    backfill_chunks(context, from_height, to_height, backfill_chunk).await
+}
+
+/// Backfills a range requested once, like the `backfill` command does. The completed chunks are
+/// forgotten when every chunk succeeded so the same range can be backfilled again, a failed run
+/// keeps them for its retry to skip.
+pub async fn backfill_requested_range<F, Fut>(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+    index_chunk: F,
+) -> Result<(), anyhow::Error>
+where
+    F: Fn(Arc<IndexerContext>, u64, u64) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    backfill_chunks(context.clone(), from_height, to_height, index_chunk).await?;
+
+    context
+        .chunk_store
+        .delete_chunks(context.clone(), from_height, to_height)
+        .await
+}
+
+/// Indexes `from_height..=to_height` with up to `backfill_workers` chunks in flight, each with
+/// `index_chunk`, skipping chunks completed by a previous run. Fails if any chunk failed,
+/// leaving it to be retried.
+pub async fn backfill_chunks<F, Fut>(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+    index_chunk: F,
+) -> Result<(), anyhow::Error>
+where
+    F: Fn(Arc<IndexerContext>, u64, u64) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let completed = context
+        .chunk_store
+        .completed_chunks(context.clone(), from_height, to_height)
+        .await?;
+    let pending: Vec<(u64, u64)> = split_chunks(
+        from_height,
+        to_height,
+        context.indexer_config.backfill_chunk_size,
+    )
+    .into_iter()
+    .filter(|(chunk_from, chunk_to)| {
+        !completed
+            .iter()
+            .any(|(from, to)| from <= chunk_from && to >= chunk_to)
+    })
+    .collect();
+
+    let total = pending.len();
+    info!(
+        "Backfilling: from_height: {}, to_height: {}, pending_chunks: {}, workers: {}",
+        from_height, to_height, total, context.indexer_config.backfill_workers
+    );
+
+    let mut results = futures::stream::iter(pending)
+        .map(|(chunk_from, chunk_to)| {
+            let context = context.clone();
+            let indexing = AbortOnDrop(tokio::spawn(index_chunk(
+                context.clone(),
+                chunk_from,
+                chunk_to,
+            )));
+            async move {
+                let result = match indexing.await {
+                    Ok(()) => {
+                        context
+                            .chunk_store
+                            .save_completed_chunk(context.clone(), chunk_from, chunk_to)
+                            .await
+                    }
+                    Err(err) => Err(err.into()),
+                };
+                (chunk_from, chunk_to, result)
+            }
+        })
+        .buffer_unordered(context.indexer_config.backfill_workers.max(1) as usize);
+
+    let mut done = 0;
+    let mut failed = 0;
+    while let Some((chunk_from, chunk_to, result)) = results.next().await {
+        done += 1;
+        match result {
+            Ok(()) => info!(
+                "Backfilled chunk: from_height: {}, to_height: {}, progress: {}/{}",
+                chunk_from, chunk_to, done, total
+            ),
+            Err(err) => {
+                failed += 1;
+                error!(
+                    "Failed to backfill chunk: from_height: {}, to_height: {}, error: {}",
+                    chunk_from, chunk_to, err
+                );
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} backfill chunks failed", failed));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use mongodb::Database;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::info;
+
+use crate::backfill;
+use crate::database;
+use crate::event_matcher::matcher::MatchedEvent;
+use crate::helpers::AbortOnDrop;
+use crate::pipeline::{self, MatchedRange};
+use crate::source::recorded;
+use crate::IndexerContext;
+
+/// Indexes `from_height..=to_height` with the parallel backfill without touching the stored
+/// indexed height, so it can run next to the live loop. Ranges above the indexed height are
+/// refused, the live loop would index those blocks again.
+pub async fn backfill(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+) -> Result<(), anyhow::Error> {
+    if from_height > to_height {
+        return Err(anyhow::anyhow!(
+            "From height: {} is above to height: {}",
+            from_height,
+            to_height
+        ));
+    }
+    let status = database::stream_status::fetch_indexer_status(context.clone()).await?;
+    if to_height > status.indexed_height {
+        return Err(anyhow::anyhow!(
+            "To height: {} is above the indexed height: {}, the live loop indexes those blocks",
+            to_height,
+            status.indexed_height
+        ));
+    }
+
+    backfill::backfill_requested_range(context, from_height, to_height, backfill::backfill_chunk)
+        .await
+}
+
+/// Drops the events stored from `from_height` and rewinds the indexed height so the live loop
+/// indexes them again.
+pub async fn reindex(context: Arc<IndexerContext>, from_height: u64) {
+    let deleted = database::events::delete_events_from_height(context.clone(), from_height)
+        .await
+        .unwrap();
+    database::transactions::delete_transactions_from_height(context.clone(), from_height)
+        .await
+        .unwrap();
+    info!(
+        "Deleted {} events from height: {}, reindexing",
+        deleted, from_height
+    );
+
+    reset_height(context.clone(), from_height.saturating_sub(1)).await;
+
+    crate::start(context).await;
+}
+
+pub async fn reset_height(context: Arc<IndexerContext>, height: u64) {
+    // Makes sure the status document exists before updating it.
+    database::stream_status::fetch_indexer_status(context.clone())
+        .await
+        .unwrap();
+    database::stream_status::update_indexed_height(context.clone(), height)
+        .await
+        .unwrap();
+    database::blocks::delete_blocks_above_height(context.clone(), height)
+        .await
+        .unwrap();
+    // A later backfill would skip the completed chunks whose events are gone.
+    database::backfill_chunks::delete_chunks_above_height(context, height)
+        .await
+        .unwrap();
+    info!("Indexed height reset to: {}", height);
+}
+
+/// Reads the stored status of `chain_id` from the database alone, so it works while the
+/// node, the sinks or the other chains are unreachable.
+pub async fn status(
+    database: &Database,
+    chain_id: &str,
+) -> Result<Option<database::stream_status::StatusDocument>, anyhow::Error> {
+    Ok(database::stream_status::find_indexer_status(database, chain_id).await?)
+}
+
+/// Records the raw rpc responses of `from_height..=to_height` to `path` for offline replays.
+pub async fn record(context: Arc<IndexerContext>, from_height: u64, to_height: u64, path: &str) {
+    let recorded = recorded::record(context, from_height, to_height, path)
+        .await
+        .unwrap();
+    info!(
+        "Recorded {} responses from height: {} to: {} in: {}",
+        recorded, from_height, to_height, path
+    );
+}
+
+/// Matches the txs of `from_height..=to_height` read from the block source of the context
+/// with the fetch and match stages of the indexing pipeline. Nothing is stored.
+pub async fn replay(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+) -> Result<Vec<MatchedEvent>, anyhow::Error> {
+    // The fetcher would wait forever for heights missing from the source.
+    context
+        .source
+        .block_metas(context.clone(), from_height, to_height)
+        .await?;
+
+    let buffer_size = context.indexer_config.pipeline_buffer_size.max(1) as usize;
+    let (fetched_sender, fetched_receiver) = mpsc::channel(buffer_size);
+    let (matched_sender, mut matched_receiver) = mpsc::channel(buffer_size);
+    let _fetcher = AbortOnDrop(tokio::spawn(pipeline::fetch_stage(
+        context.clone(),
+        from_height.saturating_sub(1),
+        fetched_sender,
+    )));
+    let _matcher = AbortOnDrop(tokio::spawn(pipeline::match_stage(
+        context.clone(),
+        fetched_receiver,
+        matched_sender,
+    )));
+
+    let mut matched_events = Vec::new();
+    while let Some(range) = matched_receiver.recv().await {
+        if let MatchedRange::Blocks {
+            to_height: range_to_height,
+            events,
+            ..
+        } = range
+        {
+            // The last range may go past `to_height`, up to the head of the source.
+            matched_events.extend(
+                events
+                    .into_iter()
+                    .filter(|matched_event| matched_event.tx_height <= to_height),
+            );
+            if range_to_height >= to_height {
+                return Ok(matched_events);
+            }
+        }
+    }
+
+    Err(anyhow::anyhow!(
+        "Replay stopped before height: {}",
+        to_height
+    ))
+}
@@ -0,0 +1,597 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+use crate::IndexerConfig;
+
+/// Settings layered from lowest to highest precedence: built-in defaults, the config file, then
+/// the upper-cased environment variable of each field, e.g. `CHAIN_ID` for `chain_id`.
+const DEFAULTS: &[(&str, &str)] = &[
+    ("database_driver", "mongodb"),
+    ("start_height", "0"),
+    ("block_lag_batch_size", "100"),
+    ("fetch_batch_timeout", "100"),
+    ("fetch_single_timeout", "1000"),
+    ("block_notifications_enabled", "false"),
+    ("aws_localstack", "false"),
+    ("kafka_enabled", "false"),
+    ("kafka_brokers", "localhost:9092"),
+    ("kafka_topic", "{chain_id}.{key}"),
+    ("api_enabled", "false"),
+    ("api_address", "0.0.0.0:8080"),
+    ("liveness_max_tick_age", "60000"),
+    ("readiness_max_block_lag", "100"),
+    ("log_format", "text"),
+    ("backfill_workers", "4"),
+    ("backfill_chunk_size", "1000"),
+    ("backfill_threshold", "0"),
+    ("pipeline_buffer_size", "4"),
+    ("confirmation_depth", "0"),
+    ("reorg_detection_enabled", "false"),
+    ("max_reorg_depth", "100"),
+    ("transactions_enabled", "false"),
+    ("attribute_encoding", "auto"),
+    ("tx_search_per_page", "100"),
+    ("tx_search_max_results", "10000"),
+    ("adaptive_batch_size_enabled", "false"),
+    ("min_batch_size", "1"),
+    ("max_batch_size", "1000"),
+    ("batch_target_duration", "2000"),
+    ("batch_target_txs", "2000"),
+    ("websocket_enabled", "false"),
+    ("websocket_poll_interval", "10000"),
+    ("grpc_enabled", "false"),
+    ("leader_election_enabled", "false"),
+    ("lease_duration", "15000"),
+    ("lease_renew_interval", "5000"),
+];
+
+/// Process wide settings, only read from the top level of the config file and the environment.
+const SHARED_FIELDS: &[&str] = &[
+    "database_driver",
+    "database_uri",
+    "database_name",
+    "aws_localstack",
+    "aws_localstack_endpoint",
+    "kafka_brokers",
+    "api_enabled",
+    "api_address",
+    "log_format",
+    "otlp_endpoint",
+];
+
+#[derive(Debug, Clone)]
+pub struct ConfigFieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Every problem found while loading the config, so they can all be fixed at once.
+#[derive(Debug, Clone)]
+pub struct ConfigError {
+    pub errors: Vec<ConfigFieldError>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Invalid indexer config:")?;
+        for error in self.errors.iter() {
+            writeln!(f, "  - {}: {}", error.field, error.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+struct ConfigLoader {
+    file: HashMap<String, String>,
+    /// Environment variables by name.
+    env: HashMap<String, String>,
+    /// Entry of `chains`, overriding the file and the environment.
+    chain: HashMap<String, String>,
+    /// `chains[i].`, prefixed to the errors of fields set by the chain.
+    chain_prefix: String,
+    errors: Vec<ConfigFieldError>,
+}
+
+impl ConfigLoader {
+    fn new(file: HashMap<String, String>, env: HashMap<String, String>) -> Self {
+        ConfigLoader {
+            file,
+            env,
+            chain: HashMap::new(),
+            chain_prefix: String::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, field: &str, message: impl Into<String>) {
+        let field = if self.chain.contains_key(field) {
+            format!("{}{}", self.chain_prefix, field)
+        } else {
+            field.to_string()
+        };
+
+        self.errors.push(ConfigFieldError {
+            field,
+            message: message.into(),
+        });
+    }
+
+    fn raw(&self, field: &str) -> Option<String> {
+        self.chain
+            .get(field)
+            .cloned()
+            .or(self.env.get(&field.to_uppercase()).cloned())
+            .or(self.file.get(field).cloned())
+            .or(DEFAULTS
+                .iter()
+                .find(|(name, _)| *name == field)
+                .map(|(_, value)| value.to_string()))
+            .filter(|value| !value.is_empty())
+    }
+
+    fn optional<T: FromStr>(&mut self, field: &str) -> Option<T>
+    where
+        T::Err: fmt::Display,
+    {
+        let value = self.raw(field)?;
+
+        match value.parse::<T>() {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.error(field, format!("invalid value {:?}: {}", value, err));
+                None
+            }
+        }
+    }
+
+    fn required<T: FromStr + Default>(&mut self, field: &str) -> T
+    where
+        T::Err: fmt::Display,
+    {
+        if self.raw(field).is_none() {
+            self.error(field, "missing");
+            return T::default();
+        }
+
+        self.optional(field).unwrap_or_default()
+    }
+}
+
+#[derive(Default)]
+struct ConfigFile {
+    values: HashMap<String, String>,
+    chains: Vec<HashMap<String, String>>,
+}
+
+fn flatten_values(values: HashMap<String, serde_yaml::Value>) -> HashMap<String, String> {
+    values
+        .into_iter()
+        .filter_map(|(key, value)| match value {
+            serde_yaml::Value::Null => None,
+            serde_yaml::Value::String(value) => Some((key, value)),
+            serde_yaml::Value::Bool(value) => Some((key, value.to_string())),
+            serde_yaml::Value::Number(value) => Some((key, value.to_string())),
+            value => Some((key, serde_yaml::to_string(&value).unwrap_or_default())),
+        })
+        .collect()
+}
+
+fn read_config_file(path: &str) -> Result<ConfigFile, ConfigError> {
+    let file_error = |message: String| ConfigError {
+        errors: vec![ConfigFieldError {
+            field: path.to_string(),
+            message,
+        }],
+    };
+
+    let content = fs::read_to_string(path).map_err(|err| file_error(err.to_string()))?;
+
+    parse_config_file(path, &content)
+}
+
+/// Parses TOML when `path` ends with `.toml`, YAML otherwise.
+fn parse_config_file(path: &str, content: &str) -> Result<ConfigFile, ConfigError> {
+    let file_error = |message: String| ConfigError {
+        errors: vec![ConfigFieldError {
+            field: path.to_string(),
+            message,
+        }],
+    };
+
+    let mut values: HashMap<String, serde_yaml::Value> = if path.ends_with(".toml") {
+        toml::from_str(content).map_err(|err| file_error(err.to_string()))?
+    } else {
+        serde_yaml::from_str(content).map_err(|err| file_error(err.to_string()))?
+    };
+
+    let chains = match values.remove("chains") {
+        Some(chains) => serde_yaml::from_value::<Vec<HashMap<String, serde_yaml::Value>>>(chains)
+            .map_err(|err| ConfigError {
+            errors: vec![ConfigFieldError {
+                field: "chains".to_string(),
+                message: format!("must be a list of chain settings: {}", err),
+            }],
+        })?,
+        None => Vec::new(),
+    };
+
+    Ok(ConfigFile {
+        values: flatten_values(values),
+        chains: chains.into_iter().map(flatten_values).collect(),
+    })
+}
+
+/// Loads the indexer config from the defaults, the optional YAML or TOML file and the
+/// environment, validating every field before failing. The first chain when the file lists
+/// `chains`.
+pub fn load_indexer_config(path: Option<&str>) -> Result<IndexerConfig, ConfigError> {
+    Ok(load_indexer_configs(path)?.remove(0))
+}
+
+/// Loads one config per entry of `chains` in the config file, each entry overriding the top
+/// level settings and the environment, or the top level config alone without `chains`.
+pub fn load_indexer_configs(path: Option<&str>) -> Result<Vec<IndexerConfig>, ConfigError> {
+    let file = match path {
+        Some(path) => read_config_file(path)?,
+        None => ConfigFile::default(),
+    };
+
+    configs_from(file, dotenv::vars().collect())
+}
+
+fn configs_from(
+    file: ConfigFile,
+    env: HashMap<String, String>,
+) -> Result<Vec<IndexerConfig>, ConfigError> {
+    let chains = if file.chains.is_empty() {
+        vec![HashMap::new()]
+    } else {
+        file.chains
+    };
+    let multi_chain = chains.len() > 1 || !chains[0].is_empty();
+
+    let mut indexer_configs: Vec<IndexerConfig> = Vec::new();
+    let mut errors: Vec<ConfigFieldError> = Vec::new();
+    for (index, mut chain) in chains.into_iter().enumerate() {
+        let mut loader = ConfigLoader::new(file.values.clone(), env.clone());
+        loader.chain_prefix = format!("chains[{}].", index);
+
+        if multi_chain {
+            // Every chain names itself, an empty value is reported as missing.
+            chain.entry("chain_id".to_string()).or_default();
+            for field in SHARED_FIELDS
+                .iter()
+                .filter(|field| chain.contains_key(**field))
+            {
+                loader.errors.push(ConfigFieldError {
+                    field: format!("chains[{}].{}", index, field),
+                    message: "can only be set at the top level".to_string(),
+                });
+            }
+        }
+        loader.chain = chain;
+
+        let indexer_config = load_chain_config(&mut loader);
+        if !indexer_config.chain_id.is_empty()
+            && indexer_configs
+                .iter()
+                .any(|other| other.chain_id == indexer_config.chain_id)
+        {
+            loader.error(
+                "chain_id",
+                format!("duplicate chain id: {}", indexer_config.chain_id),
+            );
+        }
+
+        // Errors of the top level settings are the same for every chain.
+        for error in loader.errors {
+            if !errors
+                .iter()
+                .any(|other| other.field == error.field && other.message == error.message)
+            {
+                errors.push(error);
+            }
+        }
+        indexer_configs.push(indexer_config);
+    }
+
+    if !errors.is_empty() {
+        return Err(ConfigError { errors });
+    }
+
+    Ok(indexer_configs)
+}
+
+fn load_chain_config(loader: &mut ConfigLoader) -> IndexerConfig {
+    let indexer_config = IndexerConfig {
+        chain_id: loader.required("chain_id"),
+        rpc_endpoint: loader.required("rpc_endpoint"),
+        database_driver: loader.required("database_driver"),
+        database_uri: loader.required("database_uri"),
+        database_name: loader.required("database_name"),
+        start_height: loader.required("start_height"),
+        block_lag_batch_size: loader.required("block_lag_batch_size"),
+        fetch_batch_timeout: loader.required("fetch_batch_timeout"),
+        fetch_single_timeout: loader.required("fetch_single_timeout"),
+        block_notifications_enabled: loader.required("block_notifications_enabled"),
+        aws_sns_topic: loader.optional("aws_sns_topic"),
+        aws_localstack: loader.required("aws_localstack"),
+        aws_localstack_endpoint: loader.optional("aws_localstack_endpoint"),
+        kafka_enabled: loader.required("kafka_enabled"),
+        kafka_brokers: loader.required("kafka_brokers"),
+        kafka_topic: loader.required("kafka_topic"),
+        api_enabled: loader.required("api_enabled"),
+        api_address: loader.required("api_address"),
+        liveness_max_tick_age: loader.required("liveness_max_tick_age"),
+        readiness_max_block_lag: loader.required("readiness_max_block_lag"),
+        log_format: loader.required("log_format"),
+        otlp_endpoint: loader.optional("otlp_endpoint"),
+        backfill_workers: loader.required("backfill_workers"),
+        backfill_chunk_size: loader.required("backfill_chunk_size"),
+        backfill_threshold: loader.required("backfill_threshold"),
+        pipeline_buffer_size: loader.required("pipeline_buffer_size"),
+        confirmation_depth: loader.required("confirmation_depth"),
+        reorg_detection_enabled: loader.required("reorg_detection_enabled"),
+        max_reorg_depth: loader.required("max_reorg_depth"),
+        transactions_enabled: loader.required("transactions_enabled"),
+        attribute_encoding: loader.required("attribute_encoding"),
+        tx_search_per_page: loader.required("tx_search_per_page"),
+        tx_search_max_results: loader.required("tx_search_max_results"),
+        adaptive_batch_size_enabled: loader.required("adaptive_batch_size_enabled"),
+        min_batch_size: loader.required("min_batch_size"),
+        max_batch_size: loader.required("max_batch_size"),
+        batch_target_duration: loader.required("batch_target_duration"),
+        batch_target_txs: loader.required("batch_target_txs"),
+        websocket_enabled: loader.required("websocket_enabled"),
+        websocket_endpoint: loader.optional("websocket_endpoint"),
+        websocket_poll_interval: loader.required("websocket_poll_interval"),
+        grpc_enabled: loader.required("grpc_enabled"),
+        grpc_endpoint: loader.optional("grpc_endpoint"),
+        matchers: loader.optional("matchers"),
+        leader_election_enabled: loader.required("leader_election_enabled"),
+        lease_duration: loader.required("lease_duration"),
+        lease_renew_interval: loader.required("lease_renew_interval"),
+        instance_id: loader.optional("instance_id"),
+    };
+
+    if indexer_config.block_lag_batch_size == 0 {
+        loader.error("block_lag_batch_size", "must be greater than 0");
+    }
+    if indexer_config.backfill_workers == 0 {
+        loader.error("backfill_workers", "must be greater than 0");
+    }
+    if indexer_config.backfill_chunk_size == 0 {
+        loader.error("backfill_chunk_size", "must be greater than 0");
+    }
+    if indexer_config.pipeline_buffer_size == 0 {
+        loader.error("pipeline_buffer_size", "must be greater than 0");
+    }
+    if indexer_config.tx_search_per_page == 0 {
+        loader.error("tx_search_per_page", "must be greater than 0");
+    }
+    if indexer_config.tx_search_max_results == 0 {
+        loader.error("tx_search_max_results", "must be greater than 0");
+    }
+    if indexer_config.adaptive_batch_size_enabled {
+        if indexer_config.min_batch_size == 0 {
+            loader.error("min_batch_size", "must be greater than 0");
+        }
+        if indexer_config.max_batch_size < indexer_config.min_batch_size {
+            loader.error("max_batch_size", "must not be less than min_batch_size");
+        }
+    }
+    if indexer_config.reorg_detection_enabled && indexer_config.max_reorg_depth == 0 {
+        loader.error(
+            "max_reorg_depth",
+            "must be greater than 0 when reorg_detection_enabled is true",
+        );
+    }
+    if indexer_config.block_notifications_enabled && indexer_config.aws_sns_topic.is_none() {
+        loader.error(
+            "aws_sns_topic",
+            "required when block_notifications_enabled is true",
+        );
+    }
+    if indexer_config.block_notifications_enabled
+        && indexer_config.aws_localstack
+        && indexer_config.aws_localstack_endpoint.is_none()
+    {
+        loader.error(
+            "aws_localstack_endpoint",
+            "required when aws_localstack is true",
+        );
+    }
+    if indexer_config.grpc_enabled && indexer_config.grpc_endpoint.is_none() {
+        loader.error("grpc_endpoint", "required when grpc_enabled is true");
+    }
+    if indexer_config.leader_election_enabled {
+        if indexer_config.lease_renew_interval == 0 {
+            loader.error("lease_renew_interval", "must be greater than 0");
+        }
+        if indexer_config.lease_duration <= indexer_config.lease_renew_interval {
+            loader.error(
+                "lease_duration",
+                "must be greater than lease_renew_interval",
+            );
+        }
+    }
+    if indexer_config
+        .api_address
+        .parse::<std::net::SocketAddr>()
+        .is_err()
+    {
+        loader.error("api_address", "must be a socket address like 0.0.0.0:8080");
+    }
+    if indexer_config.log_format != "text" && indexer_config.log_format != "json" {
+        loader.error("log_format", "must be text or json");
+    }
+    if !["auto", "base64", "plain"].contains(&indexer_config.attribute_encoding.as_str()) {
+        loader.error("attribute_encoding", "must be auto, base64 or plain");
+    }
+
+    indexer_config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUIRED: &str = r#"
+chain_id: "pisco-1"
+rpc_endpoint: "http://localhost:26657"
+database_uri: "mongodb://localhost:27017"
+database_name: "indexer"
+"#;
+
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn load(content: &str, vars: &[(&str, &str)]) -> Result<Vec<IndexerConfig>, ConfigError> {
+        configs_from(
+            parse_config_file("indexer.yaml", content).unwrap(),
+            env(vars),
+        )
+    }
+
+    fn errors(content: &str, vars: &[(&str, &str)]) -> Vec<String> {
+        load(content, vars)
+            .unwrap_err()
+            .errors
+            .iter()
+            .map(|error| format!("{}: {}", error.field, error.message))
+            .collect()
+    }
+
+    #[test]
+    fn layers_defaults_file_and_env() {
+        let content = format!("{}block_lag_batch_size: 50\nstart_height: 5\n", REQUIRED);
+        let indexer_config = load(&content, &[("BLOCK_LAG_BATCH_SIZE", "70")])
+            .unwrap()
+            .remove(0);
+
+        // Default, file, then env.
+        assert_eq!(indexer_config.kafka_topic, "{chain_id}.{key}");
+        assert_eq!(indexer_config.start_height, 5);
+        assert_eq!(indexer_config.block_lag_batch_size, 70);
+        assert_eq!(indexer_config.chain_id, "pisco-1");
+    }
+
+    #[test]
+    fn empty_env_values_unset_the_field() {
+        let content = format!("{}otlp_endpoint: \"http://localhost:4317\"\n", REQUIRED);
+        let indexer_config = load(&content, &[("OTLP_ENDPOINT", "")]).unwrap().remove(0);
+
+        assert_eq!(indexer_config.otlp_endpoint, None);
+        assert_eq!(
+            errors(REQUIRED, &[("LOG_FORMAT", "")]),
+            vec!["log_format: missing", "log_format: must be text or json"]
+        );
+    }
+
+    #[test]
+    fn reads_toml_files() {
+        let file = parse_config_file(
+            "indexer.toml",
+            "chain_id = \"pisco-1\"\nrpc_endpoint = \"http://localhost:26657\"\n\
+             database_uri = \"mongodb://localhost:27017\"\ndatabase_name = \"indexer\"\n\
+             block_lag_batch_size = 20\n",
+        )
+        .unwrap();
+        let indexer_config = configs_from(file, HashMap::new()).unwrap().remove(0);
+
+        assert_eq!(indexer_config.block_lag_batch_size, 20);
+    }
+
+    #[test]
+    fn reports_every_invalid_field() {
+        let content = "rpc_endpoint: \"http://localhost:26657\"\nlog_format: \"xml\"\n";
+
+        assert_eq!(
+            errors(content, &[("START_HEIGHT", "abc")]),
+            vec![
+                "chain_id: missing",
+                "database_uri: missing",
+                "database_name: missing",
+                "start_height: invalid value \"abc\": invalid digit found in string",
+                "log_format: must be text or json",
+            ]
+        );
+    }
+
+    #[test]
+    fn displays_every_error() {
+        let err = load("rpc_endpoint: \"http://localhost:26657\"\n", &[]).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid indexer config:\n  - chain_id: missing\n  - database_uri: missing\n  \
+             - database_name: missing\n"
+        );
+    }
+
+    #[test]
+    fn reports_unparsable_files_by_path() {
+        let err = parse_config_file("indexer.yaml", "chains: 1\n")
+            .err()
+            .unwrap();
+
+        assert_eq!(err.errors[0].field, "chains");
+        assert!(parse_config_file("indexer.yaml", "- not a map\n").is_err());
+    }
+
+    #[test]
+    fn chains_override_the_top_level_and_the_env() {
+        let content = format!(
+            "{}chains:\n  - chain_id: \"neutron-1\"\n    block_lag_batch_size: 10\n  \
+             - chain_id: \"osmosis-1\"\n",
+            REQUIRED
+        );
+        let indexer_configs = load(&content, &[("BLOCK_LAG_BATCH_SIZE", "70")]).unwrap();
+
+        assert_eq!(indexer_configs.len(), 2);
+        assert_eq!(indexer_configs[0].chain_id, "neutron-1");
+        assert_eq!(indexer_configs[0].block_lag_batch_size, 10);
+        assert_eq!(indexer_configs[1].chain_id, "osmosis-1");
+        assert_eq!(indexer_configs[1].block_lag_batch_size, 70);
+        assert_eq!(indexer_configs[1].database_name, "indexer");
+    }
+
+    #[test]
+    fn prefixes_errors_of_chain_fields() {
+        let content = format!(
+            "{}log_format: \"xml\"\nchains:\n  - chain_id: \"neutron-1\"\n    \
+             start_height: \"abc\"\n  - database_name: \"other\"\n",
+            REQUIRED
+        );
+
+        assert_eq!(
+            errors(&content, &[]),
+            vec![
+                "chains[0].start_height: invalid value \"abc\": invalid digit found in string",
+                "log_format: must be text or json",
+                "chains[1].database_name: can only be set at the top level",
+                "chains[1].chain_id: missing",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_chain_ids() {
+        let content = format!(
+            "{}chains:\n  - chain_id: \"neutron-1\"\n  - chain_id: \"neutron-1\"\n",
+            REQUIRED
+        );
+
+        assert_eq!(
+            errors(&content, &[]),
+            vec!["chains[1].chain_id: duplicate chain id: neutron-1"]
+        );
+    }
+}
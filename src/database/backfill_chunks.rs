@@ -0,0 +1,128 @@
+use futures::TryStreamExt;
+use mongodb::bson::doc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::IndexerContext;
+
+static BACKFILL_CHUNKS_COLLECTION: &str = "backfill_chunks";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackfillChunkDocument {
+    pub _id: mongodb::bson::oid::ObjectId,
+    #[serde(rename = "chainId")]
+    pub chain_id: String,
+    #[serde(rename = "fromHeight")]
+    pub from_height: u64,
+    #[serde(rename = "toHeight")]
+    pub to_height: u64,
+    #[serde(rename = "completedAt")]
+    pub completed_at: mongodb::bson::DateTime,
+}
+
+/// Completed chunks overlapping `from_height..=to_height`.
+pub async fn fetch_completed_chunks(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+) -> mongodb::error::Result<Vec<BackfillChunkDocument>> {
+    context
+        .database
+        .collection::<BackfillChunkDocument>(BACKFILL_CHUNKS_COLLECTION)
+        .find(
+            doc! {
+                "chainId": &context.indexer_config.chain_id,
+                "fromHeight": { "$lte": to_height as i64 },
+                "toHeight": { "$gte": from_height as i64 },
+            },
+            None,
+        )
+        .await?
+        .try_collect()
+        .await
+}
+
+pub async fn save_completed_chunk(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+) -> mongodb::error::Result<()> {
+    context
+        .database
+        .collection::<BackfillChunkDocument>(BACKFILL_CHUNKS_COLLECTION)
+        .insert_one(
+            BackfillChunkDocument {
+                _id: mongodb::bson::oid::ObjectId::new(),
+                chain_id: context.indexer_config.chain_id.to_owned(),
+                from_height,
+                to_height,
+                completed_at: mongodb::bson::DateTime::from(std::time::SystemTime::now()),
+            },
+            None,
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete_chunks_up_to_height(
+    context: Arc<IndexerContext>,
+    to_height: u64,
+) -> mongodb::error::Result<()> {
+    context
+        .database
+        .collection::<BackfillChunkDocument>(BACKFILL_CHUNKS_COLLECTION)
+        .delete_many(
+            doc! {
+                "chainId": &context.indexer_config.chain_id,
+                "toHeight": { "$lte": to_height as i64 },
+            },
+            None,
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Drops the chunks within `from_height..=to_height`.
+pub async fn delete_chunks_in_range(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+) -> mongodb::error::Result<()> {
+    context
+        .database
+        .collection::<BackfillChunkDocument>(BACKFILL_CHUNKS_COLLECTION)
+        .delete_many(
+            doc! {
+                "chainId": &context.indexer_config.chain_id,
+                "fromHeight": { "$gte": from_height as i64 },
+                "toHeight": { "$lte": to_height as i64 },
+            },
+            None,
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Drops the chunks reaching above `height`, their events are dropped with everything stored
+/// above the indexed height when a replica takes over.
+pub async fn delete_chunks_above_height(
+    context: Arc<IndexerContext>,
+    height: u64,
+) -> mongodb::error::Result<()> {
+    context
+        .database
+        .collection::<BackfillChunkDocument>(BACKFILL_CHUNKS_COLLECTION)
+        .delete_many(
+            doc! {
+                "chainId": &context.indexer_config.chain_id,
+                "toHeight": { "$gt": height as i64 },
+            },
+            None,
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use futures::TryStreamExt;
+use mongodb::bson::doc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::rpc::blockchain::BlockMeta;
+use crate::IndexerContext;
+
+static BLOCKS_COLLECTION: &str = "blocks";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlockDocument {
+    pub _id: mongodb::bson::oid::ObjectId,
+    #[serde(rename = "chainId")]
+    pub chain_id: String,
+    pub height: u64,
+    pub hash: String,
+    #[serde(rename = "parentHash")]
+    pub parent_hash: String,
+    #[serde(default)]
+    pub time: String,
+    #[serde(rename = "proposerAddress", default)]
+    pub proposer_address: String,
+}
+
+pub async fn save_blocks(
+    context: Arc<IndexerContext>,
+    blocks: &[BlockMeta],
+) -> mongodb::error::Result<()> {
+    let (first, last) = match (blocks.first(), blocks.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Ok(()),
+    };
+
+    // Replaces hashes left over from a range that was saved but never committed.
+    let collection = context
+        .database
+        .collection::<BlockDocument>(BLOCKS_COLLECTION);
+    collection
+        .delete_many(
+            doc! {
+                "chainId": &context.indexer_config.chain_id,
+                "height": { "$gte": first.height as i64, "$lte": last.height as i64 },
+            },
+            None,
+        )
+        .await?;
+    collection
+        .insert_many(
+            blocks.iter().map(|block| BlockDocument {
+                _id: mongodb::bson::oid::ObjectId::new(),
+                chain_id: context.indexer_config.chain_id.to_owned(),
+                height: block.height,
+                hash: block.hash.to_owned(),
+                parent_hash: block.parent_hash.to_owned(),
+                time: block.time.to_owned(),
+                proposer_address: block.proposer_address.to_owned(),
+            }),
+            None,
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Stored blocks from `from_height` upwards.
+pub async fn fetch_blocks_from_height(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+) -> mongodb::error::Result<Vec<BlockMeta>> {
+    let blocks: Vec<BlockDocument> = context
+        .database
+        .collection::<BlockDocument>(BLOCKS_COLLECTION)
+        .find(
+            doc! {
+                "chainId": &context.indexer_config.chain_id,
+                "height": { "$gte": from_height as i64 },
+            },
+            None,
+        )
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(blocks
+        .into_iter()
+        .map(|block| BlockMeta {
+            height: block.height,
+            hash: block.hash,
+            parent_hash: block.parent_hash,
+            time: block.time,
+            proposer_address: block.proposer_address,
+        })
+        .collect())
+}
+
+pub async fn delete_blocks_above_height(
+    context: Arc<IndexerContext>,
+    height: u64,
+) -> mongodb::error::Result<()> {
+    context
+        .database
+        .collection::<BlockDocument>(BLOCKS_COLLECTION)
+        .delete_many(
+            doc! {
+                "chainId": &context.indexer_config.chain_id,
+                "height": { "$gt": height as i64 },
+            },
+            None,
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete_blocks_below_height(
+    context: Arc<IndexerContext>,
+    height: u64,
+) -> mongodb::error::Result<()> {
+    context
+        .database
+        .collection::<BlockDocument>(BLOCKS_COLLECTION)
+        .delete_many(
+            doc! {
+                "chainId": &context.indexer_config.chain_id,
+                "height": { "$lt": height as i64 },
+            },
+            None,
+        )
+        .await?;
+
+    Ok(())
+}
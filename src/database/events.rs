@@ -1,7 +1,14 @@
-use mongodb::bson::doc;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::options::FindOptions;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Instant;
+use tracing::instrument;
 
+use crate::event_matcher::matcher::MatchedEvent;
+use crate::rpc::blockchain::BlockMeta;
+use crate::rpc::decode::DecodedMessage;
 use crate::IndexerContext;
 
 pub static EVENTS_COLLECTION: &str = "events";
@@ -13,59 +20,252 @@ pub struct EventsDocument {
     pub chain_id: String,
     #[serde(rename = "blockHeight")]
     pub block_height: u64,
+    /// Missing on events indexed before block headers were stored.
+    #[serde(rename = "blockTime", default)]
+    pub block_time: Option<mongodb::bson::DateTime>,
+    #[serde(rename = "blockHash", default)]
+    pub block_hash: Option<String>,
+    #[serde(rename = "proposerAddress", default)]
+    pub proposer_address: Option<String>,
     #[serde(rename = "txHash")]
     pub tx_hash: String,
+    /// Set when transactions are stored.
+    #[serde(rename = "transactionId", default)]
+    pub transaction_id: Option<mongodb::bson::oid::ObjectId>,
     pub key: String,
     pub logs: Vec<EventLog>,
     #[serde(rename = "fullLogs")]
     pub full_logs: Vec<EventLog>,
+    /// The decoded message a failed tx was matched on, its logs are empty.
+    #[serde(rename = "failedMessage", default)]
+    pub failed_message: Option<DecodedMessage>,
+    /// Events indexed before failed txs could be included all succeeded.
+    #[serde(default = "default_success")]
+    pub success: bool,
+    #[serde(default)]
+    pub code: i64,
+    #[serde(default)]
+    pub codespace: String,
+    /// Error of a failed tx.
+    #[serde(default)]
+    pub log: String,
     #[serde(rename = "createdAt")]
     pub created_at: mongodb::bson::DateTime,
 }
 
+fn default_success() -> bool {
+    true
+}
+
+fn event_logs(logs: &[(String, String)]) -> Vec<EventLog> {
+    logs.iter()
+        .map(|(k, v)| EventLog {
+            key: k.to_owned(),
+            value: v.to_owned(),
+        })
+        .collect()
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EventLog {
     pub key: String,
     pub value: String,
 }
 
+#[instrument(name = "save_event", skip_all, fields(key = %matched_event.key, height = block.height))]
 pub async fn save_event(
     context: Arc<IndexerContext>,
-    block_height: u64,
-    tx_hash: String,
-    event_key: String,
-    event_logs: Vec<(String, String)>,
-    event_full_logs: Vec<(String, String)>,
-) -> mongodb::error::Result<()> {
+    matched_event: &MatchedEvent,
+    block: &BlockMeta,
+    transaction_id: Option<mongodb::bson::oid::ObjectId>,
+) -> mongodb::error::Result<EventsDocument> {
+    let event = EventsDocument {
+        _id: mongodb::bson::oid::ObjectId::new(),
+        chain_id: context.as_ref().indexer_config.chain_id.to_owned(),
+        block_height: block.height,
+        block_time: mongodb::bson::DateTime::parse_rfc3339_str(&block.time).ok(),
+        block_hash: Some(block.hash.to_owned()),
+        proposer_address: Some(block.proposer_address.to_owned()),
+        tx_hash: matched_event.tx_hash.to_owned(),
+        transaction_id,
+        key: matched_event.key.to_owned(),
+        logs: event_logs(&matched_event.logs),
+        full_logs: event_logs(&matched_event.full_logs),
+        failed_message: matched_event.failed_message.clone(),
+        success: matched_event.success(),
+        code: matched_event.code,
+        codespace: matched_event.codespace.to_owned(),
+        log: matched_event.log.to_owned(),
+        created_at: mongodb::bson::DateTime::from(std::time::SystemTime::now()),
+    };
+
+    let started_at = Instant::now();
     context
         .database
         .collection::<EventsDocument>(EVENTS_COLLECTION)
-        .insert_one(
-            EventsDocument {
-                _id: mongodb::bson::oid::ObjectId::new(),
-                chain_id: context.as_ref().indexer_config.chain_id.to_owned(),
-                block_height,
-                tx_hash: tx_hash.to_owned(),
-                key: event_key.to_owned(),
-                logs: event_logs
-                    .iter()
-                    .map(|(k, v)| EventLog {
-                        key: k.to_owned(),
-                        value: v.to_owned(),
-                    })
-                    .collect(),
-                full_logs: event_full_logs
-                    .iter()
-                    .map(|(k, v)| EventLog {
-                        key: k.to_owned(),
-                        value: v.to_owned(),
-                    })
-                    .collect(),
-                created_at: mongodb::bson::DateTime::from(std::time::SystemTime::now()),
+        .insert_one(&event, None)
+        .await?;
+    context.metrics.observe_db_write("save_event", started_at);
+
+    Ok(event)
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct EventsQuery {
+    pub chain_id: Option<String>,
+    pub keys: Vec<String>,
+    pub contract_address: Option<String>,
+    pub tx_hash: Option<String>,
+    pub success: Option<bool>,
+    pub from_height: Option<u64>,
+    pub to_height: Option<u64>,
+    /// Attributes that must all be present in the matched `logs`.
+    pub attributes: Vec<(String, String)>,
+    pub descending: bool,
+    /// Resume after the `(blockHeight, _id)` of the last event of the previous page.
+    pub after: Option<(u64, mongodb::bson::oid::ObjectId)>,
+    pub limit: i64,
+}
+
+impl EventsQuery {
+    pub fn to_filter(&self) -> Document {
+        let mut filter = doc! {};
+
+        if let Some(chain_id) = &self.chain_id {
+            filter.insert("chainId", chain_id);
+        }
+        if !self.keys.is_empty() {
+            filter.insert("key", doc! { "$in": &self.keys });
+        }
+        let mut conditions = Vec::new();
+        if let Some(contract_address) = &self.contract_address {
+            conditions.push(doc! {
+                "$or": [
+                    {
+                        "logs": {
+                            "$elemMatch": {
+                                "key": "_contract_address",
+                                "value": contract_address,
+                            }
+                        }
+                    },
+                    { "failedMessage.contract": contract_address },
+                ]
+            });
+        }
+        let mut log_filters = Vec::new();
+        for (key, value) in self.attributes.iter() {
+            log_filters.push(doc! {
+                "$elemMatch": {
+                    "key": key,
+                    "value": value,
+                }
+            });
+        }
+        if !log_filters.is_empty() {
+            filter.insert("logs", doc! { "$all": log_filters });
+        }
+        if let Some(tx_hash) = &self.tx_hash {
+            filter.insert("txHash", tx_hash);
+        }
+        match self.success {
+            // Events stored before failed txs could be included have no success field.
+            Some(true) => {
+                filter.insert("success", doc! { "$ne": false });
+            }
+            Some(false) => {
+                filter.insert("success", false);
+            }
+            None => {}
+        }
+
+        let mut height_filter = doc! {};
+        if let Some(from_height) = self.from_height {
+            height_filter.insert("$gte", from_height as i64);
+        }
+        if let Some(to_height) = self.to_height {
+            height_filter.insert("$lte", to_height as i64);
+        }
+        if !height_filter.is_empty() {
+            filter.insert("blockHeight", height_filter);
+        }
+
+        if let Some((height, id)) = &self.after {
+            let operator = if self.descending { "$lt" } else { "$gt" };
+            conditions.push(doc! {
+                "$or": [
+                    { "blockHeight": { operator: *height as i64 } },
+                    { "blockHeight": *height as i64, "_id": { operator: id } },
+                ]
+            });
+        }
+        // A single condition stays at the top level, several have to share an `$and`.
+        match conditions.len() {
+            0 => {}
+            1 => filter.extend(conditions.pop().unwrap()),
+            _ => {
+                filter.insert("$and", conditions);
+            }
+        }
+
+        filter
+    }
+}
+
+pub async fn delete_events_in_range(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+) -> mongodb::error::Result<u64> {
+    let result = context
+        .database
+        .collection::<EventsDocument>(EVENTS_COLLECTION)
+        .delete_many(
+            doc! {
+                "chainId": &context.indexer_config.chain_id,
+                "blockHeight": { "$gte": from_height as i64, "$lte": to_height as i64 },
+            },
+            None,
+        )
+        .await?;
+
+    Ok(result.deleted_count)
+}
+
+pub async fn delete_events_from_height(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+) -> mongodb::error::Result<u64> {
+    let result = context
+        .database
+        .collection::<EventsDocument>(EVENTS_COLLECTION)
+        .delete_many(
+            doc! {
+                "chainId": &context.indexer_config.chain_id,
+                "blockHeight": { "$gte": from_height as i64 },
             },
             None,
         )
         .await?;
 
-    Ok(())
+    Ok(result.deleted_count)
+}
+
+pub async fn fetch_events(
+    context: Arc<IndexerContext>,
+    query: &EventsQuery,
+) -> mongodb::error::Result<Vec<EventsDocument>> {
+    let direction = if query.descending { -1 } else { 1 };
+    let options = FindOptions::builder()
+        .sort(doc! { "blockHeight": direction, "_id": direction })
+        .limit(query.limit)
+        .build();
+
+    context
+        .database
+        .collection::<EventsDocument>(EVENTS_COLLECTION)
+        .find(query.to_filter(), options)
+        .await?
+        .try_collect()
+        .await
 }
@@ -0,0 +1,99 @@
+use mongodb::bson::doc;
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::options::UpdateOptions;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::IndexerContext;
+
+static LEASES_COLLECTION: &str = "leases";
+
+/// Duplicate key, returned when the upsert races another replica for the same lease.
+const DUPLICATE_KEY_ERROR: i32 = 11000;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LeaseDocument {
+    /// The chain id, so a chain has at most one lease.
+    pub _id: String,
+    pub holder: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: mongodb::bson::DateTime,
+    #[serde(rename = "renewedAt")]
+    pub renewed_at: mongodb::bson::DateTime,
+}
+
+/// Takes the lease of the chain for `duration` when it is free, expired or already held by
+/// `holder`. Returns whether `holder` holds it now. Expiries are in the server time, so replica
+/// clocks don't have to agree.
+pub async fn try_acquire_lease(
+    context: Arc<IndexerContext>,
+    holder: &str,
+    duration: Duration,
+) -> mongodb::error::Result<bool> {
+    let result = context
+        .database
+        .collection::<LeaseDocument>(LEASES_COLLECTION)
+        .update_one(
+            doc! {
+                "_id": &context.indexer_config.chain_id,
+                "$or": [
+                    { "holder": holder },
+                    { "$expr": { "$lte": ["$expiresAt", "$$NOW"] } },
+                ],
+            },
+            vec![doc! {
+                "$set": {
+                    "holder": holder,
+                    "expiresAt": { "$add": ["$$NOW", duration.as_millis() as i64] },
+                    "renewedAt": "$$NOW",
+                },
+            }],
+            UpdateOptions::builder().upsert(true).build(),
+        )
+        .await;
+
+    match result {
+        Ok(_) => Ok(true),
+        // The lease exists and another replica holds it, so the filter missed and the upsert
+        // collided with its id.
+        Err(err) if is_duplicate_key(&err) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Expires the lease right away if `holder` still holds it, so a standby replica takes over
+/// without waiting for it to expire.
+pub async fn release_lease(
+    context: Arc<IndexerContext>,
+    holder: &str,
+) -> mongodb::error::Result<()> {
+    context
+        .database
+        .collection::<LeaseDocument>(LEASES_COLLECTION)
+        .update_one(
+            doc! {
+                "_id": &context.indexer_config.chain_id,
+                "holder": holder,
+            },
+            vec![doc! {
+                "$set": {
+                    "expiresAt": "$$NOW",
+                },
+            }],
+            None,
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn is_duplicate_key(err: &mongodb::error::Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) => {
+            write_error.code == DUPLICATE_KEY_ERROR
+        }
+        ErrorKind::Command(command_error) => command_error.code == DUPLICATE_KEY_ERROR,
+        _ => false,
+    }
+}
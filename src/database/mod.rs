@@ -1,8 +1,12 @@
 use mongodb::Client;
 use mongodb::Database;
 
+pub mod backfill_chunks;
+pub mod blocks;
 pub mod events;
+pub mod leases;
 pub mod stream_status;
+pub mod transactions;
 
 pub async fn connect(
     _database_driver: &str,
@@ -1,6 +1,9 @@
+use futures::TryStreamExt;
 use mongodb::bson::doc;
+use mongodb::Database;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Instant;
 
 use crate::IndexerContext;
 
@@ -8,28 +11,35 @@ static STATUS_COLLECTION: &str = "status";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StatusDocument {
-    _id: mongodb::bson::oid::ObjectId,
+    pub _id: mongodb::bson::oid::ObjectId,
     #[serde(rename = "chainId")]
-    chain_id: String,
+    pub chain_id: String,
     #[serde(rename = "indexedHeight")]
-    indexed_height: u64,
+    pub indexed_height: u64,
     #[serde(rename = "updatedAt")]
-    updated_at: mongodb::bson::DateTime,
+    pub updated_at: mongodb::bson::DateTime,
 }
 
-pub async fn fetch_indexer_status(
-    context: Arc<IndexerContext>,
-) -> mongodb::error::Result<StatusDocument> {
-    let result = context
-        .database
+/// Stored status of `chain_id` without creating it, a chain that never indexed has none.
+pub async fn find_indexer_status(
+    database: &Database,
+    chain_id: &str,
+) -> mongodb::error::Result<Option<StatusDocument>> {
+    database
         .collection::<StatusDocument>(STATUS_COLLECTION)
         .find_one(
             doc! {
-                "chainId": &context.indexer_config.chain_id,
+                "chainId": chain_id,
             },
             None,
         )
-        .await?;
+        .await
+}
+
+pub async fn fetch_indexer_status(
+    context: Arc<IndexerContext>,
+) -> mongodb::error::Result<StatusDocument> {
+    let result = find_indexer_status(&context.database, &context.indexer_config.chain_id).await?;
 
     match result {
         Some(status) => Ok(status),
@@ -52,6 +62,18 @@ pub async fn fetch_indexer_status(
     }
 }
 
+pub async fn fetch_all_indexer_statuses(
+    context: Arc<IndexerContext>,
+) -> mongodb::error::Result<Vec<StatusDocument>> {
+    context
+        .database
+        .collection::<StatusDocument>(STATUS_COLLECTION)
+        .find(doc! {}, None)
+        .await?
+        .try_collect()
+        .await
+}
+
 pub async fn fetch_indexed_height(context: Arc<IndexerContext>) -> mongodb::error::Result<u64> {
     let status = fetch_indexer_status(context).await?;
 
@@ -62,6 +84,7 @@ pub async fn update_indexed_height(
     context: Arc<IndexerContext>,
     indexed_height: u64,
 ) -> mongodb::error::Result<()> {
+    let started_at = Instant::now();
     context
         .database
         .collection::<StatusDocument>(STATUS_COLLECTION)
@@ -78,6 +101,9 @@ pub async fn update_indexed_height(
             None,
         )
         .await?;
+    context
+        .metrics
+        .observe_db_write("update_indexed_height", started_at);
 
     Ok(())
 }
@@ -0,0 +1,111 @@
+use mongodb::bson::doc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::rpc::decode::DecodedTx;
+use crate::rpc::txs::Tx;
+use crate::IndexerContext;
+
+static TRANSACTIONS_COLLECTION: &str = "transactions";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TransactionDocument {
+    pub _id: mongodb::bson::oid::ObjectId,
+    #[serde(rename = "chainId")]
+    pub chain_id: String,
+    #[serde(rename = "blockHeight")]
+    pub block_height: u64,
+    #[serde(rename = "txHash")]
+    pub tx_hash: String,
+    pub index: u64,
+    #[serde(flatten)]
+    pub body: DecodedTx,
+    #[serde(rename = "createdAt")]
+    pub created_at: mongodb::bson::DateTime,
+}
+
+impl TransactionDocument {
+    pub fn new(chain_id: &str, tx: &Tx, body: DecodedTx) -> Self {
+        TransactionDocument {
+            _id: mongodb::bson::oid::ObjectId::new(),
+            chain_id: chain_id.to_owned(),
+            block_height: tx.height,
+            tx_hash: tx.hash.to_owned(),
+            index: tx.index,
+            body,
+            created_at: mongodb::bson::DateTime::from(std::time::SystemTime::now()),
+        }
+    }
+}
+
+/// Replaces any copy of the transactions saved by an earlier run over the same heights.
+pub async fn save_transactions(
+    context: Arc<IndexerContext>,
+    transactions: &[TransactionDocument],
+) -> mongodb::error::Result<()> {
+    if transactions.is_empty() {
+        return Ok(());
+    }
+
+    let started_at = Instant::now();
+    let collection = context
+        .database
+        .collection::<TransactionDocument>(TRANSACTIONS_COLLECTION);
+    let tx_hashes: Vec<&str> = transactions
+        .iter()
+        .map(|transaction| transaction.tx_hash.as_str())
+        .collect();
+    collection
+        .delete_many(
+            doc! {
+                "chainId": &context.indexer_config.chain_id,
+                "txHash": { "$in": tx_hashes },
+            },
+            None,
+        )
+        .await?;
+    collection.insert_many(transactions, None).await?;
+    context
+        .metrics
+        .observe_db_write("save_transactions", started_at);
+
+    Ok(())
+}
+
+pub async fn fetch_transaction(
+    context: Arc<IndexerContext>,
+    chain_id: &str,
+    tx_hash: &str,
+) -> mongodb::error::Result<Option<TransactionDocument>> {
+    context
+        .database
+        .collection::<TransactionDocument>(TRANSACTIONS_COLLECTION)
+        .find_one(
+            doc! {
+                "chainId": chain_id,
+                "txHash": tx_hash,
+            },
+            None,
+        )
+        .await
+}
+
+pub async fn delete_transactions_from_height(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+) -> mongodb::error::Result<u64> {
+    let result = context
+        .database
+        .collection::<TransactionDocument>(TRANSACTIONS_COLLECTION)
+        .delete_many(
+            doc! {
+                "chainId": &context.indexer_config.chain_id,
+                "blockHeight": { "$gte": from_height as i64 },
+            },
+            None,
+        )
+        .await?;
+
+    Ok(result.deleted_count)
+}
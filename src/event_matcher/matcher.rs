@@ -0,0 +1,261 @@
+use tracing::warn;
+
+use crate::event_matcher::matcher_config::{MatcherConfig, MatcherEvent, TxFilter};
+use crate::rpc::decode::{self, DecodedMessage, DecodedTx};
+use crate::rpc::txs::Tx;
+
+pub type Logs = Vec<(String, String)>;
+
+/// A matcher that matched a group of logs of a tx, ready to be stored.
+#[derive(Debug, Clone)]
+pub struct MatchedEvent {
+    pub name: String,
+    pub key: String,
+    pub tx_height: u64,
+    pub tx_hash: String,
+    /// Empty for failed txs, they emit no events.
+    pub logs: Logs,
+    pub full_logs: Logs,
+    /// The decoded message a failed tx was matched on.
+    pub failed_message: Option<DecodedMessage>,
+    pub code: i64,
+    pub codespace: String,
+    /// Only kept for failed txs, where it holds the error.
+    pub log: String,
+}
+
+impl MatchedEvent {
+    pub fn success(&self) -> bool {
+        self.code == 0
+    }
+}
+
+/// Splits the attributes of every `wasm` event of the tx into one group per contract call,
+/// starting at each `_contract_address`, alongside every attribute of the event.
+pub fn group_wasm_logs(tx: &Tx) -> Vec<(Logs, Logs)> {
+    let mut groups = Vec::new();
+
+    let events = match tx.tx_result.events.as_ref() {
+        Some(events) => events,
+        None => return groups,
+    };
+
+    for event in events.iter() {
+        if event.attributes.is_none() || event.type_str.is_none() {
+            continue;
+        }
+
+        let event_type = event.type_str.as_ref().unwrap();
+        if event_type != "wasm" {
+            continue;
+        }
+
+        let mut all_attributes = Vec::new();
+        let mut grouped_attributes = Vec::new();
+        let mut current_group = Vec::new();
+        let event_attributes = event.attributes.as_ref().unwrap();
+        for attribute in event_attributes.iter() {
+            if (attribute.key.is_none()) || (attribute.value.is_none()) {
+                continue;
+            }
+
+            let key = attribute.key.as_ref().unwrap();
+            let value = attribute.value.as_ref().unwrap();
+
+            if key == "_contract_address" {
+                grouped_attributes.push(current_group);
+                current_group = Vec::new();
+            }
+
+            current_group.push((key.to_owned(), value.to_owned()));
+            all_attributes.push((key.to_owned(), value.to_owned()));
+        }
+
+        if !current_group.is_empty() {
+            grouped_attributes.push(current_group);
+        }
+
+        for group in grouped_attributes {
+            groups.push((group, all_attributes.clone()));
+        }
+    }
+
+    groups
+}
+
+/// Failed txs emit no events, so each of their messages is matched on its own against the
+/// `failed_patterns`, as the json of the decoded message.
+pub fn failed_messages(decoded_tx: &DecodedTx) -> Vec<(DecodedMessage, serde_json::Value)> {
+    decoded_tx
+        .all_messages()
+        .into_iter()
+        .map(|message| {
+            let value = serde_json::to_value(message).unwrap_or_default();
+            (message.clone(), value)
+        })
+        .collect()
+}
+
+fn decoded<'a>(tx: &Tx, decoded_tx: &'a mut Option<Option<DecodedTx>>) -> Option<&'a DecodedTx> {
+    decoded_tx
+        .get_or_insert_with(|| match decode::decode_tx(tx) {
+            Ok(decoded_tx) => Some(decoded_tx),
+            Err(err) => {
+                warn!("Failed to decode tx: {}: {}", tx.hash, err);
+                None
+            }
+        })
+        .as_ref()
+}
+
+pub fn matches_patterns(event: &MatcherEvent, logs: &[(String, String)]) -> bool {
+    let patterns_found = logs
+        .iter()
+        .filter(|(key, value)| {
+            event
+                .patterns
+                .iter()
+                .any(|pattern| pattern.key == *key && pattern.value == *value)
+        })
+        .count();
+
+    event.patterns.len() == patterns_found
+}
+
+/// Looks up a dot separated path like `liquidate.user` in a contract msg, numeric segments
+/// index into arrays.
+pub fn json_path<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.')
+        .try_fold(value, |value, segment| match value {
+            serde_json::Value::Array(items) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| items.get(index)),
+            _ => value.get(segment),
+        })
+}
+
+/// Every pattern key is a dot separated path into the decoded message, like `contract` or
+/// `msg.swap.offer_asset.amount`.
+pub fn matches_failed_patterns(event: &MatcherEvent, message: &serde_json::Value) -> bool {
+    event.failed_patterns.iter().all(|pattern| {
+        json_path(message, &pattern.key)
+            .is_some_and(|value| matches_json_value(value, &pattern.value))
+    })
+}
+
+fn matches_json_value(value: &serde_json::Value, expected: &str) -> bool {
+    match value {
+        serde_json::Value::String(value) => value == expected,
+        serde_json::Value::Number(value) => value.to_string() == expected,
+        serde_json::Value::Bool(value) => expected.parse::<bool>() == Ok(*value),
+        _ => false,
+    }
+}
+
+/// Signers are the tx level ones, message types and contract msgs also match messages nested in
+/// authz execs.
+pub fn matches_tx_filter(tx_filter: &TxFilter, decoded_tx: &DecodedTx) -> bool {
+    let signers = tx_filter.signers.is_empty()
+        || decoded_tx
+            .signers
+            .iter()
+            .any(|signer| tx_filter.signers.contains(signer));
+    let message_types = tx_filter.message_types.is_empty()
+        || decoded_tx
+            .all_messages()
+            .into_iter()
+            .any(|message| tx_filter.message_types.contains(&message.type_url));
+    let contract_msg = tx_filter.contract_msg.is_empty()
+        || decoded_tx
+            .all_messages()
+            .into_iter()
+            .filter_map(|message| message.msg.as_ref())
+            .any(|msg| {
+                tx_filter.contract_msg.iter().all(|pattern| {
+                    json_path(msg, &pattern.key)
+                        .is_some_and(|value| matches_json_value(value, &pattern.value))
+                })
+            });
+
+    signers && message_types && contract_msg
+}
+
+pub fn match_tx(matcher_config: &MatcherConfig, tx: &Tx) -> Vec<MatchedEvent> {
+    let mut matched = Vec::new();
+
+    let success = tx.tx_result.code == 0;
+    if !success
+        && !matcher_config
+            .events
+            .iter()
+            .any(|event| event.include_failed)
+    {
+        return matched;
+    }
+
+    // Only decoded once a failed tx or a matcher with a tx filter needs it.
+    let mut decoded_tx: Option<Option<DecodedTx>> = None;
+
+    if success {
+        for (logs, full_logs) in group_wasm_logs(tx) {
+            for event in matcher_config.events.iter() {
+                if matches_patterns(event, &logs) && tx_filter_matches(event, tx, &mut decoded_tx) {
+                    matched.push(MatchedEvent {
+                        logs: logs.clone(),
+                        full_logs: full_logs.clone(),
+                        ..matched_event(event, tx)
+                    });
+                }
+            }
+        }
+    } else {
+        let messages = decoded(tx, &mut decoded_tx)
+            .map(failed_messages)
+            .unwrap_or_default();
+
+        for (message, value) in messages {
+            for event in matcher_config.events.iter() {
+                if event.include_failed
+                    && matches_failed_patterns(event, &value)
+                    && tx_filter_matches(event, tx, &mut decoded_tx)
+                {
+                    matched.push(MatchedEvent {
+                        failed_message: Some(message.clone()),
+                        log: tx.tx_result.log.to_owned(),
+                        ..matched_event(event, tx)
+                    });
+                }
+            }
+        }
+    }
+
+    matched
+}
+
+fn tx_filter_matches(
+    event: &MatcherEvent,
+    tx: &Tx,
+    decoded_tx: &mut Option<Option<DecodedTx>>,
+) -> bool {
+    match event.tx_filter.as_ref() {
+        Some(tx_filter) => decoded(tx, decoded_tx)
+            .is_some_and(|decoded_tx| matches_tx_filter(tx_filter, decoded_tx)),
+        None => true,
+    }
+}
+
+fn matched_event(event: &MatcherEvent, tx: &Tx) -> MatchedEvent {
+    MatchedEvent {
+        name: event.name.to_owned(),
+        key: event.key.to_owned(),
+        tx_height: tx.height,
+        tx_hash: tx.hash.to_owned(),
+        logs: Vec::new(),
+        full_logs: Vec::new(),
+        failed_message: None,
+        code: tx.tx_result.code,
+        codespace: tx.tx_result.codespace.to_owned(),
+        log: String::new(),
+    }
+}
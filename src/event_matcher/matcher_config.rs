@@ -11,6 +11,43 @@ pub struct MatcherEvent {
     pub name: String,
     pub key: String,
     pub patterns: Vec<Pattern>,
+    /// Extra attribute keys exposed as typed fields by the graphql api, on top of the patterns.
+    #[serde(default)]
+    pub fields: Vec<String>,
+    #[serde(default)]
+    pub kafka_topic: Option<String>,
+    /// Conditions on the decoded tx on top of the event patterns.
+    #[serde(default)]
+    pub tx_filter: Option<TxFilter>,
+    /// Also match failed txs, against the `failed_patterns` instead of the `patterns` since
+    /// they emit no events.
+    #[serde(default)]
+    pub include_failed: bool,
+    /// Matched against each decoded message of a failed tx, keys are dot separated paths into
+    /// the message json like `contract` or `msg.swap.offer_asset.amount`.
+    #[serde(default)]
+    pub failed_patterns: Vec<Pattern>,
+}
+
+/// Every non empty condition must hold for the tx of a matched event.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TxFilter {
+    /// One of the tx signers must be listed.
+    #[serde(default)]
+    pub signers: Vec<String>,
+    /// One of the tx messages must have a listed type url.
+    #[serde(default)]
+    pub message_types: Vec<String>,
+    /// One of the contract msgs must match every pattern, keys are dot separated paths into the
+    /// msg json like `liquidate.user`.
+    #[serde(default)]
+    pub contract_msg: Vec<Pattern>,
+}
+
+impl TxFilter {
+    pub fn is_empty(&self) -> bool {
+        self.signers.is_empty() && self.message_types.is_empty() && self.contract_msg.is_empty()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -19,8 +56,54 @@ pub struct Pattern {
     pub value: String,
 }
 
-pub fn load_matcher_config_from_file(file: &str) -> MatcherConfig {
-    let config_file = File::open(file).unwrap();
-    let matcher_config = serde_yaml::from_reader::<File, MatcherConfig>(config_file).unwrap();
-    matcher_config
+pub fn load_matcher_config_from_file(file: &str) -> Result<MatcherConfig, anyhow::Error> {
+    let config_file = File::open(file)
+        .map_err(|err| anyhow::anyhow!("Failed to open matcher config {}: {}", file, err))?;
+    let matcher_config = serde_yaml::from_reader::<File, MatcherConfig>(config_file)
+        .map_err(|err| anyhow::anyhow!("Failed to parse matcher config {}: {}", file, err))?;
+
+    validate_matcher_config(&matcher_config)?;
+
+    Ok(matcher_config)
+}
+
+/// Rejects matchers that would be ambiguous once stored or served, like duplicated keys or keys
+/// without distinct graphql names.
+pub fn validate_matcher_config(matcher_config: &MatcherConfig) -> Result<(), anyhow::Error> {
+    let mut keys = std::collections::HashSet::new();
+
+    for event in matcher_config.events.iter() {
+        if event.key.is_empty() {
+            return Err(anyhow::anyhow!("Matcher {} has an empty key", event.name));
+        }
+        if !keys.insert(event.key.to_owned()) {
+            return Err(anyhow::anyhow!("Matcher key {} is duplicated", event.key));
+        }
+        if event.include_failed && event.failed_patterns.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Matcher {} includes failed txs without failed_patterns",
+                event.key
+            ));
+        }
+        if !event.include_failed && !event.failed_patterns.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Matcher {} has failed_patterns but doesn't include failed txs",
+                event.key
+            ));
+        }
+        if event
+            .tx_filter
+            .as_ref()
+            .is_some_and(|tx_filter| tx_filter.is_empty())
+        {
+            return Err(anyhow::anyhow!(
+                "Matcher {} has an empty tx_filter",
+                event.key
+            ));
+        }
+    }
+
+    crate::api::graphql::validate_names(matcher_config)?;
+
+    Ok(())
 }
@@ -1 +1,2 @@
+pub mod matcher;
 pub mod matcher_config;
@@ -0,0 +1,102 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long a readiness probe result is reused, so frequent probes don't load the node.
+const PROBE_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// State shared by the main loop with the health probes.
+pub struct LoopState {
+    last_tick_at: AtomicU64,
+    head_height: AtomicU64,
+    indexed_height: AtomicU64,
+    /// Whether the block source answered.
+    pub source_probe: ProbeCache,
+    /// Whether the database answered.
+    pub database_probe: ProbeCache,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl LoopState {
+    pub fn new() -> Self {
+        LoopState {
+            last_tick_at: AtomicU64::new(now_millis()),
+            head_height: AtomicU64::new(0),
+            indexed_height: AtomicU64::new(0),
+            source_probe: ProbeCache::new(PROBE_CACHE_TTL),
+            database_probe: ProbeCache::new(PROBE_CACHE_TTL),
+        }
+    }
+
+    pub fn tick(&self, head_height: u64) {
+        self.last_tick_at.store(now_millis(), Ordering::Relaxed);
+        self.head_height.store(head_height, Ordering::Relaxed);
+    }
+
+    /// Ticks without a new head, for loops that wait on something else than the chain.
+    pub fn touch(&self) {
+        self.last_tick_at.store(now_millis(), Ordering::Relaxed);
+    }
+
+    pub fn set_indexed_height(&self, indexed_height: u64) {
+        self.indexed_height.store(indexed_height, Ordering::Relaxed);
+    }
+
+    pub fn millis_since_last_tick(&self) -> u64 {
+        now_millis().saturating_sub(self.last_tick_at.load(Ordering::Relaxed))
+    }
+
+    pub fn head_height(&self) -> u64 {
+        self.head_height.load(Ordering::Relaxed)
+    }
+
+    pub fn indexed_height(&self) -> u64 {
+        self.indexed_height.load(Ordering::Relaxed)
+    }
+
+    pub fn block_lag(&self) -> u64 {
+        self.head_height().saturating_sub(self.indexed_height())
+    }
+}
+
+impl Default for LoopState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Result of a probe, reused until it is older than the ttl.
+pub struct ProbeCache {
+    ttl: Duration,
+    last: Mutex<Option<(Instant, bool)>>,
+}
+
+impl ProbeCache {
+    pub fn new(ttl: Duration) -> Self {
+        ProbeCache {
+            ttl,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// The cached result while fresh, otherwise the result of running `probe`.
+    pub async fn get_or_probe(&self, probe: impl Future<Output = bool>) -> bool {
+        if let Some((checked_at, healthy)) = *self.last.lock().unwrap() {
+            if checked_at.elapsed() < self.ttl {
+                return healthy;
+            }
+        }
+
+        let healthy = probe.await;
+        *self.last.lock().unwrap() = Some((Instant::now(), healthy));
+
+        healthy
+    }
+}
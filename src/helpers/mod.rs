@@ -1,5 +1,9 @@
 use base64::{engine::general_purpose, Engine as _};
 use serde::{self, Deserialize, Deserializer};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::task::{JoinError, JoinHandle};
 
 pub fn deserialize_string_to_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
 where
@@ -10,27 +14,26 @@ where
     s.parse().map_err(serde::de::Error::custom)
 }
 
-pub fn deserialize_base64_to_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s: Option<String> = Option::deserialize(deserializer)?;
-
-    if let Some(s) = s {
-        let base64_decoded = general_purpose::STANDARD.decode(&s);
+pub fn decode_base64_to_string(s: &str) -> Option<String> {
+    let base64_decoded = general_purpose::STANDARD.decode(s).ok()?;
 
-        if base64_decoded.is_err() {
-            return Ok(None);
-        }
+    String::from_utf8(base64_decoded).ok()
+}
 
-        let string_encoded = String::from_utf8(base64_decoded.unwrap());
+/// Spawned task aborted when its handle is dropped, so the tasks of a loop that is stopped don't
+/// keep running on their own.
+pub struct AbortOnDrop<T>(pub JoinHandle<T>);
 
-        if string_encoded.is_err() {
-            return Ok(None);
-        }
+impl<T> Future for AbortOnDrop<T> {
+    type Output = Result<T, JoinError>;
 
-        return Ok(Some(string_encoded.unwrap()));
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
     }
+}
 
-    Ok(None)
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
 }
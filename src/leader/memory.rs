@@ -0,0 +1,72 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::LeaseStore;
+use crate::IndexerContext;
+
+/// Leases kept in memory, for running replicas side by side in tests. Share one store between
+/// the leases of the replicas.
+#[derive(Default)]
+pub struct MemoryLeaseStore {
+    /// Holder and expiry of the lease of each chain.
+    leases: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl MemoryLeaseStore {
+    pub fn new() -> Self {
+        MemoryLeaseStore::default()
+    }
+
+    /// Holder of the lease of the chain, unless it's free or expired.
+    pub fn holder(&self, chain_id: &str) -> Option<String> {
+        self.leases
+            .lock()
+            .unwrap()
+            .get(chain_id)
+            .filter(|(_, expires_at)| Instant::now() < *expires_at)
+            .map(|(holder, _)| holder.clone())
+    }
+}
+
+#[async_trait]
+impl LeaseStore for MemoryLeaseStore {
+    async fn try_acquire(
+        &self,
+        context: Arc<IndexerContext>,
+        holder: &str,
+        duration: Duration,
+    ) -> Result<bool, anyhow::Error> {
+        let now = Instant::now();
+        let mut leases = self.leases.lock().unwrap();
+        let lease = leases
+            .entry(context.indexer_config.chain_id.clone())
+            .or_insert_with(|| (holder.to_string(), now));
+
+        if lease.0 != holder && now < lease.1 {
+            return Ok(false);
+        }
+        *lease = (holder.to_string(), now + duration);
+
+        Ok(true)
+    }
+
+    async fn release(
+        &self,
+        context: Arc<IndexerContext>,
+        holder: &str,
+    ) -> Result<(), anyhow::Error> {
+        if let Some(lease) = self
+            .leases
+            .lock()
+            .unwrap()
+            .get_mut(&context.indexer_config.chain_id)
+            .filter(|(lease_holder, _)| lease_holder == holder)
+        {
+            lease.1 = Instant::now();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::{sleep, timeout_at};
+use tracing::{info, warn};
+
+use crate::database;
+use crate::IndexerContext;
+
+pub mod memory;
+
+pub use memory::MemoryLeaseStore;
+
+/// Where the leases of the chains are kept, shared by every replica.
+#[async_trait]
+pub trait LeaseStore: Send + Sync {
+    /// Takes the lease of the chain for `duration` when it is free, expired or already held by
+    /// `holder`. Returns whether `holder` holds it now.
+    async fn try_acquire(
+        &self,
+        context: Arc<IndexerContext>,
+        holder: &str,
+        duration: Duration,
+    ) -> Result<bool, anyhow::Error>;
+
+    /// Expires the lease of the chain right away if `holder` still holds it.
+    async fn release(
+        &self,
+        context: Arc<IndexerContext>,
+        holder: &str,
+    ) -> Result<(), anyhow::Error>;
+}
+
+/// Leases in the database of the indexer.
+pub struct DatabaseLeaseStore;
+
+#[async_trait]
+impl LeaseStore for DatabaseLeaseStore {
+    async fn try_acquire(
+        &self,
+        context: Arc<IndexerContext>,
+        holder: &str,
+        duration: Duration,
+    ) -> Result<bool, anyhow::Error> {
+        Ok(database::leases::try_acquire_lease(context, holder, duration).await?)
+    }
+
+    async fn release(
+        &self,
+        context: Arc<IndexerContext>,
+        holder: &str,
+    ) -> Result<(), anyhow::Error> {
+        Ok(database::leases::release_lease(context, holder).await?)
+    }
+}
+
+/// Lease of a chain in the store, only the replica holding it indexes the chain.
+///
+/// The lease counts as held locally until `lease_renew_interval` before it expires in the
+/// store, measured from when the request was sent, so a replica that can't renew stops writing
+/// before another one may take over.
+pub struct Lease {
+    holder: String,
+    duration: Duration,
+    renew_interval: Duration,
+    store: Arc<dyn LeaseStore>,
+    valid_until: Mutex<Option<Instant>>,
+}
+
+impl Lease {
+    pub fn new(
+        holder: String,
+        duration: Duration,
+        renew_interval: Duration,
+        store: Arc<dyn LeaseStore>,
+    ) -> Self {
+        Lease {
+            holder,
+            duration,
+            renew_interval,
+            store,
+            valid_until: Mutex::new(None),
+        }
+    }
+
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+
+    pub fn renew_interval(&self) -> Duration {
+        self.renew_interval
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.valid_until
+            .lock()
+            .unwrap()
+            .is_some_and(|valid_until| Instant::now() < valid_until)
+    }
+
+    fn valid_until(&self) -> Option<Instant> {
+        *self.valid_until.lock().unwrap()
+    }
+
+    /// Acquires or renews the lease. A failed request leaves it held until it expires locally.
+    pub async fn try_acquire(&self, context: Arc<IndexerContext>) -> Result<bool, anyhow::Error> {
+        let requested_at = Instant::now();
+        let acquired = self
+            .store
+            .try_acquire(context.clone(), &self.holder, self.duration)
+            .await?;
+
+        *self.valid_until.lock().unwrap() = if acquired {
+            Some(requested_at + self.duration.saturating_sub(self.renew_interval))
+        } else {
+            None
+        };
+        context
+            .metrics
+            .leader
+            .with_label_values(&[&context.indexer_config.chain_id])
+            .set(acquired as i64);
+
+        Ok(acquired)
+    }
+
+    pub async fn release(&self, context: Arc<IndexerContext>) -> Result<(), anyhow::Error> {
+        *self.valid_until.lock().unwrap() = None;
+        context
+            .metrics
+            .leader
+            .with_label_values(&[&context.indexer_config.chain_id])
+            .set(0);
+
+        self.store.release(context, &self.holder).await
+    }
+}
+
+/// `HOSTNAME`, the pod name on kubernetes, with a random suffix so two processes on the same
+/// host never share a lease.
+pub fn default_holder() -> String {
+    let suffix = mongodb::bson::oid::ObjectId::new().to_hex();
+
+    match std::env::var("HOSTNAME") {
+        Ok(hostname) if !hostname.is_empty() => format!("{}-{}", hostname, suffix),
+        _ => suffix,
+    }
+}
+
+/// Waits on standby until the lease of the chain is acquired, ticking the loop so a standby
+/// replica stays alive.
+pub async fn acquire(context: Arc<IndexerContext>) {
+    let lease = context.lease.as_ref().unwrap();
+    let mut standby = false;
+
+    loop {
+        match lease.try_acquire(context.clone()).await {
+            Ok(true) => {
+                info!("Acquired the lease as: {}", lease.holder());
+                return;
+            }
+            Ok(false) => {
+                if !standby {
+                    info!("Lease held by another replica, standing by");
+                    standby = true;
+                }
+            }
+            Err(err) => warn!("Failed to acquire the lease: {}", err),
+        }
+
+        context.loop_state.touch();
+        sleep(lease.renew_interval).await;
+    }
+}
+
+/// Renews the lease every `lease_renew_interval` and returns once it is lost, either taken by
+/// another replica or expired locally after failed renewals.
+pub async fn hold(context: Arc<IndexerContext>) {
+    let lease = context.lease.as_ref().unwrap();
+
+    loop {
+        let valid_until = match lease.valid_until() {
+            Some(valid_until) => valid_until,
+            None => return,
+        };
+        sleep(
+            lease
+                .renew_interval
+                .min(valid_until.saturating_duration_since(Instant::now())),
+        )
+        .await;
+
+        // A renewal still pending once the lease expires locally can't keep it.
+        let renewal = timeout_at(
+            tokio::time::Instant::from_std(valid_until),
+            lease.try_acquire(context.clone()),
+        );
+        match renewal.await {
+            Ok(Ok(true)) => {}
+            Ok(Ok(false)) => {
+                warn!("Lease taken over by another replica");
+                return;
+            }
+            Ok(Err(err)) => warn!("Failed to renew the lease: {}", err),
+            Err(_) => warn!("Lease renewal timed out"),
+        }
+
+        if !lease.is_held() {
+            warn!("Lease expired without renewal");
+            context
+                .metrics
+                .leader
+                .with_label_values(&[&context.indexer_config.chain_id])
+                .set(0);
+            return;
+        }
+    }
+}
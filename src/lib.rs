@@ -1,15 +1,29 @@
-use log::{debug, error, info, warn};
 use serde::Deserialize;
 use serde::Serialize;
 use std::sync::Arc;
-use tokio::time::sleep;
-use tokio::time::Duration;
-
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, info_span, instrument, warn, Instrument};
+
+pub mod api;
+pub mod backfill;
+pub mod commands;
+pub mod config;
 pub mod database;
 pub mod event_matcher;
+pub mod health;
 pub mod helpers;
+pub mod leader;
+pub mod metrics;
 pub mod notifications;
+pub mod pipeline;
+pub mod reorg;
 pub mod rpc;
+pub mod sinks;
+pub mod source;
+pub mod telemetry;
+
+const EVENT_STREAM_CAPACITY: usize = 1024;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct IndexerConfig {
@@ -23,9 +37,51 @@ pub struct IndexerConfig {
     pub fetch_batch_timeout: u64,
     pub fetch_single_timeout: u64,
     pub block_notifications_enabled: bool,
-    pub aws_sns_topic: String,
+    pub aws_sns_topic: Option<String>,
     pub aws_localstack: bool,
-    pub aws_localstack_endpoint: String,
+    pub aws_localstack_endpoint: Option<String>,
+    pub kafka_enabled: bool,
+    pub kafka_brokers: String,
+    pub kafka_topic: String,
+    pub api_enabled: bool,
+    pub api_address: String,
+    pub liveness_max_tick_age: u64,
+    pub readiness_max_block_lag: u64,
+    pub log_format: String,
+    pub otlp_endpoint: Option<String>,
+    pub backfill_workers: u64,
+    pub backfill_chunk_size: u64,
+    pub backfill_threshold: u64,
+    pub pipeline_buffer_size: u64,
+    pub confirmation_depth: u64,
+    pub reorg_detection_enabled: bool,
+    pub max_reorg_depth: u64,
+    pub transactions_enabled: bool,
+    /// `auto` detects it from the node version, `base64` or `plain` force it.
+    pub attribute_encoding: String,
+    pub tx_search_per_page: u64,
+    pub tx_search_max_results: u64,
+    pub adaptive_batch_size_enabled: bool,
+    pub min_batch_size: u64,
+    pub max_batch_size: u64,
+    /// Milliseconds a batch should take at most.
+    pub batch_target_duration: u64,
+    pub batch_target_txs: u64,
+    pub websocket_enabled: bool,
+    /// Defaults to the `/websocket` path of the rpc endpoint.
+    pub websocket_endpoint: Option<String>,
+    pub websocket_poll_interval: u64,
+    /// Fetch txs and headers over grpc instead of the rpc.
+    pub grpc_enabled: bool,
+    pub grpc_endpoint: Option<String>,
+    /// Event matchers file of the chain, overrides `--matchers`.
+    pub matchers: Option<String>,
+    /// Only index while holding the lease of the chain, so replicas can run side by side.
+    pub leader_election_enabled: bool,
+    pub lease_duration: u64,
+    pub lease_renew_interval: u64,
+    /// Lease holder name, defaults to the hostname with a random suffix.
+    pub instance_id: Option<String>,
 }
 
 pub struct MatcherOptions {
@@ -38,27 +94,55 @@ pub struct IndexerContext {
     pub matcher_config: event_matcher::matcher_config::MatcherConfig,
     pub database: mongodb::Database,
     pub sns: Option<aws_sdk_sns::Client>,
+    pub kafka: Option<rdkafka::producer::FutureProducer>,
+    /// Where blocks and their txs are fetched from.
+    pub source: Arc<dyn source::BlockSource>,
+    pub metrics: metrics::Metrics,
+    pub loop_state: health::LoopState,
+    /// How the rpc encodes event attributes, in `tx_search` responses and websocket txs alike.
+    /// Grpc responses carry them as bytes.
+    pub attribute_encoding: rpc::txs::AttributeEncoding,
+    /// Every saved event and rollback is published here for live subscribers of the api.
+    pub event_stream: broadcast::Sender<StreamMessage>,
+    /// Lease of the chain when several replicas index it, `None` indexes unconditionally.
+    pub lease: Option<leader::Lease>,
+    /// Where the completed chunks of backfills are kept.
+    pub chunk_store: Arc<dyn backfill::ChunkStore>,
 }
 
-pub async fn run(indexer_config: IndexerConfig, matcher_options: Option<MatcherOptions>) {
-    info!("Indexer config: {:?}", &indexer_config);
+pub async fn run(
+    indexer_config: IndexerConfig,
+    matcher_options: Option<MatcherOptions>,
+) -> Result<(), anyhow::Error> {
+    let context = build_context(indexer_config, matcher_options).await?;
 
-    let matcher_options = matcher_options.unwrap_or(MatcherOptions {
-        matcher_file_path: None,
-        matcher_config: None,
-    });
+    start(context).await;
 
-    let matcher_config = if let Some(matcher_config) = matcher_options.matcher_config {
-        matcher_config
-    } else {
-        let matcher_file_path = matcher_options
-            .matcher_file_path
-            .unwrap_or("config.yaml".to_string());
+    Ok(())
+}
 
-        debug!("Parsing event matcher config");
-        event_matcher::matcher_config::load_matcher_config_from_file(&matcher_file_path)
-    };
-    info!("Matcher config: {:?}", &matcher_config);
+/// Connections and state shared by every chain indexed by the process.
+#[derive(Clone)]
+pub struct SharedResources {
+    pub database: mongodb::Database,
+    pub sns: Option<aws_sdk_sns::Client>,
+    pub kafka: Option<rdkafka::producer::FutureProducer>,
+    pub metrics: metrics::Metrics,
+    pub event_stream: broadcast::Sender<StreamMessage>,
+}
+
+/// What live subscribers receive, in the order it was written.
+#[derive(Debug, Clone)]
+pub enum StreamMessage {
+    Event(Box<database::events::EventsDocument>),
+    /// The events above the fork height were deleted after a reorg.
+    Rollback(reorg::Rollback),
+}
+
+/// Connects to the database and to the notification sinks enabled by any chain, the process
+/// wide settings are the same in every config.
+pub async fn connect_shared(indexer_configs: &[IndexerConfig]) -> SharedResources {
+    let indexer_config = &indexer_configs[0];
 
     debug!("Connecting to database");
     let database = database::connect(
@@ -70,13 +154,18 @@ pub async fn run(indexer_config: IndexerConfig, matcher_options: Option<MatcherO
     .unwrap();
     info!("Connected to database");
 
-    let sns = if indexer_config.block_notifications_enabled {
+    let sns = if indexer_configs
+        .iter()
+        .any(|indexer_config| indexer_config.block_notifications_enabled)
+    {
         debug!("Connecting to aws sns");
         let aws_shared_config = aws_config::load_from_env().await;
         let mut aws_config_builder = aws_sdk_sns::config::Builder::from(&aws_shared_config);
-        if indexer_config.aws_localstack {
-            aws_config_builder =
-                aws_config_builder.endpoint_url(indexer_config.aws_localstack_endpoint.to_owned());
+        if let (true, Some(endpoint)) = (
+            indexer_config.aws_localstack,
+            indexer_config.aws_localstack_endpoint.as_ref(),
+        ) {
+            aws_config_builder = aws_config_builder.endpoint_url(endpoint);
         }
         let client = Some(aws_sdk_sns::Client::from_conf(aws_config_builder.build()));
         info!("Connected to aws sns");
@@ -85,182 +174,494 @@ pub async fn run(indexer_config: IndexerConfig, matcher_options: Option<MatcherO
         None
     };
 
-    let context = Arc::new(IndexerContext {
-        indexer_config,
+    let kafka = if indexer_configs
+        .iter()
+        .any(|indexer_config| indexer_config.kafka_enabled)
+    {
+        debug!("Connecting to kafka");
+        let producer = sinks::kafka::connect(&indexer_config.kafka_brokers).unwrap();
+        info!("Connected to kafka");
+        Some(producer)
+    } else {
+        None
+    };
+
+    SharedResources {
         database,
         sns,
-        matcher_config,
-    });
-    let context_ref = context.as_ref();
-
-    let last_indexed_height_result =
-        database::stream_status::fetch_indexed_height(context.clone()).await;
-    let mut last_indexed_height = 0;
-    if last_indexed_height_result.is_ok() {
-        last_indexed_height = last_indexed_height_result.unwrap();
+        kafka,
+        metrics: metrics::Metrics::new().unwrap(),
+        event_stream: broadcast::channel(EVENT_STREAM_CAPACITY).0,
     }
+}
 
-    if last_indexed_height < context_ref.indexer_config.start_height {
-        last_indexed_height = context_ref.indexer_config.start_height;
+/// Loads the matchers and connects to the database and notification sinks.
+pub async fn build_context(
+    indexer_config: IndexerConfig,
+    matcher_options: Option<MatcherOptions>,
+) -> Result<Arc<IndexerContext>, anyhow::Error> {
+    let shared = connect_shared(std::slice::from_ref(&indexer_config)).await;
+
+    build_chain_context(indexer_config, matcher_options, &shared).await
+}
+
+/// Builds the context of every chain on the same connections, each chain loads its own
+/// matchers file or the one of `--matchers`.
+pub async fn build_contexts(
+    indexer_configs: Vec<IndexerConfig>,
+    matcher_file_path: Option<String>,
+) -> Result<Vec<Arc<IndexerContext>>, anyhow::Error> {
+    let shared = connect_shared(&indexer_configs).await;
+
+    let mut contexts = Vec::new();
+    for indexer_config in indexer_configs {
+        let matcher_options = MatcherOptions {
+            matcher_file_path: matcher_file_path.clone(),
+            matcher_config: None,
+        };
+        contexts.push(build_chain_context(indexer_config, Some(matcher_options), &shared).await?);
     }
 
-    loop {
-        let last_current_height = rpc::blockchain::fetch_last_block_height(context.clone())
-            .await
-            .unwrap();
+    Ok(contexts)
+}
 
-        let from_block_height = last_indexed_height + 1;
-        let mut to_block_height = from_block_height;
-
-        if last_current_height > last_indexed_height {
-            let block_lag = last_current_height - last_indexed_height;
-            if block_lag > 1 {
-                to_block_height = last_indexed_height + context.indexer_config.block_lag_batch_size;
-                if to_block_height > last_current_height {
-                    to_block_height = last_current_height;
-                }
-                warn!("Currently behind, fetching in batch mode: last_current_height: {}, last_indexed_height: {}, block_lag: {}", last_current_height, last_indexed_height, block_lag);
-            } else {
-                info!("All caught up, keep stream indexing as normal: last_current_height: {}, last_indexed_height: {}", last_current_height, last_indexed_height);
-            }
+/// Loads the matchers of the chain and picks its block source.
+pub async fn build_chain_context(
+    indexer_config: IndexerConfig,
+    matcher_options: Option<MatcherOptions>,
+    shared: &SharedResources,
+) -> Result<Arc<IndexerContext>, anyhow::Error> {
+    info!("Indexer config: {:?}", &indexer_config);
 
-            let txs = rpc::txs::tx_search(context.clone(), from_block_height, to_block_height)
-                .await
-                .unwrap();
+    let matcher_config = load_matcher_config(&indexer_config, matcher_options)?;
 
-            let mut tasks = Vec::new();
-            for tx in txs {
-                tasks.push(tokio::spawn(process_tx(context.clone(), tx)));
+    let attribute_encoding = match indexer_config.attribute_encoding.as_str() {
+        "base64" => rpc::txs::AttributeEncoding::Base64,
+        "plain" => rpc::txs::AttributeEncoding::Plain,
+        // Grpc returns attributes as bytes, only rpc responses are decoded. The txs of the
+        // websocket still come from the rpc, so its encoding is detected for them.
+        _ if indexer_config.grpc_enabled && !indexer_config.websocket_enabled => {
+            rpc::txs::AttributeEncoding::Plain
+        }
+        _ => match rpc::status::fetch_node_version(&indexer_config.rpc_endpoint, &shared.metrics)
+            .await
+        {
+            Ok(version) => {
+                let attribute_encoding = rpc::txs::AttributeEncoding::from_node_version(&version);
+                info!(
+                    "Node version: {}, decoding event attributes as: {:?}",
+                    version, attribute_encoding
+                );
+                attribute_encoding
             }
-            for task in tasks {
-                task.await.unwrap();
+            Err(err) => {
+                warn!(
+                    "Failed to detect node version, guessing the encoding per attribute: {}",
+                    err
+                );
+                rpc::txs::AttributeEncoding::Auto
             }
+        },
+    };
+
+    let source: Arc<dyn source::BlockSource> = if indexer_config.grpc_enabled {
+        let grpc_endpoint = indexer_config.grpc_endpoint.as_ref().unwrap();
+        let channel = rpc::grpc::connect(grpc_endpoint).unwrap();
+        info!("Fetching blocks over grpc from: {}", grpc_endpoint);
+        Arc::new(source::GrpcSource::new(channel))
+    } else {
+        Arc::new(source::RpcSource)
+    };
+
+    let lease = if indexer_config.leader_election_enabled {
+        let holder = indexer_config
+            .instance_id
+            .clone()
+            .unwrap_or_else(leader::default_holder);
+        info!("Leader election enabled, instance id: {}", holder);
+        Some(leader::Lease::new(
+            holder,
+            Duration::from_millis(indexer_config.lease_duration),
+            Duration::from_millis(indexer_config.lease_renew_interval),
+            Arc::new(leader::DatabaseLeaseStore),
+        ))
+    } else {
+        None
+    };
+
+    Ok(Arc::new(IndexerContext {
+        indexer_config,
+        database: shared.database.clone(),
+        sns: shared.sns.clone(),
+        kafka: shared.kafka.clone(),
+        source,
+        matcher_config,
+        event_stream: shared.event_stream.clone(),
+        metrics: shared.metrics.clone(),
+        loop_state: health::LoopState::new(),
+        attribute_encoding,
+        lease,
+        chunk_store: Arc::new(backfill::DatabaseChunkStore),
+    }))
+}
+
+/// Context reading the chain from a recording, for replays without network. The database client
+/// connects lazily and nothing is published.
+pub async fn build_replay_context(
+    mut indexer_config: IndexerConfig,
+    matcher_options: Option<MatcherOptions>,
+    source: source::RecordedSource,
+) -> Result<Arc<IndexerContext>, anyhow::Error> {
+    // The pipeline replays every recorded block as it is, without the stored hashes, the node
+    // or the backfill of the live loop.
+    indexer_config.websocket_enabled = false;
+    indexer_config.reorg_detection_enabled = false;
+    indexer_config.backfill_threshold = 0;
+    indexer_config.confirmation_depth = 0;
+
+    let matcher_config = load_matcher_config(&indexer_config, matcher_options)?;
+
+    let attribute_encoding = match (
+        indexer_config.attribute_encoding.as_str(),
+        &source.node_version,
+    ) {
+        ("base64", _) => rpc::txs::AttributeEncoding::Base64,
+        ("plain", _) => rpc::txs::AttributeEncoding::Plain,
+        (_, Some(version)) => rpc::txs::AttributeEncoding::from_node_version(version),
+        (_, None) => rpc::txs::AttributeEncoding::Auto,
+    };
+
+    let database = database::connect(
+        &indexer_config.database_driver,
+        &indexer_config.database_uri,
+        &indexer_config.database_name,
+    )
+    .await
+    .unwrap();
+
+    Ok(Arc::new(IndexerContext {
+        indexer_config,
+        matcher_config,
+        database,
+        sns: None,
+        kafka: None,
+        source: Arc::new(source),
+        metrics: metrics::Metrics::new().unwrap(),
+        loop_state: health::LoopState::new(),
+        attribute_encoding,
+        event_stream: broadcast::channel(1).0,
+        lease: None,
+        chunk_store: Arc::new(backfill::MemoryChunkStore::new()),
+    }))
+}
+
+/// Matchers given in the options, else the file of the chain, of `--matchers` or `config.yaml`.
+fn load_matcher_config(
+    indexer_config: &IndexerConfig,
+    matcher_options: Option<MatcherOptions>,
+) -> Result<event_matcher::matcher_config::MatcherConfig, anyhow::Error> {
+    let matcher_options = matcher_options.unwrap_or(MatcherOptions {
+        matcher_file_path: None,
+        matcher_config: None,
+    });
 
-            last_indexed_height = to_block_height;
-            database::stream_status::update_indexed_height(context.clone(), last_indexed_height)
-                .await
-                .unwrap();
-
-            if context.indexer_config.block_notifications_enabled {
-                let result =
-                    notifications::notify_last_indexed_height(context.clone(), last_indexed_height)
-                        .await;
-
-                if result.is_err() {
-                    error!(
-                        "Failed to send notification for height: {}",
-                        last_indexed_height
-                    );
-                }
+    let matcher_config = if let Some(matcher_config) = matcher_options.matcher_config {
+        matcher_config
+    } else {
+        let matcher_file_path = indexer_config
+            .matchers
+            .clone()
+            .or(matcher_options.matcher_file_path)
+            .unwrap_or("config.yaml".to_string());
+
+        debug!("Parsing event matcher config");
+        event_matcher::matcher_config::load_matcher_config_from_file(&matcher_file_path)?
+    };
+    info!("Matcher config: {:?}", &matcher_config);
+
+    Ok(matcher_config)
+}
+
+/// Serves the api when enabled and indexes new blocks forever, resuming from the stored height.
+pub async fn start(context: Arc<IndexerContext>) {
+    start_chains(vec![context]).await;
+}
+
+/// Serves the metrics, the probes and the read api when enabled for every chain and indexes
+/// each chain as an independent task, a chain that stops doesn't stop the others. Returns once
+/// every chain stopped or on ctrl-c or SIGTERM.
+pub async fn start_chains(contexts: Vec<Arc<IndexerContext>>) {
+    let api_config = &contexts[0].indexer_config;
+    let api_address = api_config.api_address.to_owned();
+    let api_enabled = api_config.api_enabled;
+    let api_contexts = contexts.clone();
+    tokio::spawn(async move {
+        if api_enabled {
+            info!("Serving read api, metrics and probes on: {}", api_address);
+        } else {
+            info!("Serving metrics and probes on: {}", api_address);
+        }
+        if let Err(err) = api::serve(api_contexts).await {
+            error!("Api stopped: {}", err);
+        }
+    });
+
+    let mut tasks: Vec<_> = contexts
+        .iter()
+        .map(|context| {
+            let span = info_span!("chain", chain_id = %context.indexer_config.chain_id);
+            helpers::AbortOnDrop(tokio::spawn(index_chain(context.clone()).instrument(span)))
+        })
+        .collect();
+    let indexing = async {
+        for task in tasks.iter_mut() {
+            if let Err(err) = task.await {
+                error!("Indexing stopped: {}", err);
             }
         }
+    };
 
-        if to_block_height - from_block_height > 1 {
-            sleep(Duration::from_millis(
-                context.indexer_config.fetch_batch_timeout,
-            ))
-            .await;
-        } else {
-            sleep(Duration::from_millis(
-                context.indexer_config.fetch_single_timeout,
-            ))
-            .await;
+    // Returning on a signal lets the caller flush the telemetry before the process exits.
+    let shutdown = tokio::select! {
+        _ = indexing => false,
+        _ = shutdown_signal() => true,
+    };
+    if !shutdown {
+        return;
+    }
+
+    info!("Shutting down");
+    for task in tasks.iter() {
+        task.0.abort();
+    }
+    for task in tasks.iter_mut() {
+        let _ = task.await;
+    }
+    // Writes sent before the abort may still be applied by the database. Holding the lease one
+    // more renew interval keeps a standby replica from taking over before they land, then
+    // releasing lets it take over without waiting for the lease to expire.
+    let leases: Vec<_> = contexts
+        .iter()
+        .filter_map(|context| Some((context, context.lease.as_ref()?)))
+        .collect();
+    if let Some(renew_interval) = leases.iter().map(|(_, lease)| lease.renew_interval()).max() {
+        tokio::time::sleep(renew_interval).await;
+    }
+    for (context, lease) in leases {
+        if let Err(err) = lease.release(context.clone()).await {
+            warn!("Failed to release the lease: {}", err);
         }
     }
 }
 
-async fn process_tx(context: Arc<IndexerContext>, tx: rpc::txs::Tx) {
-    if tx.tx_result.code != 0 && tx.tx_result.events.is_none() {
-        return;
+/// Resolves on ctrl-c or SIGTERM.
+async fn shutdown_signal() {
+    let mut terminate =
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).unwrap();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
     }
+}
 
-    debug!("Found tx: height: {}, hash: {}", tx.height, tx.hash);
+/// Indexes new blocks of the chain forever, resuming from the stored height. With leader
+/// election, only while this replica holds the lease of the chain.
+pub async fn index_chain(context: Arc<IndexerContext>) {
+    if context.lease.is_none() {
+        index_from_stored_height(context).await;
+        return;
+    }
 
-    let events = tx.tx_result.events.unwrap();
+    loop {
+        leader::acquire(context.clone()).await;
 
-    for event in events.iter() {
-        if event.attributes.is_none() || event.type_str.is_none() {
-            continue;
+        tokio::select! {
+            _ = take_over(context.clone()) => return,
+            _ = leader::hold(context.clone()) => {
+                warn!("Lost the lease, stopped indexing until it is acquired again");
+            }
         }
+    }
+}
 
-        let event_type = event.type_str.as_ref().unwrap();
-        if event_type != "wasm" {
-            continue;
-        }
+/// Drops what the previous leader stored above the indexed height before it stopped, then
+/// indexes from there.
+async fn take_over(context: Arc<IndexerContext>) {
+    let indexed_height = database::stream_status::fetch_indexed_height(context.clone())
+        .await
+        .unwrap()
+        .max(context.indexer_config.start_height);
+
+    let deleted = database::events::delete_events_from_height(context.clone(), indexed_height + 1)
+        .await
+        .unwrap();
+    database::transactions::delete_transactions_from_height(context.clone(), indexed_height + 1)
+        .await
+        .unwrap();
+    database::backfill_chunks::delete_chunks_above_height(context.clone(), indexed_height)
+        .await
+        .unwrap();
+    if deleted > 0 {
+        info!(
+            "Dropped {} events stored above indexed height: {}",
+            deleted, indexed_height
+        );
+    }
 
-        let mut all_attributes = Vec::new();
-        let mut grouped_attributes = Vec::new();
-        let mut current_group = Vec::new();
-        let event_attributes = event.attributes.as_ref().unwrap();
-        for attribute in event_attributes.iter() {
-            if (attribute.key.is_none()) || (attribute.value.is_none()) {
-                continue;
-            }
+    index_from_stored_height(context).await;
+}
 
-            let key = attribute.key.as_ref().unwrap();
-            let value = attribute.value.as_ref().unwrap();
+async fn index_from_stored_height(context: Arc<IndexerContext>) {
+    let context_ref = context.as_ref();
 
-            if key == "_contract_address" {
-                grouped_attributes.push(current_group);
-                current_group = Vec::new();
-            }
+    let mut last_indexed_height = database::stream_status::fetch_indexed_height(context.clone())
+        .await
+        .unwrap_or(0);
 
-            current_group.push((key.to_owned(), value.to_owned()));
-            all_attributes.push((key.to_owned(), value.to_owned()));
-        }
+    if last_indexed_height < context_ref.indexer_config.start_height {
+        last_indexed_height = context_ref.indexer_config.start_height;
+    }
 
-        if current_group.len() > 0 {
-            grouped_attributes.push(current_group);
-        }
+    context.loop_state.set_indexed_height(last_indexed_height);
 
-        let mut tasks = Vec::new();
-        for log in grouped_attributes {
-            tasks.push(tokio::spawn(process_event_matcher(
-                context.clone(),
-                tx.height,
-                tx.hash.to_owned(),
-                log,
-                all_attributes.clone(),
-            )));
-        }
-        for task in tasks {
-            task.await.unwrap();
-        }
+    pipeline::run(context, last_indexed_height).await;
+}
+
+#[instrument(name = "tx", skip_all, fields(height = tx.height, hash = %tx.hash))]
+pub fn match_tx(
+    context: &IndexerContext,
+    tx: &rpc::txs::Tx,
+) -> Vec<event_matcher::matcher::MatchedEvent> {
+    debug!("Found tx: height: {}, hash: {}", tx.height, tx.hash);
+
+    event_matcher::matcher::match_tx(&context.matcher_config, tx)
+}
+
+/// Decodes the txs that produced a matched event, when transactions are stored. Txs that fail
+/// to decode are skipped and their events stored without a transaction.
+pub fn decode_transactions(
+    context: &IndexerContext,
+    txs: &[rpc::txs::Tx],
+    matched_events: &[event_matcher::matcher::MatchedEvent],
+) -> Vec<database::transactions::TransactionDocument> {
+    if !context.indexer_config.transactions_enabled {
+        return Vec::new();
     }
+
+    txs.iter()
+        .filter(|tx| {
+            matched_events
+                .iter()
+                .any(|matched_event| matched_event.tx_hash == tx.hash)
+        })
+        .filter_map(|tx| match rpc::decode::decode_tx(tx) {
+            Ok(body) => Some(database::transactions::TransactionDocument::new(
+                &context.indexer_config.chain_id,
+                tx,
+                body,
+            )),
+            Err(err) => {
+                warn!("Failed to decode tx: {}: {}", tx.hash, err);
+                None
+            }
+        })
+        .collect()
+}
+
+pub fn transaction_id(
+    transactions: &[database::transactions::TransactionDocument],
+    matched_event: &event_matcher::matcher::MatchedEvent,
+) -> Option<mongodb::bson::oid::ObjectId> {
+    transactions
+        .iter()
+        .find(|transaction| transaction.tx_hash == matched_event.tx_hash)
+        .map(|transaction| transaction._id)
 }
 
-async fn process_event_matcher(
+/// Saves a matched event with the header of its block. Saves run concurrently, the saved
+/// events are published afterwards with `publish_event` in the order they were matched.
+pub async fn store_event(
     context: Arc<IndexerContext>,
-    tx_height: u64,
-    tx_hash: String,
-    grouped_logs: Vec<(String, String)>,
-    full_logs: Vec<(String, String)>,
-) {
-    for event in context.as_ref().matcher_config.events.iter() {
-        let patterns_found = grouped_logs
-            .iter()
-            .filter(|(key, value)| {
-                return event.patterns.iter().any(|pattern| {
-                    return pattern.key == *key && pattern.value == *value;
-                });
-            })
-            .count();
-
-        if event.patterns.len() == patterns_found {
-            info!(
-                "Found event: {} at height: {} with txHash: {} and logs: {:?}",
-                event.name, tx_height, tx_hash, grouped_logs
-            );
-            database::events::save_event(
-                context.clone(),
-                tx_height,
-                tx_hash.to_owned(),
-                event.key.to_owned(),
-                grouped_logs.clone(),
-                full_logs.clone(),
-            )
+    matched_event: event_matcher::matcher::MatchedEvent,
+    block: rpc::blockchain::BlockMeta,
+    transaction_id: Option<mongodb::bson::oid::ObjectId>,
+) -> database::events::EventsDocument {
+    info!(
+        "Found event: {} at height: {} with txHash: {}, success: {} and logs: {:?}",
+        matched_event.name,
+        matched_event.tx_height,
+        matched_event.tx_hash,
+        matched_event.success(),
+        matched_event.logs
+    );
+    let saved_event =
+        database::events::save_event(context.clone(), &matched_event, &block, transaction_id)
             .await
             .unwrap();
+
+    context
+        .metrics
+        .events_matched
+        .with_label_values(&[&context.indexer_config.chain_id, &matched_event.key])
+        .inc();
+
+    saved_event
+}
+
+/// Publishes a saved event to the live subscribers and the kafka sink. Callers publish one event
+/// at a time in `(blockHeight, tx index)` order, which keeps the events of a contract ordered
+/// within its kafka partition.
+pub async fn publish_event(
+    context: Arc<IndexerContext>,
+    saved_event: &database::events::EventsDocument,
+) {
+    // Sending only fails when nobody is subscribed.
+    let _ = context
+        .event_stream
+        .send(StreamMessage::Event(Box::new(saved_event.clone())));
+
+    if context.indexer_config.kafka_enabled {
+        let result = sinks::kafka::produce_event(context.clone(), saved_event).await;
+
+        if let Err(err) = result {
+            context
+                .metrics
+                .notification_failures
+                .with_label_values(&["kafka"])
+                .inc();
+            error!(
+                "Failed to produce event: {} with txHash: {} to kafka: {}",
+                saved_event.key, saved_event.tx_hash, err
+            );
+        }
+    }
+}
+
+/// Tells the live subscribers and the kafka sink that the events above `fork_height` were
+/// deleted, after every event published before the reorg.
+pub async fn publish_rollback(context: Arc<IndexerContext>, fork_height: u64) {
+    let rollback = reorg::Rollback {
+        chain_id: context.indexer_config.chain_id.to_owned(),
+        fork_height,
+    };
+    let _ = context
+        .event_stream
+        .send(StreamMessage::Rollback(rollback.clone()));
+
+    if context.indexer_config.kafka_enabled {
+        let result = sinks::kafka::produce_rollback(context.clone(), &rollback).await;
+
+        if let Err(err) = result {
+            context
+                .metrics
+                .notification_failures
+                .with_label_values(&["kafka"])
+                .inc();
+            error!(
+                "Failed to produce rollback above height: {} to kafka: {}",
+                fork_height, err
+            );
         }
     }
 }
@@ -1,43 +1,271 @@
-use dotenv;
-use env_logger;
-use log::debug;
+use clap::{Parser, Subcommand};
+use std::sync::Arc;
+use tracing::debug;
 
-use cosmos_indexer::IndexerConfig;
+use cosmos_indexer::event_matcher::matcher_config;
+use cosmos_indexer::source::RecordedSource;
+use cosmos_indexer::telemetry::{self, TelemetryOptions};
+use cosmos_indexer::{commands, config, database, IndexerConfig, IndexerContext, MatcherOptions};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// YAML or TOML file with the indexer settings, environment variables take precedence
+    #[arg(long, global = true)]
+    config: Option<String>,
+    /// YAML file with the event matchers
+    #[arg(long, global = true, default_value = "config.yaml")]
+    matchers: String,
+    /// Chain of the backfill, reindex, reset-height, record and replay commands, required with
+    /// several chains
+    #[arg(long, global = true)]
+    chain_id: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Index new blocks forever, resuming from the stored height (default)
+    Run,
+    /// Index a height range without moving the stored height
+    Backfill {
+        #[arg(long)]
+        from: u64,
+        #[arg(long)]
+        to: u64,
+    },
+    /// Drop the stored events from a height and index them again
+    Reindex {
+        /// Defaults to the configured start height
+        #[arg(long)]
+        from: Option<u64>,
+    },
+    /// Print the stored status of every chain
+    Status,
+    /// Check the indexer settings and event matchers
+    ValidateConfig,
+    /// Overwrite the stored indexed height
+    ResetHeight {
+        #[arg(long)]
+        height: u64,
+    },
+    /// Record the raw rpc responses of a height range to a gzip compressed JSONL file
+    Record {
+        #[arg(long)]
+        from: u64,
+        #[arg(long)]
+        to: u64,
+        #[arg(long)]
+        output: String,
+    },
+    /// Match the txs of a recording without network and print every matched event as JSON
+    Replay {
+        #[arg(long)]
+        input: String,
+        /// Defaults to the first recorded height
+        #[arg(long)]
+        from: Option<u64>,
+        /// Defaults to the last recorded height
+        #[arg(long)]
+        to: Option<u64>,
+    },
+}
+
+/// Index of the chain picked with `--chain-id`, or of the only chain.
+fn chain_index(indexer_configs: &[IndexerConfig], chain_id: Option<&String>) -> usize {
+    match chain_id {
+        Some(chain_id) => indexer_configs
+            .iter()
+            .position(|indexer_config| indexer_config.chain_id == *chain_id)
+            .unwrap_or_else(|| {
+                eprintln!("Unknown chain id: {}", chain_id);
+                std::process::exit(1);
+            }),
+        None if indexer_configs.len() == 1 => 0,
+        None => {
+            eprintln!("Several chains are configured, pick one with --chain-id");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Context of the chain picked with `--chain-id`.
+async fn chain_context(
+    indexer_configs: &[IndexerConfig],
+    matchers: &str,
+    chain_id: Option<&String>,
+) -> Arc<IndexerContext> {
+    let indexer_config = indexer_configs[chain_index(indexer_configs, chain_id)].clone();
+
+    let contexts = exit_on_error(
+        cosmos_indexer::build_contexts(vec![indexer_config], Some(matchers.to_owned())).await,
+        "Failed to load the event matchers",
+    );
+
+    contexts.into_iter().next().unwrap()
+}
+
+fn exit_on_error<T>(result: Result<T, anyhow::Error>, message: &str) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("{}: {}", message, err);
+        std::process::exit(1);
+    })
+}
+
+fn validate_config(indexer_configs: &[IndexerConfig], matchers: &str) {
+    let mut valid = true;
+    for indexer_config in indexer_configs.iter() {
+        let matcher_file_path = indexer_config.matchers.as_deref().unwrap_or(matchers);
+        match matcher_config::load_matcher_config_from_file(matcher_file_path) {
+            Ok(matcher_config) => println!(
+                "Config is valid: chain_id: {}, matchers: {}",
+                indexer_config.chain_id,
+                matcher_config.events.len()
+            ),
+            Err(err) => {
+                eprintln!("chain_id: {}: {}", indexer_config.chain_id, err);
+                valid = false;
+            }
+        }
+    }
+    if !valid {
+        std::process::exit(1);
+    }
+}
+
+/// Replays read a recording and skip the connections of the other commands.
+async fn replay(
+    indexer_config: IndexerConfig,
+    matchers: &str,
+    input: &str,
+    from: Option<u64>,
+    to: Option<u64>,
+) {
+    let source = exit_on_error(
+        RecordedSource::open(input),
+        &format!("Failed to read recording: {}", input),
+    );
+    let (first_height, last_height) = source.range().unwrap_or_default();
+    let matcher_options = MatcherOptions {
+        matcher_file_path: Some(matchers.to_owned()),
+        matcher_config: None,
+    };
+    let context = exit_on_error(
+        cosmos_indexer::build_replay_context(indexer_config, Some(matcher_options), source).await,
+        "Failed to load the event matchers",
+    );
+
+    let matched_events = exit_on_error(
+        commands::replay(
+            context,
+            from.unwrap_or(first_height),
+            to.unwrap_or(last_height),
+        )
+        .await,
+        "Failed to replay",
+    );
+    for matched_event in matched_events {
+        println!(
+            "{}",
+            serde_json::json!({
+                "name": matched_event.name,
+                "key": matched_event.key,
+                "blockHeight": matched_event.tx_height,
+                "txHash": matched_event.tx_hash,
+                "success": matched_event.success(),
+                "logs": matched_event.logs,
+            })
+        );
+    }
+}
 
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok();
 
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
-
-    debug!("Parsing indexer config");
-    let indexer_config = IndexerConfig {
-        chain_id: dotenv::var("CHAIN_ID").unwrap(),
-        rpc_endpoint: dotenv::var("RPC_ENDPOINT").unwrap(),
-        database_driver: dotenv::var("DATABASE_DRIVER").unwrap(),
-        database_uri: dotenv::var("DATABASE_URI").unwrap(),
-        database_name: dotenv::var("DATABASE_NAME").unwrap(),
-        start_height: dotenv::var("START_HEIGHT").unwrap().parse::<u64>().unwrap(),
-        block_lag_batch_size: dotenv::var("BLOCK_LAG_BATCH_SIZE")
-            .unwrap()
-            .parse::<u64>()
-            .unwrap(),
-        fetch_batch_timeout: dotenv::var("FETCH_BATCH_TIMEOUT")
-            .unwrap()
-            .parse::<u64>()
-            .unwrap(),
-        fetch_single_timeout: dotenv::var("FETCH_SINGLE_TIMEOUT")
-            .unwrap()
-            .parse::<u64>()
-            .unwrap(),
-        block_notifications_enabled: dotenv::var("BLOCK_NOTIFICATIONS_ENABLED")
-            .unwrap()
-            .parse()
-            .unwrap(),
-        aws_sns_topic: dotenv::var("AWS_SNS_TOPIC").unwrap(),
-        aws_localstack: dotenv::var("AWS_LOCALSTACK").unwrap().parse().unwrap(),
-        aws_localstack_endpoint: dotenv::var("AWS_LOCALSTACK_ENDPOINT").unwrap(),
+    let cli = Cli::parse();
+    let indexer_configs = match config::load_indexer_configs(cli.config.as_deref()) {
+        Ok(indexer_configs) => indexer_configs,
+        Err(err) => {
+            eprint!("{}", err);
+            std::process::exit(1);
+        }
     };
 
-    cosmos_indexer::run(indexer_config, None).await;
+    telemetry::init(&TelemetryOptions {
+        log_format: indexer_configs[0].log_format.to_owned(),
+        otlp_endpoint: indexer_configs[0].otlp_endpoint.to_owned(),
+    })
+    .unwrap();
+    debug!("Parsed indexer config");
+
+    let matchers = cli.matchers.as_str();
+    let chain_id = cli.chain_id.as_ref();
+    let chain_context = || chain_context(&indexer_configs, matchers, chain_id);
+
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => {
+            let contexts = exit_on_error(
+                cosmos_indexer::build_contexts(indexer_configs.clone(), Some(cli.matchers.clone()))
+                    .await,
+                "Failed to load the event matchers",
+            );
+            cosmos_indexer::start_chains(contexts).await
+        }
+        Command::Backfill { from, to } => exit_on_error(
+            commands::backfill(chain_context().await, from, to).await,
+            "Failed to backfill",
+        ),
+        Command::Reindex { from } => {
+            let context = chain_context().await;
+            let from = from.unwrap_or(context.indexer_config.start_height + 1);
+            commands::reindex(context, from).await
+        }
+        Command::Status => {
+            // Database settings are shared by every chain.
+            let indexer_config = &indexer_configs[0];
+            let database = exit_on_error(
+                database::connect(
+                    &indexer_config.database_driver,
+                    &indexer_config.database_uri,
+                    &indexer_config.database_name,
+                )
+                .await
+                .map_err(anyhow::Error::from),
+                "Failed to connect to the database",
+            );
+            for indexer_config in indexer_configs.iter() {
+                let status = exit_on_error(
+                    commands::status(&database, &indexer_config.chain_id).await,
+                    "Failed to read the status",
+                );
+                match status {
+                    Some(status) => println!(
+                        "chain_id: {}, indexed_height: {}, updated_at: {}",
+                        status.chain_id,
+                        status.indexed_height,
+                        status
+                            .updated_at
+                            .try_to_rfc3339_string()
+                            .unwrap_or_default()
+                    ),
+                    None => println!("chain_id: {}, not indexed yet", indexer_config.chain_id),
+                }
+            }
+        }
+        Command::ValidateConfig => validate_config(&indexer_configs, matchers),
+        Command::ResetHeight { height } => {
+            commands::reset_height(chain_context().await, height).await
+        }
+        Command::Record { from, to, output } => {
+            commands::record(chain_context().await, from, to, &output).await
+        }
+        Command::Replay { input, from, to } => {
+            let indexer_config = indexer_configs[chain_index(&indexer_configs, chain_id)].clone();
+            replay(indexer_config, matchers, &input, from, to).await
+        }
+    }
+
+    telemetry::shutdown().await;
 }
@@ -0,0 +1,68 @@
+use std::time::Duration;
+use tracing::info;
+
+use crate::IndexerConfig;
+
+/// Picks how many blocks the live loop fetches per batch. Batches that are slow or hold many txs
+/// halve the size, fast and sparse ones double it, within `min_batch_size..=max_batch_size`.
+pub struct BatchSizer {
+    enabled: bool,
+    size: u64,
+    min_size: u64,
+    max_size: u64,
+    target_duration: Duration,
+    target_txs: u64,
+}
+
+impl BatchSizer {
+    pub fn new(indexer_config: &IndexerConfig) -> Self {
+        let size = if indexer_config.adaptive_batch_size_enabled {
+            indexer_config
+                .block_lag_batch_size
+                .clamp(indexer_config.min_batch_size, indexer_config.max_batch_size)
+        } else {
+            indexer_config.block_lag_batch_size
+        };
+
+        BatchSizer {
+            enabled: indexer_config.adaptive_batch_size_enabled,
+            size,
+            min_size: indexer_config.min_batch_size,
+            max_size: indexer_config.max_batch_size,
+            target_duration: Duration::from_millis(indexer_config.batch_target_duration),
+            target_txs: indexer_config.batch_target_txs,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Adjusts the size after fetching `blocks` blocks holding `txs` txs in `elapsed`.
+    pub fn record(&mut self, blocks: u64, txs: u64, elapsed: Duration) {
+        if !self.enabled {
+            return;
+        }
+
+        let previous_size = self.size;
+        if elapsed > self.target_duration || txs > self.target_txs {
+            self.size = (self.size / 2).max(self.min_size);
+        } else if blocks >= self.size
+            && elapsed < self.target_duration / 2
+            && txs < self.target_txs / 2
+        {
+            // Only full batches tell whether a larger one would still be fast.
+            self.size = (self.size * 2).min(self.max_size);
+        }
+
+        if self.size != previous_size {
+            info!(
+                "Adjusted batch size from: {} to: {}, txs: {}, duration_ms: {}",
+                previous_size,
+                self.size,
+                txs,
+                elapsed.as_millis()
+            );
+        }
+    }
+}
@@ -0,0 +1,599 @@
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{sleep, Duration};
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::database::transactions::TransactionDocument;
+use crate::event_matcher::matcher::MatchedEvent;
+use crate::helpers::AbortOnDrop;
+use crate::reorg::{ForkTooDeep, ReorgCheck, ReorgGuard};
+use crate::rpc::blockchain::{BlockMeta, HeadersCache};
+use crate::rpc::txs::Tx;
+use crate::rpc::websocket::LiveTxs;
+use crate::{backfill, database, notifications, rpc, IndexerContext};
+
+pub mod batch_sizer;
+
+use batch_sizer::BatchSizer;
+
+/// Ranges flow through the stages in the order they were fetched, so the writer commits heights
+/// in order even though fetching the next range overlaps with storing the previous one.
+pub enum FetchedRange {
+    Blocks {
+        from_height: u64,
+        to_height: u64,
+        head_height: u64,
+        blocks: Vec<BlockMeta>,
+        txs: Vec<Tx>,
+    },
+    /// Too far behind for the live loop, the writer backfills the range in parallel instead.
+    Backfill {
+        from_height: u64,
+        to_height: u64,
+        head_height: u64,
+        blocks: Vec<BlockMeta>,
+    },
+    /// The chain reorganized, the writer drops everything indexed above the fork.
+    Rollback { fork_height: u64, head_height: u64 },
+}
+
+pub enum MatchedRange {
+    Blocks {
+        from_height: u64,
+        to_height: u64,
+        head_height: u64,
+        blocks: Vec<BlockMeta>,
+        events: Vec<MatchedEvent>,
+        /// Headers of every height with a matched event.
+        headers: HeadersCache,
+        /// Decoded txs of the matched events, empty unless transactions are stored.
+        transactions: Vec<TransactionDocument>,
+    },
+    Backfill {
+        from_height: u64,
+        to_height: u64,
+        head_height: u64,
+        blocks: Vec<BlockMeta>,
+    },
+    Rollback {
+        fork_height: u64,
+        head_height: u64,
+    },
+}
+
+/// Runs the fetcher, matcher and writer stages until one of them fails.
+pub async fn run(context: Arc<IndexerContext>, last_indexed_height: u64) {
+    let buffer_size = context.indexer_config.pipeline_buffer_size.max(1) as usize;
+    let (fetched_sender, fetched_receiver) = mpsc::channel(buffer_size);
+    let (matched_sender, matched_receiver) = mpsc::channel(buffer_size);
+
+    // Dropping the pipeline, as when the lease is lost, aborts its stages.
+    let fetcher = AbortOnDrop(tokio::spawn(fetch_stage(
+        context.clone(),
+        last_indexed_height,
+        fetched_sender,
+    )));
+    let matcher = AbortOnDrop(tokio::spawn(match_stage(
+        context.clone(),
+        fetched_receiver,
+        matched_sender,
+    )));
+    let writer = AbortOnDrop(tokio::spawn(write_stage(context.clone(), matched_receiver)));
+
+    if let Err(err) = tokio::try_join!(fetcher, matcher, writer) {
+        panic!("Indexing pipeline stopped: {}", err);
+    }
+}
+
+/// Fetches ranges from the block source up to the safe head and sends them on in order, forever.
+pub async fn fetch_stage(
+    context: Arc<IndexerContext>,
+    last_indexed_height: u64,
+    sender: mpsc::Sender<FetchedRange>,
+) {
+    let chain_id = context.indexer_config.chain_id.to_owned();
+    let mut last_fetched_height = last_indexed_height;
+    let mut batch_sizer = BatchSizer::new(&context.indexer_config);
+    let (mut new_blocks, live_txs, _new_blocks_watcher) =
+        if context.indexer_config.websocket_enabled {
+            let (new_blocks_sender, new_blocks) = watch::channel(None);
+            let live_txs = Arc::new(LiveTxs::new());
+            let watcher = AbortOnDrop(tokio::spawn(rpc::websocket::watch_new_blocks(
+                context.clone(),
+                new_blocks_sender,
+                live_txs.clone(),
+            )));
+            (Some(new_blocks), Some(live_txs), Some(watcher))
+        } else {
+            (None, None, None)
+        };
+    let mut reorg_guard = if context.indexer_config.reorg_detection_enabled {
+        Some(
+            ReorgGuard::load(context.clone(), last_indexed_height)
+                .await
+                .unwrap(),
+        )
+    } else {
+        None
+    };
+
+    loop {
+        let last_current_height = context.source.head_height(context.clone()).await.unwrap();
+        context
+            .metrics
+            .chain_head_height
+            .with_label_values(&[&chain_id])
+            .set(last_current_height as i64);
+        context.loop_state.tick(last_current_height);
+
+        // Blocks within the confirmation depth of the head may still be replaced.
+        let last_safe_height =
+            last_current_height.saturating_sub(context.indexer_config.confirmation_depth);
+
+        let from_block_height = last_fetched_height + 1;
+        let mut to_block_height = from_block_height;
+
+        if last_safe_height > last_fetched_height {
+            let block_lag = last_safe_height - last_fetched_height;
+
+            let backfill_threshold = context.indexer_config.backfill_threshold;
+            if backfill_threshold > 0 && block_lag > backfill_threshold {
+                warn!("Far behind, backfilling in parallel: last_current_height: {}, last_fetched_height: {}", last_current_height, last_fetched_height);
+                let blocks = match check_reorg(
+                    context.clone(),
+                    reorg_guard.as_mut(),
+                    from_block_height,
+                    last_safe_height,
+                )
+                .await
+                {
+                    Ok(ReorgCheck::Valid(blocks)) => blocks,
+                    Ok(ReorgCheck::Forked { fork_height }) => {
+                        let range = FetchedRange::Rollback {
+                            fork_height,
+                            head_height: last_current_height,
+                        };
+                        if sender.send(range).await.is_err() {
+                            return;
+                        }
+                        last_fetched_height = fork_height;
+                        continue;
+                    }
+                    Err(err) => {
+                        error!("Failed to check blocks for a reorg, retrying: {}", err);
+                        sleep(Duration::from_millis(
+                            context.indexer_config.fetch_batch_timeout,
+                        ))
+                        .await;
+                        continue;
+                    }
+                };
+
+                let range = FetchedRange::Backfill {
+                    from_height: from_block_height,
+                    to_height: last_safe_height,
+                    head_height: last_current_height,
+                    blocks,
+                };
+                if sender.send(range).await.is_err() {
+                    return;
+                }
+                last_fetched_height = last_safe_height;
+                continue;
+            }
+
+            if block_lag > 1 {
+                to_block_height = last_fetched_height + batch_sizer.size();
+                if to_block_height > last_safe_height {
+                    to_block_height = last_safe_height;
+                }
+                warn!("Currently behind, fetching in batch mode: last_current_height: {}, last_fetched_height: {}, block_lag: {}", last_current_height, last_fetched_height, block_lag);
+            } else {
+                info!("All caught up, keep stream indexing as normal: last_current_height: {}, last_fetched_height: {}", last_current_height, last_fetched_height);
+            }
+
+            context
+                .metrics
+                .batch_size
+                .with_label_values(&[&chain_id])
+                .set((to_block_height - from_block_height + 1) as i64);
+
+            let blocks = match check_reorg(
+                context.clone(),
+                reorg_guard.as_mut(),
+                from_block_height,
+                to_block_height,
+            )
+            .await
+            {
+                Ok(ReorgCheck::Valid(blocks)) => blocks,
+                Ok(ReorgCheck::Forked { fork_height }) => {
+                    let range = FetchedRange::Rollback {
+                        fork_height,
+                        head_height: last_current_height,
+                    };
+                    if sender.send(range).await.is_err() {
+                        return;
+                    }
+                    last_fetched_height = fork_height;
+                    continue;
+                }
+                Err(err) => {
+                    error!("Failed to check blocks for a reorg, retrying: {}", err);
+                    sleep(Duration::from_millis(
+                        context.indexer_config.fetch_batch_timeout,
+                    ))
+                    .await;
+                    continue;
+                }
+            };
+
+            // Txs received over the websocket spare the fetch, they are only complete for
+            // blocks received while connected.
+            let live = live_txs
+                .as_ref()
+                .and_then(|live_txs| live_txs.take(from_block_height, to_block_height, &blocks));
+            let txs = match live {
+                Some(mut txs) => {
+                    rpc::txs::decode_attributes(&mut txs, context.attribute_encoding);
+                    txs
+                }
+                None => {
+                    let started_at = Instant::now();
+                    let txs = context
+                        .source
+                        .txs(context.clone(), from_block_height, to_block_height)
+                        .instrument(info_span!(
+                            "fetch",
+                            from_height = from_block_height,
+                            to_height = to_block_height
+                        ))
+                        .await
+                        .unwrap();
+
+                    batch_sizer.record(
+                        to_block_height - from_block_height + 1,
+                        txs.len() as u64,
+                        started_at.elapsed(),
+                    );
+                    txs
+                }
+            };
+            context
+                .metrics
+                .target_batch_size
+                .with_label_values(&[&chain_id])
+                .set(batch_sizer.size() as i64);
+
+            let range = FetchedRange::Blocks {
+                from_height: from_block_height,
+                to_height: to_block_height,
+                head_height: last_current_height,
+                blocks,
+                txs,
+            };
+            if sender.send(range).await.is_err() {
+                return;
+            }
+            last_fetched_height = to_block_height;
+        }
+
+        if to_block_height - from_block_height > 1 {
+            sleep(Duration::from_millis(
+                context.indexer_config.fetch_batch_timeout,
+            ))
+            .await;
+        } else {
+            wait_for_new_block(context.as_ref(), new_blocks.as_mut()).await;
+        }
+    }
+}
+
+/// Sleeps `fetch_single_timeout`, unless the websocket is connected. Then the next block wakes
+/// the fetcher right away and `websocket_poll_interval` only guards against missed
+/// notifications. Every wake up fetches from the last fetched height, so heights notified while
+/// disconnected are never skipped.
+pub async fn wait_for_new_block(
+    context: &IndexerContext,
+    new_blocks: Option<&mut watch::Receiver<Option<u64>>>,
+) {
+    let new_blocks = match new_blocks {
+        Some(new_blocks) if new_blocks.borrow().is_some() => new_blocks,
+        _ => {
+            sleep(Duration::from_millis(
+                context.indexer_config.fetch_single_timeout,
+            ))
+            .await;
+            return;
+        }
+    };
+
+    let poll_interval = Duration::from_millis(context.indexer_config.websocket_poll_interval);
+    let _ = tokio::time::timeout(poll_interval, new_blocks.changed()).await;
+}
+
+/// Matches the txs of every fetched range and fetches the headers of the matched heights.
+pub async fn match_stage(
+    context: Arc<IndexerContext>,
+    mut receiver: mpsc::Receiver<FetchedRange>,
+    sender: mpsc::Sender<MatchedRange>,
+) {
+    while let Some(range) = receiver.recv().await {
+        let range = match range {
+            FetchedRange::Blocks {
+                from_height,
+                to_height,
+                head_height,
+                blocks,
+                txs,
+            } => {
+                let (events, headers, transactions) = match_txs(
+                    context.clone(),
+                    from_height,
+                    to_height,
+                    blocks.clone(),
+                    &txs,
+                )
+                .await;
+
+                MatchedRange::Blocks {
+                    from_height,
+                    to_height,
+                    head_height,
+                    blocks,
+                    events,
+                    headers,
+                    transactions,
+                }
+            }
+            FetchedRange::Backfill {
+                from_height,
+                to_height,
+                head_height,
+                blocks,
+            } => MatchedRange::Backfill {
+                from_height,
+                to_height,
+                head_height,
+                blocks,
+            },
+            FetchedRange::Rollback {
+                fork_height,
+                head_height,
+            } => MatchedRange::Rollback {
+                fork_height,
+                head_height,
+            },
+        };
+
+        if sender.send(range).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Matches the txs of `from_height..=to_height` and fetches the headers of the matched heights
+/// that aren't among `blocks`. Returns the events with their headers and decoded txs.
+pub(crate) async fn match_txs(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+    blocks: Vec<BlockMeta>,
+    txs: &[Tx],
+) -> (Vec<MatchedEvent>, HeadersCache, Vec<TransactionDocument>) {
+    let span = info_span!("match", from_height, to_height);
+    let events: Vec<MatchedEvent> = span.in_scope(|| {
+        txs.iter()
+            .flat_map(|tx| crate::match_tx(context.as_ref(), tx))
+            .collect()
+    });
+    let transactions = span.in_scope(|| crate::decode_transactions(context.as_ref(), txs, &events));
+
+    let mut headers = HeadersCache::new(blocks);
+    headers
+        .fetch(
+            context.clone(),
+            events.iter().map(|matched_event| matched_event.tx_height),
+        )
+        .instrument(span)
+        .await
+        .unwrap();
+
+    (events, headers, transactions)
+}
+
+/// Saves the matched events of `from_height..=to_height` with their txs and publishes them to
+/// the sinks and subscribers, without committing the height.
+pub(crate) async fn store_events(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+    events: Vec<MatchedEvent>,
+    headers: HeadersCache,
+    transactions: Vec<TransactionDocument>,
+) {
+    database::transactions::save_transactions(context.clone(), &transactions)
+        .await
+        .unwrap();
+
+    let mut tasks = Vec::new();
+    for matched_event in events {
+        let block = headers.get(matched_event.tx_height).cloned().unwrap();
+        let transaction_id = crate::transaction_id(&transactions, &matched_event);
+        tasks.push(AbortOnDrop(tokio::spawn(crate::store_event(
+            context.clone(),
+            matched_event,
+            block,
+            transaction_id,
+        ))));
+    }
+    async {
+        let mut saved_events = Vec::new();
+        for task in tasks {
+            saved_events.push(task.await.unwrap());
+        }
+        for saved_event in saved_events.iter() {
+            crate::publish_event(context.clone(), saved_event).await;
+        }
+    }
+    .instrument(info_span!("write", from_height, to_height))
+    .await;
+}
+
+async fn write_stage(context: Arc<IndexerContext>, mut receiver: mpsc::Receiver<MatchedRange>) {
+    while let Some(range) = receiver.recv().await {
+        match range {
+            MatchedRange::Blocks {
+                from_height,
+                to_height,
+                head_height,
+                blocks,
+                events,
+                headers,
+                transactions,
+            } => {
+                store_events(
+                    context.clone(),
+                    from_height,
+                    to_height,
+                    events,
+                    headers,
+                    transactions,
+                )
+                .await;
+
+                commit_height(context.clone(), to_height, head_height, &blocks).await;
+            }
+            MatchedRange::Backfill {
+                from_height,
+                to_height,
+                head_height,
+                blocks,
+            } => {
+                while let Err(err) =
+                    backfill::backfill_range(context.clone(), from_height, to_height).await
+                {
+                    error!("Backfill failed, retrying: {}", err);
+                    sleep(Duration::from_millis(
+                        context.indexer_config.fetch_batch_timeout,
+                    ))
+                    .await;
+                }
+
+                commit_height(context.clone(), to_height, head_height, &blocks).await;
+                database::backfill_chunks::delete_chunks_up_to_height(context.clone(), to_height)
+                    .await
+                    .unwrap();
+                info!(
+                    "Backfill done, handing over to live indexing: last_indexed_height: {}",
+                    to_height
+                );
+            }
+            MatchedRange::Rollback {
+                fork_height,
+                head_height,
+            } => {
+                let deleted =
+                    database::events::delete_events_from_height(context.clone(), fork_height + 1)
+                        .await
+                        .unwrap();
+                database::transactions::delete_transactions_from_height(
+                    context.clone(),
+                    fork_height + 1,
+                )
+                .await
+                .unwrap();
+                database::blocks::delete_blocks_above_height(context.clone(), fork_height)
+                    .await
+                    .unwrap();
+                context
+                    .metrics
+                    .reorgs
+                    .with_label_values(&[&context.indexer_config.chain_id])
+                    .inc();
+                warn!(
+                    "Chain reorganized, rolled back {} events above fork height: {}",
+                    deleted, fork_height
+                );
+                crate::publish_rollback(context.clone(), fork_height).await;
+
+                commit_height(context.clone(), fork_height, head_height, &[]).await;
+            }
+        }
+    }
+}
+
+/// Checks the range against the known block hashes, any range is valid while reorg detection
+/// is disabled. A fork deeper than `max_reorg_depth` stops indexing, retrying would never find
+/// the fork.
+async fn check_reorg(
+    context: Arc<IndexerContext>,
+    reorg_guard: Option<&mut ReorgGuard>,
+    from_height: u64,
+    to_height: u64,
+) -> Result<ReorgCheck, anyhow::Error> {
+    match reorg_guard {
+        Some(reorg_guard) => match reorg_guard
+            .check_range(context, from_height, to_height)
+            .await
+        {
+            Err(err) if err.is::<ForkTooDeep>() => panic!("{}", err),
+            result => result,
+        },
+        None => Ok(ReorgCheck::Valid(Vec::new())),
+    }
+}
+
+async fn commit_height(
+    context: Arc<IndexerContext>,
+    last_indexed_height: u64,
+    head_height: u64,
+    blocks: &[BlockMeta],
+) {
+    let chain_id = context.indexer_config.chain_id.to_owned();
+
+    // Hashes are saved before the height so a restart never finds an indexed height without them.
+    if !blocks.is_empty() {
+        database::blocks::save_blocks(context.clone(), blocks)
+            .await
+            .unwrap();
+        database::blocks::delete_blocks_below_height(
+            context.clone(),
+            last_indexed_height.saturating_sub(context.indexer_config.max_reorg_depth),
+        )
+        .await
+        .unwrap();
+    }
+    database::stream_status::update_indexed_height(context.clone(), last_indexed_height)
+        .await
+        .unwrap();
+    context
+        .metrics
+        .indexed_height
+        .with_label_values(&[&chain_id])
+        .set(last_indexed_height as i64);
+    context
+        .metrics
+        .block_lag
+        .with_label_values(&[&chain_id])
+        .set(head_height.saturating_sub(last_indexed_height) as i64);
+    context.loop_state.set_indexed_height(last_indexed_height);
+
+    if context.indexer_config.block_notifications_enabled {
+        let result =
+            notifications::notify_last_indexed_height(context.clone(), last_indexed_height).await;
+
+        if result.is_err() {
+            context
+                .metrics
+                .notification_failures
+                .with_label_values(&["sns"])
+                .inc();
+            error!(
+                "Failed to send notification for height: {}",
+                last_indexed_height
+            );
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::database;
+use crate::rpc::blockchain::BlockMeta;
+use crate::IndexerContext;
+
+pub enum ReorgCheck {
+    /// The blocks extend the chain indexed so far.
+    Valid(Vec<BlockMeta>),
+    /// The node's chain diverged above `fork_height`, everything indexed above it is stale.
+    Forked { fork_height: u64 },
+}
+
+/// The node's chain diverged below every block still known, the stale heights can't be found so
+/// indexing stops instead of retrying.
+#[derive(Debug)]
+pub struct ForkTooDeep {
+    pub max_depth: u64,
+    pub lowest_known_height: u64,
+}
+
+impl fmt::Display for ForkTooDeep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Chain reorganized deeper than max_reorg_depth: {}, no common block since height {}, reindex manually",
+            self.max_depth, self.lowest_known_height
+        )
+    }
+}
+
+impl std::error::Error for ForkTooDeep {}
+
+/// Published to live subscribers and kafka after a reorg, consumers drop what they received
+/// above `fork_height`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename = "rollback", rename_all = "camelCase")]
+pub struct Rollback {
+    pub chain_id: String,
+    pub fork_height: u64,
+}
+
+/// Hashes of the most recently fetched blocks, used to notice when the node no longer serves
+/// the chain that was indexed. Only the last `max_reorg_depth` heights are kept, a fork below
+/// them can't be rolled back automatically.
+pub struct ReorgGuard {
+    recent: BTreeMap<u64, String>,
+    max_depth: u64,
+}
+
+impl ReorgGuard {
+    /// Starts from the given blocks, usually the ones fetched last.
+    pub fn new(max_depth: u64, blocks: impl IntoIterator<Item = BlockMeta>) -> Self {
+        let mut guard = ReorgGuard {
+            recent: BTreeMap::new(),
+            max_depth,
+        };
+        guard.record(blocks.into_iter());
+
+        guard
+    }
+
+    /// Restores the hashes stored up to `last_indexed_height`.
+    pub async fn load(
+        context: Arc<IndexerContext>,
+        last_indexed_height: u64,
+    ) -> Result<Self, anyhow::Error> {
+        let max_depth = context.indexer_config.max_reorg_depth;
+        let blocks = database::blocks::fetch_blocks_from_height(
+            context.clone(),
+            last_indexed_height.saturating_sub(max_depth),
+        )
+        .await?;
+
+        Ok(ReorgGuard::new(
+            max_depth,
+            blocks
+                .into_iter()
+                .filter(|block| block.height <= last_indexed_height),
+        ))
+    }
+
+    /// Fetches the blocks of `from_height..=to_height` and checks that the first one extends
+    /// the last known block. Ranges longer than `max_reorg_depth` are far below the head, so
+    /// only their first and last block are fetched.
+    pub async fn check_range(
+        &mut self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<ReorgCheck, anyhow::Error> {
+        let blocks = if to_height - from_height + 1 > self.max_depth {
+            let mut blocks = context
+                .source
+                .block_metas(context.clone(), from_height, from_height)
+                .await?;
+            blocks.extend(
+                context
+                    .source
+                    .block_metas(context.clone(), to_height, to_height)
+                    .await?,
+            );
+            blocks
+        } else {
+            context
+                .source
+                .block_metas(context.clone(), from_height, to_height)
+                .await?
+        };
+
+        for pair in blocks.windows(2) {
+            if pair[1].height == pair[0].height + 1 && pair[1].parent_hash != pair[0].hash {
+                return Err(anyhow::anyhow!(
+                    "Block at height {} does not extend block at height {}, node changed while fetching",
+                    pair[1].height,
+                    pair[0].height
+                ));
+            }
+        }
+
+        let first = match blocks.first() {
+            Some(first) => first,
+            None => return Ok(ReorgCheck::Valid(blocks)),
+        };
+        let parent_height = first.height.saturating_sub(1);
+        if let Some(known_hash) = self.recent.get(&parent_height) {
+            if *known_hash != first.parent_hash {
+                let fork_height = self.find_fork_height(context, parent_height).await?;
+                self.rollback(fork_height);
+
+                return Ok(ReorgCheck::Forked { fork_height });
+            }
+        }
+
+        self.record(blocks.iter().cloned());
+
+        Ok(ReorgCheck::Valid(blocks))
+    }
+
+    /// Walks back from `mismatch_height` to the highest block the node still agrees with.
+    async fn find_fork_height(
+        &self,
+        context: Arc<IndexerContext>,
+        mismatch_height: u64,
+    ) -> Result<u64, anyhow::Error> {
+        let lowest_known_height = *self.recent.keys().next().unwrap_or(&mismatch_height);
+        let node_blocks = context
+            .source
+            .block_metas(context.clone(), lowest_known_height, mismatch_height)
+            .await?;
+
+        for block in node_blocks.iter().rev() {
+            if self.recent.get(&block.height) == Some(&block.hash) {
+                return Ok(block.height);
+            }
+        }
+
+        Err(ForkTooDeep {
+            max_depth: self.max_depth,
+            lowest_known_height,
+        }
+        .into())
+    }
+
+    fn record(&mut self, blocks: impl Iterator<Item = BlockMeta>) {
+        for block in blocks {
+            self.recent.insert(block.height, block.hash);
+        }
+
+        if let Some(&last_height) = self.recent.keys().next_back() {
+            self.recent = self
+                .recent
+                .split_off(&last_height.saturating_sub(self.max_depth));
+        }
+    }
+
+    fn rollback(&mut self, fork_height: u64) {
+        self.recent.split_off(&(fork_height + 1));
+    }
+}
@@ -1,9 +1,14 @@
 use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
+use std::time::Instant;
 
 use crate::helpers;
 use crate::IndexerContext;
 
+/// Most block metas a node returns for a single `/blockchain` request.
+pub const MAX_BLOCK_METAS_PER_REQUEST: u64 = 20;
+
 #[derive(Deserialize)]
 pub struct BlockchainResponse {
     pub result: BlockchainResult,
@@ -13,19 +18,192 @@ pub struct BlockchainResponse {
 pub struct BlockchainResult {
     #[serde(deserialize_with = "helpers::deserialize_string_to_u64")]
     pub last_height: u64,
+    #[serde(default)]
+    pub block_metas: Vec<BlockMetaResponse>,
+}
+
+#[derive(Deserialize)]
+pub struct BlockMetaResponse {
+    pub block_id: BlockId,
+    pub header: BlockHeader,
+}
+
+#[derive(Deserialize)]
+pub struct BlockId {
+    pub hash: String,
+}
+
+#[derive(Deserialize)]
+pub struct BlockHeader {
+    #[serde(deserialize_with = "helpers::deserialize_string_to_u64")]
+    pub height: u64,
+    pub time: String,
+    pub proposer_address: String,
+    pub last_block_id: BlockId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockMeta {
+    pub height: u64,
+    pub hash: String,
+    pub parent_hash: String,
+    /// RFC 3339 timestamp of the block header.
+    pub time: String,
+    pub proposer_address: String,
+}
+
+impl From<BlockMetaResponse> for BlockMeta {
+    fn from(block_meta: BlockMetaResponse) -> Self {
+        BlockMeta {
+            height: block_meta.header.height,
+            hash: block_meta.block_id.hash,
+            parent_hash: block_meta.header.last_block_id.hash,
+            time: block_meta.header.time,
+            proposer_address: block_meta.header.proposer_address,
+        }
+    }
+}
+
+/// Block metas by height, shared by all events of a batch so each header is fetched once from
+/// the block source.
+#[derive(Default)]
+pub struct HeadersCache {
+    headers: HashMap<u64, BlockMeta>,
+}
+
+impl HeadersCache {
+    pub fn new(blocks: Vec<BlockMeta>) -> Self {
+        HeadersCache {
+            headers: blocks
+                .into_iter()
+                .map(|block| (block.height, block))
+                .collect(),
+        }
+    }
+
+    /// Fetches the headers of `heights` that aren't cached yet, heights close to each other
+    /// share a request.
+    pub async fn fetch(
+        &mut self,
+        context: Arc<IndexerContext>,
+        heights: impl IntoIterator<Item = u64>,
+    ) -> Result<(), anyhow::Error> {
+        let missing: BTreeSet<u64> = heights
+            .into_iter()
+            .filter(|height| !self.headers.contains_key(height))
+            .collect();
+
+        let mut windows: Vec<(u64, u64)> = Vec::new();
+        for height in missing {
+            match windows.last_mut() {
+                Some((from_height, to_height))
+                    if height - *from_height < MAX_BLOCK_METAS_PER_REQUEST =>
+                {
+                    *to_height = height
+                }
+                _ => windows.push((height, height)),
+            }
+        }
+
+        for (from_height, to_height) in windows {
+            let blocks = context
+                .source
+                .block_metas(context.clone(), from_height, to_height)
+                .await?;
+            for block in blocks {
+                self.headers.insert(block.height, block);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, height: u64) -> Option<&BlockMeta> {
+        self.headers.get(&height)
+    }
 }
 
 pub async fn fetch_last_block_height(context: Arc<IndexerContext>) -> Result<u64, anyhow::Error> {
-    let client = reqwest::Client::new();
-    let response = client
-        .get(format!(
-            "{}/blockchain",
-            context.indexer_config.rpc_endpoint
-        ))
-        .send()
-        .await?
-        .json::<BlockchainResponse>()
-        .await?;
+    let response = request_blockchain(context.as_ref(), None).await?;
 
     Ok(response.result.last_height)
 }
+
+/// Block metas of `from_height..=to_height` in ascending height order.
+pub async fn fetch_block_metas(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+) -> Result<Vec<BlockMeta>, anyhow::Error> {
+    let mut block_metas = Vec::new();
+
+    let mut min_height = from_height;
+    while min_height <= to_height {
+        let max_height = (min_height + MAX_BLOCK_METAS_PER_REQUEST - 1).min(to_height);
+        let response = request_blockchain(context.as_ref(), Some((min_height, max_height))).await?;
+        block_metas.extend(response.result.block_metas.into_iter().map(BlockMeta::from));
+        min_height = max_height + 1;
+    }
+
+    block_metas.sort_by_key(|block_meta| block_meta.height);
+    block_metas.dedup_by_key(|block_meta| block_meta.height);
+
+    if block_metas.len() as u64 != to_height - from_height + 1 {
+        return Err(anyhow::anyhow!(
+            "Expected {} block metas from height {} to {}, got {}",
+            to_height - from_height + 1,
+            from_height,
+            to_height,
+            block_metas.len()
+        ));
+    }
+
+    Ok(block_metas)
+}
+
+async fn request_blockchain(
+    context: &IndexerContext,
+    range: Option<(u64, u64)>,
+) -> Result<BlockchainResponse, anyhow::Error> {
+    let response = request_blockchain_json(context, range).await?;
+
+    Ok(serde_json::from_value(response)?)
+}
+
+/// Raw `/blockchain` response of `minHeight..=maxHeight`, as kept in recordings.
+pub async fn request_blockchain_json(
+    context: &IndexerContext,
+    range: Option<(u64, u64)>,
+) -> Result<serde_json::Value, anyhow::Error> {
+    let started_at = Instant::now();
+    let response = send_blockchain_request(context, range).await;
+    context.metrics.observe_rpc(
+        &context.indexer_config.rpc_endpoint,
+        "/blockchain",
+        started_at,
+        &response,
+    );
+
+    response
+}
+
+async fn send_blockchain_request(
+    context: &IndexerContext,
+    range: Option<(u64, u64)>,
+) -> Result<serde_json::Value, anyhow::Error> {
+    let client = reqwest::Client::new();
+    let mut request = client.get(format!(
+        "{}/blockchain",
+        context.indexer_config.rpc_endpoint
+    ));
+    if let Some((min_height, max_height)) = range {
+        request = request.query(&[
+            ("minHeight", min_height.to_string()),
+            ("maxHeight", max_height.to_string()),
+        ]);
+    }
+
+    let response = request.send().await?.json::<serde_json::Value>().await?;
+
+    Ok(response)
+}
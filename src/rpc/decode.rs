@@ -0,0 +1,223 @@
+use base64::{engine::general_purpose, Engine as _};
+use cosmos_sdk_proto::cosmos::authz::v1beta1::{MsgExec, MsgGrant, MsgRevoke};
+use cosmos_sdk_proto::cosmos::bank::v1beta1::{MsgMultiSend, MsgSend};
+use cosmos_sdk_proto::cosmos::base::v1beta1::Coin as ProtoCoin;
+use cosmos_sdk_proto::cosmos::distribution::v1beta1::MsgWithdrawDelegatorReward;
+use cosmos_sdk_proto::cosmos::gov;
+use cosmos_sdk_proto::cosmos::staking::v1beta1::{MsgBeginRedelegate, MsgDelegate, MsgUndelegate};
+use cosmos_sdk_proto::cosmos::tx::v1beta1::{AuthInfo, TxBody, TxRaw};
+use cosmos_sdk_proto::cosmwasm::wasm::v1::{
+    MsgExecuteContract, MsgInstantiateContract, MsgMigrateContract, MsgStoreCode,
+};
+use cosmos_sdk_proto::prost::Message;
+use cosmos_sdk_proto::Any;
+use serde::{Deserialize, Serialize};
+
+use super::txs::Tx;
+
+const MSG_EXECUTE_CONTRACT: &str = "/cosmwasm.wasm.v1.MsgExecuteContract";
+const MSG_INSTANTIATE_CONTRACT: &str = "/cosmwasm.wasm.v1.MsgInstantiateContract";
+const MSG_EXEC: &str = "/cosmos.authz.v1beta1.MsgExec";
+
+/// Body of a tx decoded from the protobuf `tx` bytes returned by `tx_search`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DecodedTx {
+    /// Signer of the first message with a known signer.
+    pub sender: Option<String>,
+    /// Signers of the messages, in message order. Only messages with a known signer field
+    /// contribute, see `message_signer`.
+    #[serde(default)]
+    pub signers: Vec<String>,
+    pub memo: String,
+    pub fee: Vec<Coin>,
+    #[serde(rename = "gasLimit")]
+    pub gas_limit: u64,
+    pub messages: Vec<DecodedMessage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Coin {
+    pub denom: String,
+    pub amount: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DecodedMessage {
+    #[serde(rename = "typeUrl")]
+    pub type_url: String,
+    /// Address that signs the message: the sender of contract calls, the grantee of an authz
+    /// exec, or the signer field of the other known message types.
+    pub sender: Option<String>,
+    pub contract: Option<String>,
+    /// Set on contract instantiations.
+    #[serde(rename = "codeId", default)]
+    pub code_id: Option<u64>,
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Contract msg, parsed as JSON from the message bytes. Missing when they aren't valid JSON.
+    pub msg: Option<serde_json::Value>,
+    pub funds: Vec<Coin>,
+    /// Base64 protobuf of messages that aren't decoded.
+    pub value: Option<String>,
+    /// Messages run on behalf of their granters by an authz exec.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<DecodedMessage>,
+}
+
+impl DecodedTx {
+    /// Every message of the tx, including the ones nested in authz execs.
+    pub fn all_messages(&self) -> Vec<&DecodedMessage> {
+        fn collect<'a>(messages: &'a [DecodedMessage], all: &mut Vec<&'a DecodedMessage>) {
+            for message in messages {
+                all.push(message);
+                collect(&message.messages, all);
+            }
+        }
+
+        let mut all = Vec::new();
+        collect(&self.messages, &mut all);
+        all
+    }
+}
+
+impl From<ProtoCoin> for Coin {
+    fn from(coin: ProtoCoin) -> Self {
+        Coin {
+            denom: coin.denom,
+            amount: coin.amount,
+        }
+    }
+}
+
+pub fn decode_tx(tx: &Tx) -> Result<DecodedTx, anyhow::Error> {
+    let bytes = general_purpose::STANDARD.decode(&tx.tx)?;
+    let tx_raw = TxRaw::decode(bytes.as_slice())?;
+    let body = TxBody::decode(tx_raw.body_bytes.as_slice())?;
+    let auth_info = AuthInfo::decode(tx_raw.auth_info_bytes.as_slice())?;
+
+    let messages = body
+        .messages
+        .into_iter()
+        .map(decode_message)
+        .collect::<Result<Vec<DecodedMessage>, anyhow::Error>>()?;
+    let (fee, gas_limit) = match auth_info.fee {
+        Some(fee) => (
+            fee.amount.into_iter().map(Coin::from).collect(),
+            fee.gas_limit,
+        ),
+        None => (Vec::new(), 0),
+    };
+
+    let mut signers: Vec<String> = Vec::new();
+    for sender in messages
+        .iter()
+        .filter_map(|message| message.sender.as_ref())
+    {
+        if !signers.contains(sender) {
+            signers.push(sender.to_owned());
+        }
+    }
+
+    Ok(DecodedTx {
+        sender: signers.first().cloned(),
+        signers,
+        memo: body.memo,
+        fee,
+        gas_limit,
+        messages,
+    })
+}
+
+fn decode_message(message: Any) -> Result<DecodedMessage, anyhow::Error> {
+    match message.type_url.as_str() {
+        MSG_EXECUTE_CONTRACT => {
+            let execute = MsgExecuteContract::decode(message.value.as_slice())?;
+            Ok(DecodedMessage {
+                type_url: message.type_url,
+                sender: Some(execute.sender),
+                contract: Some(execute.contract),
+                code_id: None,
+                label: None,
+                msg: serde_json::from_slice(&execute.msg).ok(),
+                funds: execute.funds.into_iter().map(Coin::from).collect(),
+                value: None,
+                messages: Vec::new(),
+            })
+        }
+        MSG_INSTANTIATE_CONTRACT => {
+            let instantiate = MsgInstantiateContract::decode(message.value.as_slice())?;
+            Ok(DecodedMessage {
+                type_url: message.type_url,
+                sender: Some(instantiate.sender),
+                contract: None,
+                code_id: Some(instantiate.code_id),
+                label: Some(instantiate.label),
+                msg: serde_json::from_slice(&instantiate.msg).ok(),
+                funds: instantiate.funds.into_iter().map(Coin::from).collect(),
+                value: None,
+                messages: Vec::new(),
+            })
+        }
+        MSG_EXEC => {
+            let exec = MsgExec::decode(message.value.as_slice())?;
+            Ok(DecodedMessage {
+                type_url: message.type_url,
+                sender: Some(exec.grantee),
+                contract: None,
+                code_id: None,
+                label: None,
+                msg: None,
+                funds: Vec::new(),
+                value: None,
+                messages: exec
+                    .msgs
+                    .into_iter()
+                    .map(decode_message)
+                    .collect::<Result<Vec<DecodedMessage>, anyhow::Error>>()?,
+            })
+        }
+        _ => Ok(DecodedMessage {
+            sender: message_signer(&message)?,
+            type_url: message.type_url,
+            contract: None,
+            code_id: None,
+            label: None,
+            msg: None,
+            funds: Vec::new(),
+            value: Some(general_purpose::STANDARD.encode(&message.value)),
+            messages: Vec::new(),
+        }),
+    }
+}
+
+/// Signer field of the common sdk and wasm messages that are otherwise kept encoded, `None` for
+/// the message types it isn't known for.
+fn message_signer(message: &Any) -> Result<Option<String>, anyhow::Error> {
+    let value = message.value.as_slice();
+    let signer = match message.type_url.as_str() {
+        "/cosmos.bank.v1beta1.MsgSend" => MsgSend::decode(value)?.from_address,
+        "/cosmos.bank.v1beta1.MsgMultiSend" => {
+            match MsgMultiSend::decode(value)?.inputs.into_iter().next() {
+                Some(input) => input.address,
+                None => return Ok(None),
+            }
+        }
+        "/cosmos.staking.v1beta1.MsgDelegate" => MsgDelegate::decode(value)?.delegator_address,
+        "/cosmos.staking.v1beta1.MsgUndelegate" => MsgUndelegate::decode(value)?.delegator_address,
+        "/cosmos.staking.v1beta1.MsgBeginRedelegate" => {
+            MsgBeginRedelegate::decode(value)?.delegator_address
+        }
+        "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward" => {
+            MsgWithdrawDelegatorReward::decode(value)?.delegator_address
+        }
+        "/cosmos.gov.v1beta1.MsgVote" => gov::v1beta1::MsgVote::decode(value)?.voter,
+        "/cosmos.gov.v1.MsgVote" => gov::v1::MsgVote::decode(value)?.voter,
+        "/cosmos.authz.v1beta1.MsgGrant" => MsgGrant::decode(value)?.granter,
+        "/cosmos.authz.v1beta1.MsgRevoke" => MsgRevoke::decode(value)?.granter,
+        "/cosmwasm.wasm.v1.MsgMigrateContract" => MsgMigrateContract::decode(value)?.sender,
+        "/cosmwasm.wasm.v1.MsgStoreCode" => MsgStoreCode::decode(value)?.sender,
+        _ => return Ok(None),
+    };
+
+    Ok(Some(signer))
+}
@@ -0,0 +1,444 @@
+use base64::{engine::general_purpose, Engine as _};
+use cosmos_sdk_proto::cosmos::base::abci::v1beta1::TxResponse;
+use cosmos_sdk_proto::cosmos::base::query::v1beta1::PageRequest;
+use cosmos_sdk_proto::cosmos::base::tendermint::v1beta1::service_client::ServiceClient as TendermintServiceClient;
+use cosmos_sdk_proto::cosmos::base::tendermint::v1beta1::GetLatestBlockRequest;
+use cosmos_sdk_proto::cosmos::tx::v1beta1::service_client::ServiceClient as TxServiceClient;
+use cosmos_sdk_proto::cosmos::tx::v1beta1::{GetBlockWithTxsRequest, GetTxsEventResponse, OrderBy};
+use cosmos_sdk_proto::tendermint::google::protobuf::Timestamp;
+use cosmos_sdk_proto::tendermint::serializers::timestamp::Rfc3339;
+use futures::{StreamExt, TryStreamExt};
+use std::sync::Arc;
+use std::time::Instant;
+use tonic::codec::ProstCodec;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+
+use super::blockchain::BlockMeta;
+use super::txs::{
+    paginate_tx_search, Attribute, Event, Tx, TxResult, TxSearchOptions, TxSearchResult,
+};
+use crate::IndexerContext;
+
+/// Block headers of a range fetched at the same time, each height is a request.
+const MAX_CONCURRENT_BLOCK_REQUESTS: usize = 20;
+
+/// `GetTxsEventRequest` as of cosmos sdk 0.50, which requires `query` instead of `events`.
+/// Older nodes skip the unknown `query` field and read `events`, so both are sent. Nodes before
+/// 0.46 also skip `page` and `limit` and read `pagination`, so the page is sent both ways too.
+#[derive(Clone, PartialEq, prost::Message)]
+struct GetTxsEventRequest {
+    #[prost(string, repeated, tag = "1")]
+    events: Vec<String>,
+    #[prost(message, optional, tag = "2")]
+    pagination: Option<PageRequest>,
+    #[prost(int32, tag = "3")]
+    order_by: i32,
+    #[prost(uint64, tag = "4")]
+    page: u64,
+    #[prost(uint64, tag = "5")]
+    limit: u64,
+    #[prost(string, tag = "6")]
+    query: String,
+}
+
+/// Lazy channel to `grpc_endpoint`, connected on the first request and reconnected by tonic.
+pub fn connect(grpc_endpoint: &str) -> Result<Channel, anyhow::Error> {
+    let mut endpoint = Endpoint::from_shared(grpc_endpoint.to_owned())?;
+    if endpoint.uri().scheme_str() == Some("https") {
+        endpoint = endpoint.tls_config(ClientTlsConfig::new())?;
+    }
+
+    Ok(endpoint.connect_lazy())
+}
+
+fn grpc_endpoint(context: &IndexerContext) -> &str {
+    context
+        .indexer_config
+        .grpc_endpoint
+        .as_deref()
+        .unwrap_or_default()
+}
+
+pub async fn fetch_last_block_height(
+    context: Arc<IndexerContext>,
+    channel: Channel,
+) -> Result<u64, anyhow::Error> {
+    let mut client = TendermintServiceClient::new(channel);
+
+    let started_at = Instant::now();
+    let response = client.get_latest_block(GetLatestBlockRequest {}).await;
+    context.metrics.observe_rpc(
+        grpc_endpoint(context.as_ref()),
+        "GetLatestBlock",
+        started_at,
+        &response,
+    );
+
+    let header = response?
+        .into_inner()
+        .block
+        .and_then(|block| block.header)
+        .ok_or_else(|| anyhow::anyhow!("Latest block without header"))?;
+
+    Ok(header.height as u64)
+}
+
+/// Block metas of `from_height..=to_height` in ascending height order, from `GetBlockWithTxs`.
+pub async fn fetch_block_metas(
+    context: Arc<IndexerContext>,
+    channel: Channel,
+    from_height: u64,
+    to_height: u64,
+) -> Result<Vec<BlockMeta>, anyhow::Error> {
+    futures::stream::iter(from_height..=to_height)
+        .map(|height| fetch_block_meta(context.clone(), channel.clone(), height))
+        .buffered(MAX_CONCURRENT_BLOCK_REQUESTS)
+        .try_collect()
+        .await
+}
+
+async fn fetch_block_meta(
+    context: Arc<IndexerContext>,
+    channel: Channel,
+    height: u64,
+) -> Result<BlockMeta, anyhow::Error> {
+    let mut client = TxServiceClient::new(channel);
+
+    let started_at = Instant::now();
+    let response = client
+        .get_block_with_txs(GetBlockWithTxsRequest {
+            height: height as i64,
+            // Only the header is needed, the txs come with their results from `GetTxsEvent`.
+            pagination: Some(PageRequest {
+                limit: 1,
+                ..Default::default()
+            }),
+        })
+        .await;
+    context.metrics.observe_rpc(
+        grpc_endpoint(context.as_ref()),
+        "GetBlockWithTxs",
+        started_at,
+        &response,
+    );
+
+    let response = response?.into_inner();
+    let header = response
+        .block
+        .and_then(|block| block.header)
+        .ok_or_else(|| anyhow::anyhow!("Block at height: {} without header", height))?;
+
+    Ok(BlockMeta {
+        height: header.height as u64,
+        hash: response
+            .block_id
+            .map(|block_id| encode_hex(&block_id.hash))
+            .unwrap_or_default(),
+        parent_hash: header
+            .last_block_id
+            .map(|block_id| encode_hex(&block_id.hash))
+            .unwrap_or_default(),
+        time: header.time.map(format_timestamp).unwrap_or_default(),
+        proposer_address: encode_hex(&header.proposer_address),
+    })
+}
+
+/// Every tx of `from_block_height..=to_block_height` with its events, like `rpc::txs::tx_search`.
+pub async fn tx_search(
+    context: Arc<IndexerContext>,
+    channel: Channel,
+    from_block_height: u64,
+    to_block_height: u64,
+) -> Result<Vec<Tx>, anyhow::Error> {
+    let options = TxSearchOptions {
+        per_page: context.indexer_config.tx_search_per_page,
+        max_results: context.indexer_config.tx_search_max_results,
+    };
+
+    let mut txs = paginate_tx_search(
+        |from_height, to_height, page, per_page| {
+            get_txs_event(
+                context.clone(),
+                channel.clone(),
+                from_height,
+                to_height,
+                page,
+                per_page,
+            )
+        },
+        from_block_height,
+        to_block_height,
+        &options,
+    )
+    .await?;
+
+    assign_indexes(&mut txs);
+
+    Ok(txs)
+}
+
+/// Tx responses don't carry their index, they come in block order so it is their position
+/// within the height.
+fn assign_indexes(txs: &mut [Tx]) {
+    let mut previous_height = None;
+    let mut index = 0;
+    for tx in txs.iter_mut() {
+        if previous_height != Some(tx.height) {
+            previous_height = Some(tx.height);
+            index = 0;
+        }
+        tx.index = index;
+        index += 1;
+    }
+}
+
+async fn get_txs_event(
+    context: Arc<IndexerContext>,
+    channel: Channel,
+    from_block_height: u64,
+    to_block_height: u64,
+    page: u64,
+    per_page: u64,
+) -> Result<TxSearchResult, anyhow::Error> {
+    let request = txs_event_request(from_block_height, to_block_height, page, per_page);
+
+    let started_at = Instant::now();
+    let response = request_txs_event(channel, request).await;
+    context.metrics.observe_rpc(
+        grpc_endpoint(context.as_ref()),
+        "GetTxsEvent",
+        started_at,
+        &response,
+    );
+    let response = response?;
+
+    Ok(TxSearchResult {
+        total_count: total_count(&response),
+        txs: response
+            .tx_responses
+            .into_iter()
+            .map(tx_from_response)
+            .collect(),
+    })
+}
+
+fn txs_event_request(
+    from_block_height: u64,
+    to_block_height: u64,
+    page: u64,
+    per_page: u64,
+) -> GetTxsEventRequest {
+    let events = if from_block_height == to_block_height {
+        vec![format!("tx.height={}", from_block_height)]
+    } else {
+        vec![
+            format!("tx.height>={}", from_block_height),
+            format!("tx.height<={}", to_block_height),
+        ]
+    };
+
+    GetTxsEventRequest {
+        query: events.join(" AND "),
+        events,
+        pagination: Some(PageRequest {
+            offset: page.saturating_sub(1) * per_page,
+            limit: per_page,
+            count_total: true,
+            ..Default::default()
+        }),
+        order_by: OrderBy::Asc as i32,
+        page,
+        limit: per_page,
+    }
+}
+
+/// Nodes before cosmos sdk 0.46 leave `total` unset and count in `pagination` instead.
+#[allow(deprecated)]
+fn total_count(response: &GetTxsEventResponse) -> u64 {
+    match response.pagination.as_ref() {
+        Some(pagination) if response.total == 0 => pagination.total,
+        _ => response.total,
+    }
+}
+
+async fn request_txs_event(
+    channel: Channel,
+    request: GetTxsEventRequest,
+) -> Result<GetTxsEventResponse, anyhow::Error> {
+    let mut client = tonic::client::Grpc::new(channel);
+    client.ready().await?;
+
+    let response = client
+        .unary(
+            tonic::Request::new(request),
+            PathAndQuery::from_static("/cosmos.tx.v1beta1.Service/GetTxsEvent"),
+            ProstCodec::<GetTxsEventRequest, GetTxsEventResponse>::default(),
+        )
+        .await?;
+
+    Ok(response.into_inner())
+}
+
+/// Attributes arrive as bytes, unlike the base64 strings of tendermint 0.34 rpc responses.
+fn tx_from_response(tx_response: TxResponse) -> Tx {
+    Tx {
+        hash: tx_response.txhash,
+        height: tx_response.height as u64,
+        index: 0,
+        tx_result: TxResult {
+            code: tx_response.code as i64,
+            codespace: tx_response.codespace,
+            log: tx_response.raw_log,
+            events: Some(
+                tx_response
+                    .events
+                    .into_iter()
+                    .map(|event| Event {
+                        type_str: Some(event.r#type),
+                        attributes: Some(
+                            event
+                                .attributes
+                                .into_iter()
+                                .map(|attribute| Attribute {
+                                    key: String::from_utf8(attribute.key.to_vec()).ok(),
+                                    value: String::from_utf8(attribute.value.to_vec()).ok(),
+                                })
+                                .collect(),
+                        ),
+                    })
+                    .collect(),
+            ),
+        },
+        // `Tx` and `TxRaw` share their field numbers and embedded messages are encoded like
+        // bytes, so the encoded `Tx` decodes as the `TxRaw` the rpc returns.
+        tx: tx_response
+            .tx
+            .map(|tx| general_purpose::STANDARD.encode(tx.value))
+            .unwrap_or_default(),
+    }
+}
+
+/// Upper case hex, as the rpc returns hashes and addresses.
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+/// RFC 3339 with nanoseconds, as the rpc returns block times.
+fn format_timestamp(timestamp: Timestamp) -> String {
+    match serde_json::to_value(Rfc3339::from(timestamp)) {
+        Ok(serde_json::Value::String(time)) => time,
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmos_sdk_proto::cosmos::base::query::v1beta1::PageResponse;
+    use cosmos_sdk_proto::tendermint::v0_34::abci::{Event as ProtoEvent, EventAttribute};
+    use cosmos_sdk_proto::Any;
+
+    fn tx_response(height: i64, hash: &str) -> TxResponse {
+        TxResponse {
+            height,
+            txhash: hash.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn converts_tx_responses_with_their_events() {
+        let tx = tx_from_response(TxResponse {
+            code: 5,
+            codespace: "wasm".to_string(),
+            raw_log: "failed".to_string(),
+            events: vec![ProtoEvent {
+                r#type: "wasm".to_string(),
+                attributes: vec![
+                    EventAttribute {
+                        key: "action".into(),
+                        value: "swap".into(),
+                        index: true,
+                    },
+                    EventAttribute {
+                        key: vec![0xff_u8].into(),
+                        value: "invalid key".into(),
+                        index: true,
+                    },
+                ],
+            }],
+            tx: Some(Any {
+                type_url: "/cosmos.tx.v1beta1.Tx".to_string(),
+                value: vec![1, 2, 3],
+            }),
+            // Only the events are read, the logs repeat them on older nodes.
+            logs: vec![Default::default()],
+            ..tx_response(100, "HASH")
+        });
+
+        assert_eq!(tx.hash, "HASH");
+        assert_eq!(tx.height, 100);
+        assert_eq!(tx.tx_result.code, 5);
+        assert_eq!(tx.tx_result.codespace, "wasm");
+        assert_eq!(tx.tx_result.log, "failed");
+        assert_eq!(tx.tx, general_purpose::STANDARD.encode([1, 2, 3]));
+
+        let events = tx.tx_result.events.unwrap();
+        assert_eq!(events[0].type_str.as_deref(), Some("wasm"));
+        let attributes = events[0].attributes.as_ref().unwrap();
+        assert_eq!(attributes[0].key.as_deref(), Some("action"));
+        assert_eq!(attributes[0].value.as_deref(), Some("swap"));
+        assert_eq!(attributes[1].key, None);
+    }
+
+    #[test]
+    fn numbers_txs_within_each_height() {
+        let mut txs: Vec<Tx> = [(100, "A"), (100, "B"), (101, "C"), (103, "D"), (103, "E")]
+            .into_iter()
+            .map(|(height, hash)| tx_from_response(tx_response(height, hash)))
+            .collect();
+
+        assign_indexes(&mut txs);
+
+        let indexes: Vec<(u64, u64)> = txs.iter().map(|tx| (tx.height, tx.index)).collect();
+        assert_eq!(
+            indexes,
+            vec![(100, 0), (100, 1), (101, 0), (103, 0), (103, 1)]
+        );
+    }
+
+    #[test]
+    fn sends_the_page_to_old_and_new_nodes() {
+        let request = txs_event_request(100, 200, 3, 50);
+
+        assert_eq!(request.events, vec!["tx.height>=100", "tx.height<=200"]);
+        assert_eq!(request.query, "tx.height>=100 AND tx.height<=200");
+        assert_eq!((request.page, request.limit), (3, 50));
+        let pagination = request.pagination.unwrap();
+        assert_eq!((pagination.offset, pagination.limit), (100, 50));
+        assert!(pagination.count_total);
+
+        assert_eq!(
+            txs_event_request(100, 100, 1, 50).events,
+            vec!["tx.height=100"]
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn reads_the_total_of_old_nodes_from_the_pagination() {
+        let response = |total, pagination_total| GetTxsEventResponse {
+            total,
+            pagination: Some(PageResponse {
+                next_key: Vec::new(),
+                total: pagination_total,
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(total_count(&response(7, 0)), 7);
+        assert_eq!(total_count(&response(0, 12)), 12);
+        assert_eq!(total_count(&GetTxsEventResponse::default()), 0);
+    }
+}
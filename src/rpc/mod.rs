@@ -1,2 +1,6 @@
 pub mod blockchain;
+pub mod decode;
+pub mod grpc;
+pub mod status;
 pub mod txs;
+pub mod websocket;
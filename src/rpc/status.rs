@@ -0,0 +1,63 @@
+use serde::Deserialize;
+use std::time::Instant;
+
+use crate::metrics::Metrics;
+
+#[derive(Deserialize)]
+pub struct StatusResponse {
+    pub result: StatusResult,
+}
+
+#[derive(Deserialize)]
+pub struct StatusResult {
+    pub node_info: NodeInfo,
+}
+
+#[derive(Deserialize)]
+pub struct NodeInfo {
+    /// Tendermint or CometBFT version, like `0.37.2`.
+    pub version: String,
+}
+
+/// Takes the endpoint and metrics instead of the context, the node version decides how the
+/// context decodes attributes so it is fetched while building it.
+pub async fn fetch_node_version(
+    rpc_endpoint: &str,
+    metrics: &Metrics,
+) -> Result<String, anyhow::Error> {
+    let response = fetch_status_json(rpc_endpoint, metrics).await?;
+
+    node_version(response)
+}
+
+/// Node version of a raw `/status` response.
+pub fn node_version(response: serde_json::Value) -> Result<String, anyhow::Error> {
+    let response: StatusResponse = serde_json::from_value(response)?;
+
+    Ok(response.result.node_info.version)
+}
+
+/// Raw `/status` response, as kept in recordings.
+pub async fn fetch_status_json(
+    rpc_endpoint: &str,
+    metrics: &Metrics,
+) -> Result<serde_json::Value, anyhow::Error> {
+    let started_at = Instant::now();
+    let response = request_status(rpc_endpoint).await;
+    metrics.observe_rpc(rpc_endpoint, "/status", started_at, &response);
+
+    response
+}
+
+async fn request_status(rpc_endpoint: &str) -> Result<serde_json::Value, anyhow::Error> {
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!("{}/status", rpc_endpoint))
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+
+    Ok(response)
+}
@@ -1,6 +1,9 @@
-use log::debug;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, instrument, warn};
 
 use crate::helpers;
 use crate::IndexerContext;
@@ -24,11 +27,19 @@ pub struct Tx {
     pub height: u64,
     pub index: u64,
     pub tx_result: TxResult,
+    /// Base64 protobuf `TxRaw`.
+    #[serde(default)]
+    pub tx: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TxResult {
     pub code: i64,
+    #[serde(default)]
+    pub codespace: String,
+    /// Error message of failed txs, the raw events json of successful ones on older nodes.
+    #[serde(default)]
+    pub log: String,
     pub events: Option<Vec<Event>>,
 }
 
@@ -39,61 +50,288 @@ pub struct Event {
     pub attributes: Option<Vec<Attribute>>,
 }
 
+/// Raw as returned by the node until `decode_attributes` runs.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Attribute {
-    #[serde(deserialize_with = "helpers::deserialize_base64_to_string")]
     pub key: Option<String>,
-    #[serde(deserialize_with = "helpers::deserialize_base64_to_string")]
     pub value: Option<String>,
 }
 
+/// Tendermint up to 0.34 base64 encodes event attributes, 0.37 and CometBFT return them as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeEncoding {
+    Base64,
+    Plain,
+    /// Node version unknown, attributes that decode as base64 to valid utf8 are decoded and the
+    /// rest kept as is. Short plain values can be valid base64 too, so prefer detecting the node.
+    Auto,
+}
+
+impl AttributeEncoding {
+    /// Maps the `node_info.version` of `/status`, like `0.34.24` or `v0.38.2`.
+    pub fn from_node_version(version: &str) -> Self {
+        let mut parts = version.trim_start_matches('v').split('.');
+        let major = parts.next().and_then(|part| part.parse::<u64>().ok());
+        let minor = parts.next().and_then(|part| part.parse::<u64>().ok());
+
+        match (major, minor) {
+            (Some(0), Some(minor)) if minor < 37 => AttributeEncoding::Base64,
+            (Some(_), Some(_)) => AttributeEncoding::Plain,
+            _ => AttributeEncoding::Auto,
+        }
+    }
+
+    pub fn decode(&self, raw: &str) -> Option<String> {
+        match self {
+            AttributeEncoding::Base64 => helpers::decode_base64_to_string(raw),
+            AttributeEncoding::Plain => Some(raw.to_owned()),
+            AttributeEncoding::Auto => {
+                helpers::decode_base64_to_string(raw).or_else(|| Some(raw.to_owned()))
+            }
+        }
+    }
+}
+
+/// Decodes the event attributes of `txs` in place, attributes that can't be decoded are dropped
+/// by the matcher.
+pub fn decode_attributes(txs: &mut [Tx], encoding: AttributeEncoding) {
+    let attributes = txs
+        .iter_mut()
+        .filter_map(|tx| tx.tx_result.events.as_mut())
+        .flatten()
+        .filter_map(|event| event.attributes.as_mut())
+        .flatten();
+
+    for attribute in attributes {
+        attribute.key = attribute.key.as_deref().and_then(|key| {
+            let decoded = encoding.decode(key);
+            if decoded.is_none() {
+                debug!(
+                    "Dropping attribute key not encoded as {:?}: {}",
+                    encoding, key
+                );
+            }
+            decoded
+        });
+        attribute.value = attribute
+            .value
+            .as_deref()
+            .and_then(|value| encoding.decode(value));
+    }
+}
+
+/// Bounds of a `tx_search` over a height range.
+#[derive(Debug, Clone)]
+pub struct TxSearchOptions {
+    /// Requested page size, nodes cap it at their `max_per_page`.
+    pub per_page: u64,
+    /// Ranges matching more txs are split in halves, down to a single height.
+    pub max_results: u64,
+}
+
+/// Times a range is fetched again when its total count changes between pages.
+const MAX_COUNT_DRIFT_RETRIES: u32 = 3;
+
 pub async fn tx_search(
     context: Arc<IndexerContext>,
     from_block_height: u64,
     to_block_height: u64,
 ) -> Result<Vec<Tx>, anyhow::Error> {
-    let mut txs: Vec<Tx> = Vec::new();
+    let options = TxSearchOptions {
+        per_page: context.indexer_config.tx_search_per_page,
+        max_results: context.indexer_config.tx_search_max_results,
+    };
 
-    let mut current_page = 1;
-    let tx_search_page_response = tx_search_page(
-        context.clone(),
+    paginate_tx_search(
+        |from_height, to_height, page, per_page| {
+            let context = context.clone();
+            async move {
+                let response =
+                    tx_search_page(context, from_height, to_height, page, per_page).await?;
+                Ok(response.result)
+            }
+        },
         from_block_height,
         to_block_height,
-        current_page,
+        &options,
     )
-    .await?;
-    let total_count = tx_search_page_response.result.total_count;
-    txs.extend(tx_search_page_response.result.txs);
-
-    while total_count > txs.len() as u64 {
-        current_page += 1;
-        debug!("Fetching page: {}", current_page);
-        let tx_search_page_response = tx_search_page(
-            context.clone(),
-            from_block_height,
-            to_block_height,
-            current_page,
-        )
-        .await?;
-        txs.extend(tx_search_page_response.result.txs);
+    .await
+}
+
+/// Fetches every tx of `from_block_height..=to_block_height` through `fetch_page`, which takes
+/// `(from_height, to_height, page, per_page)`, sorted by height and index.
+pub async fn paginate_tx_search<F, Fut>(
+    fetch_page: F,
+    from_block_height: u64,
+    to_block_height: u64,
+    options: &TxSearchOptions,
+) -> Result<Vec<Tx>, anyhow::Error>
+where
+    F: Fn(u64, u64, u64, u64) -> Fut,
+    Fut: Future<Output = Result<TxSearchResult, anyhow::Error>>,
+{
+    let mut txs: Vec<Tx> = Vec::new();
+    let mut ranges = vec![(from_block_height, to_block_height)];
+
+    while let Some((from_height, to_height)) = ranges.pop() {
+        let mut retries = 0;
+        loop {
+            match fetch_range(&fetch_page, from_height, to_height, options).await? {
+                RangeResult::Txs(range_txs) => {
+                    txs.extend(range_txs);
+                    break;
+                }
+                RangeResult::TooMany(total_count) => {
+                    let middle_height = from_height + (to_height - from_height) / 2;
+                    debug!(
+                        "Splitting range: {}-{} with {} txs at height: {}",
+                        from_height, to_height, total_count, middle_height
+                    );
+                    ranges.push((middle_height + 1, to_height));
+                    ranges.push((from_height, middle_height));
+                    break;
+                }
+                RangeResult::CountDrift if retries < MAX_COUNT_DRIFT_RETRIES => {
+                    retries += 1;
+                    warn!(
+                        "Total count changed while paginating range: {}-{}, retrying",
+                        from_height, to_height
+                    );
+                }
+                RangeResult::CountDrift => {
+                    return Err(anyhow::anyhow!(
+                        "Total count kept changing while paginating range: {}-{}",
+                        from_height,
+                        to_height
+                    ));
+                }
+            }
+        }
     }
 
-    txs.sort_by(|a, b| a.index.cmp(&b.index));
-    txs.sort_by(|a, b| a.height.cmp(&b.height));
-    txs = txs
-        .into_iter()
-        .filter(|tx| tx.tx_result.code == 0)
-        .collect();
+    txs.sort_by_key(|tx| tx.index);
+    txs.sort_by_key(|tx| tx.height);
 
     Ok(txs)
 }
 
+enum RangeResult {
+    Txs(Vec<Tx>),
+    /// More txs than `max_results` in a range that can still be split.
+    TooMany(u64),
+    CountDrift,
+}
+
+async fn fetch_range<F, Fut>(
+    fetch_page: &F,
+    from_height: u64,
+    to_height: u64,
+    options: &TxSearchOptions,
+) -> Result<RangeResult, anyhow::Error>
+where
+    F: Fn(u64, u64, u64, u64) -> Fut,
+    Fut: Future<Output = Result<TxSearchResult, anyhow::Error>>,
+{
+    let first_page = fetch_page(from_height, to_height, 1, options.per_page).await?;
+    let total_count = first_page.total_count;
+    if total_count > options.max_results && from_height < to_height {
+        return Ok(RangeResult::TooMany(total_count));
+    }
+
+    // The node silently caps `per_page`, so the first page tells the real page size.
+    let page_size = first_page.txs.len() as u64;
+    if page_size == 0 {
+        if total_count > 0 {
+            return Err(anyhow::anyhow!(
+                "Empty first page of range: {}-{} with {} txs",
+                from_height,
+                to_height,
+                total_count
+            ));
+        }
+        return Ok(RangeResult::Txs(Vec::new()));
+    }
+
+    let mut txs = first_page.txs;
+    let pages = total_count.div_ceil(page_size);
+    for page in 2..=pages {
+        debug!("Fetching page: {} of {}", page, pages);
+        let response = fetch_page(from_height, to_height, page, page_size).await?;
+        if response.total_count != total_count {
+            return Ok(RangeResult::CountDrift);
+        }
+        if response.txs.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Empty page: {} of range: {}-{} with {} txs",
+                page,
+                from_height,
+                to_height,
+                total_count
+            ));
+        }
+        txs.extend(response.txs);
+    }
+
+    let mut hashes = HashSet::new();
+    txs.retain(|tx| hashes.insert(tx.hash.to_owned()));
+    if txs.len() as u64 != total_count {
+        return Ok(RangeResult::CountDrift);
+    }
+
+    Ok(RangeResult::Txs(txs))
+}
+
+#[instrument(name = "tx_search_page", skip(context))]
 pub async fn tx_search_page(
     context: Arc<IndexerContext>,
     from_block_height: u64,
     to_block_height: u64,
     page: u64,
+    per_page: u64,
 ) -> Result<TxSearchResponse, anyhow::Error> {
+    let response = tx_search_page_json(
+        context.as_ref(),
+        from_block_height,
+        to_block_height,
+        page,
+        per_page,
+    )
+    .await?;
+
+    let mut response: TxSearchResponse = serde_json::from_value(response)?;
+    decode_attributes(&mut response.result.txs, context.attribute_encoding);
+
+    Ok(response)
+}
+
+/// Raw `/tx_search` response with attributes still encoded, as kept in recordings.
+pub async fn tx_search_page_json(
+    context: &IndexerContext,
+    from_block_height: u64,
+    to_block_height: u64,
+    page: u64,
+    per_page: u64,
+) -> Result<serde_json::Value, anyhow::Error> {
+    let started_at = Instant::now();
+    let response =
+        request_tx_search_page(context, from_block_height, to_block_height, page, per_page).await;
+    context.metrics.observe_rpc(
+        &context.indexer_config.rpc_endpoint,
+        "/tx_search",
+        started_at,
+        &response,
+    );
+
+    response
+}
+
+async fn request_tx_search_page(
+    context: &IndexerContext,
+    from_block_height: u64,
+    to_block_height: u64,
+    page: u64,
+    per_page: u64,
+) -> Result<serde_json::Value, anyhow::Error> {
     let client = reqwest::Client::new();
 
     let mut query = format!("\"tx.height = {}\"", from_block_height);
@@ -110,11 +348,11 @@ pub async fn tx_search_page(
         .query(&[
             ("query", query),
             ("page", page.to_string()),
-            ("per_page", 100.to_string()),
+            ("per_page", per_page.to_string()),
         ])
         .send()
         .await?
-        .json::<TxSearchResponse>()
+        .json::<serde_json::Value>()
         .await?;
 
     Ok(response)
@@ -0,0 +1,329 @@
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+use crate::helpers;
+use crate::rpc::blockchain::BlockMeta;
+use crate::rpc::txs::{Event, Tx, TxResult};
+use crate::IndexerContext;
+
+const NEW_BLOCK_EVENT: &str = "tendermint/event/NewBlock";
+const TX_EVENT: &str = "tendermint/event/Tx";
+
+const MAX_RECONNECT_DELAY_SECONDS: u64 = 30;
+
+/// Heights kept in `LiveTxs` while the fetcher hasn't taken them, the fetcher takes every block
+/// right away unless it lags, and lagging ranges are fetched from the source anyway.
+const LIVE_BLOCKS_CAPACITY: usize = 256;
+
+#[derive(Deserialize)]
+struct EventMessage {
+    result: EventResult,
+}
+
+#[derive(Deserialize)]
+struct EventResult {
+    data: Option<EventData>,
+    /// Indexed attributes of the event, holds the `tx.hash` of txs.
+    #[serde(default)]
+    events: HashMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct EventData {
+    #[serde(rename = "type")]
+    type_str: String,
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct NewBlockValue {
+    block: NewBlock,
+}
+
+#[derive(Deserialize)]
+struct NewBlock {
+    header: NewBlockHeader,
+    data: NewBlockData,
+}
+
+#[derive(Deserialize)]
+struct NewBlockHeader {
+    #[serde(deserialize_with = "helpers::deserialize_string_to_u64")]
+    height: u64,
+    last_block_id: BlockId,
+}
+
+#[derive(Deserialize)]
+struct BlockId {
+    hash: String,
+}
+
+#[derive(Deserialize)]
+struct NewBlockData {
+    txs: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct TxValue {
+    #[serde(rename = "TxResult")]
+    tx_result: TxEventResult,
+}
+
+#[derive(Deserialize)]
+struct TxEventResult {
+    #[serde(deserialize_with = "helpers::deserialize_string_to_u64")]
+    height: u64,
+    #[serde(default)]
+    index: u64,
+    tx: String,
+    result: TxEventOutcome,
+}
+
+/// Fields left out when empty, unlike in `tx_search` responses.
+#[derive(Deserialize)]
+struct TxEventOutcome {
+    #[serde(default)]
+    code: i64,
+    #[serde(default)]
+    codespace: String,
+    #[serde(default)]
+    log: String,
+    events: Option<Vec<Event>>,
+}
+
+/// Txs received over the websocket for the blocks the fetcher hasn't reached yet.
+#[derive(Default)]
+pub struct LiveTxs {
+    blocks: Mutex<BTreeMap<u64, LiveBlock>>,
+}
+
+#[derive(Default)]
+struct LiveBlock {
+    parent_hash: Option<String>,
+    /// Tx count of the `NewBlock` event, the block is complete once that many txs arrived.
+    tx_count: Option<usize>,
+    txs: Vec<Tx>,
+}
+
+impl LiveBlock {
+    fn is_complete(&self) -> bool {
+        self.tx_count == Some(self.txs.len())
+    }
+}
+
+impl LiveTxs {
+    pub fn new() -> Self {
+        LiveTxs::default()
+    }
+
+    /// Records the `NewBlock` event of `height`, its txs arrive as separate events.
+    pub fn insert_block(&self, height: u64, parent_hash: String, tx_count: usize) {
+        let mut blocks = self.blocks.lock().unwrap();
+        let block = blocks.entry(height).or_default();
+        block.parent_hash = Some(parent_hash);
+        block.tx_count = Some(tx_count);
+        Self::trim(&mut blocks);
+    }
+
+    pub fn insert_tx(&self, tx: Tx) {
+        let mut blocks = self.blocks.lock().unwrap();
+        let block = blocks.entry(tx.height).or_default();
+        if !block.txs.iter().any(|known| known.index == tx.index) {
+            block.txs.push(tx);
+        }
+        Self::trim(&mut blocks);
+    }
+
+    /// Takes the txs of `from_height..=to_height`, ordered like `tx_search` returns them, when
+    /// every block of the range arrived complete and extends the parent of the matching block
+    /// in `blocks`. Returns `None` otherwise, the range is then fetched from the source, which
+    /// also fills the heights missed while disconnected. Heights up to `to_height` are dropped
+    /// either way.
+    pub fn take(&self, from_height: u64, to_height: u64, blocks: &[BlockMeta]) -> Option<Vec<Tx>> {
+        let mut blocks_by_height = self.blocks.lock().unwrap();
+        let remaining = blocks_by_height.split_off(&(to_height + 1));
+        let taken = std::mem::replace(&mut *blocks_by_height, remaining);
+
+        let mut txs = Vec::new();
+        for height in from_height..=to_height {
+            let live_block = taken.get(&height).filter(|block| block.is_complete())?;
+            let parent_matches = blocks
+                .iter()
+                .filter(|block| block.height == height)
+                .all(|block| live_block.parent_hash.as_ref() == Some(&block.parent_hash));
+            if !parent_matches {
+                return None;
+            }
+
+            let mut block_txs = live_block.txs.clone();
+            block_txs.sort_by_key(|tx| tx.index);
+            txs.extend(block_txs);
+        }
+
+        Some(txs)
+    }
+
+    fn trim(blocks: &mut BTreeMap<u64, LiveBlock>) {
+        while blocks.len() > LIVE_BLOCKS_CAPACITY {
+            blocks.pop_first();
+        }
+    }
+}
+
+/// `websocket_endpoint` or the `/websocket` path of the rpc endpoint.
+pub fn websocket_endpoint(context: &IndexerContext) -> String {
+    match context.indexer_config.websocket_endpoint.as_ref() {
+        Some(endpoint) => endpoint.to_owned(),
+        None => {
+            let rpc_endpoint = context.indexer_config.rpc_endpoint.trim_end_matches('/');
+            let endpoint = if let Some(host) = rpc_endpoint.strip_prefix("https://") {
+                format!("wss://{}", host)
+            } else if let Some(host) = rpc_endpoint.strip_prefix("http://") {
+                format!("ws://{}", host)
+            } else {
+                rpc_endpoint.to_owned()
+            };
+            format!("{}/websocket", endpoint)
+        }
+    }
+}
+
+/// Keeps the txs of every `Tx` event in `live_txs` and publishes the height of every `NewBlock`
+/// event, or `None` while disconnected, reconnecting with a growing delay until nobody listens
+/// anymore.
+pub async fn watch_new_blocks(
+    context: Arc<IndexerContext>,
+    sender: watch::Sender<Option<u64>>,
+    live_txs: Arc<LiveTxs>,
+) {
+    let endpoint = websocket_endpoint(context.as_ref());
+    let mut reconnects = 0;
+
+    loop {
+        if let Err(err) =
+            subscribe_new_blocks(&endpoint, &sender, live_txs.as_ref(), &mut reconnects).await
+        {
+            context
+                .metrics
+                .rpc_errors
+                .with_label_values(&[&endpoint, "subscribe"])
+                .inc();
+            warn!(
+                "Websocket subscription to: {} failed, polling until reconnected: {}",
+                endpoint, err
+            );
+        }
+        if sender.send(None).is_err() {
+            return;
+        }
+
+        let delay = 2u64
+            .saturating_pow(reconnects)
+            .min(MAX_RECONNECT_DELAY_SECONDS);
+        reconnects += 1;
+        sleep(Duration::from_secs(delay)).await;
+    }
+}
+
+async fn subscribe_new_blocks(
+    endpoint: &str,
+    sender: &watch::Sender<Option<u64>>,
+    live_txs: &LiveTxs,
+    reconnects: &mut u32,
+) -> Result<(), anyhow::Error> {
+    let (mut stream, _) = tokio_tungstenite::connect_async(endpoint).await?;
+    for (id, query) in [(1, "tm.event='NewBlock'"), (2, "tm.event='Tx'")] {
+        stream
+            .send(Message::Text(
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "subscribe",
+                    "id": id,
+                    "params": { "query": query },
+                })
+                .to_string(),
+            ))
+            .await?;
+    }
+    info!("Subscribed to new blocks and txs on: {}", endpoint);
+    *reconnects = 0;
+
+    while let Some(message) = stream.next().await {
+        let text = match message? {
+            Message::Text(text) => text,
+            Message::Ping(payload) => {
+                stream.send(Message::Pong(payload)).await?;
+                continue;
+            }
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        match handle_event(&text, live_txs) {
+            Ok(Some(height)) => {
+                debug!("New block: {}", height);
+                if sender.send(Some(height)).is_err() {
+                    return Ok(());
+                }
+            }
+            Ok(None) => {}
+            Err(err) => debug!("Ignoring websocket message: {}", err),
+        }
+    }
+
+    Err(anyhow::anyhow!("Websocket closed"))
+}
+
+/// Keeps the block or tx of an event in `live_txs`, returns the height of new blocks.
+pub fn handle_event(text: &str, live_txs: &LiveTxs) -> Result<Option<u64>, anyhow::Error> {
+    let message: EventMessage = serde_json::from_str(text)?;
+    // Subscription confirmations have no data.
+    let data = match message.result.data {
+        Some(data) => data,
+        None => return Ok(None),
+    };
+
+    match data.type_str.as_str() {
+        NEW_BLOCK_EVENT => {
+            let block = serde_json::from_value::<NewBlockValue>(data.value)?.block;
+            live_txs.insert_block(
+                block.header.height,
+                block.header.last_block_id.hash,
+                block.data.txs.map_or(0, |txs| txs.len()),
+            );
+
+            Ok(Some(block.header.height))
+        }
+        TX_EVENT => {
+            let tx_result = serde_json::from_value::<TxValue>(data.value)?.tx_result;
+            let hash = message
+                .result
+                .events
+                .get("tx.hash")
+                .and_then(|hashes| hashes.first())
+                .ok_or_else(|| anyhow::anyhow!("Tx event without tx.hash"))?;
+            live_txs.insert_tx(Tx {
+                hash: hash.to_owned(),
+                height: tx_result.height,
+                index: tx_result.index,
+                tx_result: TxResult {
+                    code: tx_result.result.code,
+                    codespace: tx_result.result.codespace,
+                    log: tx_result.result.log,
+                    events: tx_result.result.events,
+                },
+                tx: tx_result.tx,
+            });
+
+            Ok(None)
+        }
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,242 @@
+use rdkafka::config::ClientConfig;
+use rdkafka::error::KafkaResult;
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+use rdkafka::util::Timeout;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::database::events::EventsDocument;
+use crate::reorg::Rollback;
+use crate::IndexerContext;
+
+/// How long a message may wait for room in the producer queue before producing fails.
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn connect(brokers: &str) -> KafkaResult<FutureProducer> {
+    ClientConfig::new()
+        .set("bootstrap.servers", brokers)
+        .set("enable.idempotence", "true")
+        .set("acks", "all")
+        .set("max.in.flight.requests.per.connection", "5")
+        .set("message.timeout.ms", "30000")
+        .create()
+}
+
+/// Resolves the topic for a matcher key, preferring the matcher's own `kafka_topic` over the
+/// indexer-wide template. Templates can reference `{chain_id}` and `{key}`.
+pub fn resolve_topic(context: &IndexerContext, event_key: &str) -> String {
+    let template = context
+        .matcher_config
+        .events
+        .iter()
+        .find(|event| event.key == event_key)
+        .and_then(|event| event.kafka_topic.to_owned())
+        .unwrap_or(context.indexer_config.kafka_topic.to_owned());
+
+    template
+        .replace("{chain_id}", &context.indexer_config.chain_id)
+        .replace("{key}", event_key)
+}
+
+/// Events of a contract share a partition so they stay ordered. Events without a contract
+/// address are keyed by their tx hash instead of all landing on the partition of the empty key.
+fn message_key(event: &EventsDocument) -> &str {
+    event
+        .logs
+        .iter()
+        .find(|log| log.key == "_contract_address")
+        .map(|log| log.value.as_str())
+        .unwrap_or(&event.tx_hash)
+}
+
+pub async fn produce_event(
+    context: Arc<IndexerContext>,
+    event: &EventsDocument,
+) -> Result<(), anyhow::Error> {
+    let producer = match context.kafka.as_ref() {
+        Some(producer) => producer,
+        None => return Err(anyhow::anyhow!("Kafka sink is not enabled")),
+    };
+
+    let topic = resolve_topic(context.as_ref(), &event.key);
+    let payload = serde_json::to_string(event)?;
+
+    producer
+        .send(
+            FutureRecord::to(&topic)
+                .key(message_key(event))
+                .payload(&payload),
+            Timeout::After(QUEUE_TIMEOUT),
+        )
+        .await
+        .map_err(|(err, _)| err)?;
+
+    Ok(())
+}
+
+/// Sends the rollback to every partition of every topic of the chain, so each consumer gets it
+/// in order with the events of the contracts it reads.
+pub async fn produce_rollback(
+    context: Arc<IndexerContext>,
+    rollback: &Rollback,
+) -> Result<(), anyhow::Error> {
+    let producer = match context.kafka.as_ref() {
+        Some(producer) => producer,
+        None => return Err(anyhow::anyhow!("Kafka sink is not enabled")),
+    };
+
+    let topics: BTreeSet<String> = context
+        .matcher_config
+        .events
+        .iter()
+        .map(|event| resolve_topic(context.as_ref(), &event.key))
+        .collect();
+    let payload = serde_json::to_string(rollback)?;
+
+    for topic in topics {
+        for partition in 0..partition_count(producer, &topic).await? {
+            producer
+                .send(
+                    FutureRecord::to(&topic)
+                        .partition(partition)
+                        .key(&rollback.chain_id)
+                        .payload(&payload),
+                    Timeout::After(QUEUE_TIMEOUT),
+                )
+                .await
+                .map_err(|(err, _)| err)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Topics nothing was produced to yet have no partitions.
+async fn partition_count(producer: &FutureProducer, topic: &str) -> Result<i32, anyhow::Error> {
+    let producer = producer.clone();
+    let topic = topic.to_owned();
+    let metadata = tokio::task::spawn_blocking(move || {
+        producer
+            .client()
+            .fetch_metadata(Some(&topic), QUEUE_TIMEOUT)
+    })
+    .await??;
+
+    Ok(metadata
+        .topics()
+        .first()
+        .map_or(0, |topic| topic.partitions().len() as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::events::EventLog;
+    use crate::source::RecordedSource;
+    use crate::{config, MatcherOptions};
+
+    const MATCHERS: &str = r#"
+events:
+  - name: "Swap"
+    key: "swap"
+    kafka_topic: "swaps-{chain_id}"
+    patterns:
+      - key: action
+        value: "swap"
+  - name: "Provide"
+    key: "provide"
+    patterns:
+      - key: action
+        value: "provide"
+"#;
+
+    async fn context() -> Arc<IndexerContext> {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/indexer.yaml");
+        let indexer_config = config::load_indexer_config(Some(path)).unwrap();
+
+        crate::build_replay_context(
+            indexer_config,
+            Some(MatcherOptions {
+                matcher_file_path: None,
+                matcher_config: Some(serde_yaml::from_str(MATCHERS).unwrap()),
+            }),
+            RecordedSource::default(),
+        )
+        .await
+        .unwrap()
+    }
+
+    fn event(logs: &[(&str, &str)]) -> EventsDocument {
+        EventsDocument {
+            _id: mongodb::bson::oid::ObjectId::new(),
+            chain_id: "test-1".to_string(),
+            block_height: 150,
+            block_time: None,
+            block_hash: None,
+            proposer_address: None,
+            tx_hash: "ABC".to_string(),
+            transaction_id: None,
+            key: "swap".to_string(),
+            logs: logs
+                .iter()
+                .map(|(key, value)| EventLog {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            full_logs: Vec::new(),
+            failed_message: None,
+            success: true,
+            code: 0,
+            codespace: String::new(),
+            log: String::new(),
+            created_at: mongodb::bson::DateTime::now(),
+        }
+    }
+
+    #[test]
+    fn keys_events_by_contract_address_else_tx_hash() {
+        let event_of_contract = event(&[
+            ("action", "swap"),
+            ("_contract_address", "neutron1contract"),
+        ]);
+        assert_eq!(message_key(&event_of_contract), "neutron1contract");
+
+        assert_eq!(message_key(&event(&[("action", "swap")])), "ABC");
+    }
+
+    #[tokio::test]
+    async fn expands_the_topic_of_the_matcher_else_the_indexer_template() {
+        let context = context().await;
+
+        assert_eq!(resolve_topic(&context, "swap"), "swaps-test-1");
+        // The default template of the indexer.
+        assert_eq!(resolve_topic(&context, "provide"), "test-1.provide");
+        assert_eq!(resolve_topic(&context, "unknown"), "test-1.unknown");
+    }
+
+    #[test]
+    fn serializes_payloads_like_the_stored_documents() {
+        let event = event(&[("_contract_address", "neutron1contract")]);
+        let payload: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&event).unwrap()).unwrap();
+
+        assert_eq!(payload["chainId"], "test-1");
+        assert_eq!(payload["blockHeight"], 150);
+        assert_eq!(payload["txHash"], "ABC");
+        assert_eq!(payload["key"], "swap");
+        assert_eq!(payload["logs"][0]["key"], "_contract_address");
+        assert_eq!(payload["logs"][0]["value"], "neutron1contract");
+        assert_eq!(payload["success"], true);
+
+        let rollback = Rollback {
+            chain_id: "test-1".to_string(),
+            fork_height: 149,
+        };
+        assert_eq!(
+            serde_json::to_value(&rollback).unwrap(),
+            serde_json::json!({ "type": "rollback", "chainId": "test-1", "forkHeight": 149 })
+        );
+    }
+}
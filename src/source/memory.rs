@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use super::BlockSource;
+use crate::rpc::blockchain::BlockMeta;
+use crate::rpc::txs::Tx;
+use crate::IndexerContext;
+
+/// A chain kept in memory, for deterministic runs of the indexing loop in tests. Blocks can be
+/// added or replaced while the loop runs to simulate new blocks and reorgs, the highest block is
+/// the head.
+#[derive(Default)]
+pub struct MemorySource {
+    blocks: RwLock<BTreeMap<u64, (BlockMeta, Vec<Tx>)>>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        MemorySource::default()
+    }
+
+    /// Adds the block with its txs, moved to its height, replacing the block at that height.
+    pub fn insert_block(&self, block: BlockMeta, mut txs: Vec<Tx>) {
+        for tx in txs.iter_mut() {
+            tx.height = block.height;
+        }
+        txs.sort_by_key(|tx| tx.index);
+
+        self.blocks
+            .write()
+            .unwrap()
+            .insert(block.height, (block, txs));
+    }
+}
+
+#[async_trait]
+impl BlockSource for MemorySource {
+    async fn head_height(&self, _context: Arc<IndexerContext>) -> Result<u64, anyhow::Error> {
+        Ok(self
+            .blocks
+            .read()
+            .unwrap()
+            .keys()
+            .next_back()
+            .copied()
+            .unwrap_or(0))
+    }
+
+    async fn txs(
+        &self,
+        _context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<Tx>, anyhow::Error> {
+        Ok(self
+            .blocks
+            .read()
+            .unwrap()
+            .range(from_height..=to_height)
+            .flat_map(|(_, (_, txs))| txs.iter().cloned())
+            .collect())
+    }
+
+    async fn block_metas(
+        &self,
+        _context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<BlockMeta>, anyhow::Error> {
+        let blocks = self.blocks.read().unwrap();
+
+        (from_height..=to_height)
+            .map(|height| {
+                blocks
+                    .get(&height)
+                    .map(|(block, _)| block.clone())
+                    .ok_or_else(|| anyhow::anyhow!("No block at height: {}", height))
+            })
+            .collect()
+    }
+}
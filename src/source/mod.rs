@@ -0,0 +1,103 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use tonic::transport::Channel;
+
+use crate::rpc::blockchain::{self, BlockMeta};
+use crate::rpc::grpc;
+use crate::rpc::txs::{self, Tx};
+use crate::IndexerContext;
+
+pub mod memory;
+pub mod recorded;
+
+pub use memory::MemorySource;
+pub use recorded::RecordedSource;
+
+/// Where the indexing loop reads the chain from. Sources get the context to share its config
+/// and metrics.
+#[async_trait]
+pub trait BlockSource: Send + Sync {
+    /// Latest height of the chain.
+    async fn head_height(&self, context: Arc<IndexerContext>) -> Result<u64, anyhow::Error>;
+
+    /// Every tx of `from_height..=to_height` with its events, sorted by height and index.
+    async fn txs(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<Tx>, anyhow::Error>;
+
+    /// Headers of `from_height..=to_height` in ascending height order, failing when one is
+    /// missing.
+    async fn block_metas(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<BlockMeta>, anyhow::Error>;
+}
+
+/// Tendermint rpc `/blockchain` and `/tx_search`.
+pub struct RpcSource;
+
+#[async_trait]
+impl BlockSource for RpcSource {
+    async fn head_height(&self, context: Arc<IndexerContext>) -> Result<u64, anyhow::Error> {
+        blockchain::fetch_last_block_height(context).await
+    }
+
+    async fn txs(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<Tx>, anyhow::Error> {
+        txs::tx_search(context, from_height, to_height).await
+    }
+
+    async fn block_metas(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<BlockMeta>, anyhow::Error> {
+        blockchain::fetch_block_metas(context, from_height, to_height).await
+    }
+}
+
+/// Cosmos sdk grpc `GetLatestBlock`, `GetBlockWithTxs` and `GetTxsEvent`.
+pub struct GrpcSource {
+    channel: Channel,
+}
+
+impl GrpcSource {
+    pub fn new(channel: Channel) -> Self {
+        GrpcSource { channel }
+    }
+}
+
+#[async_trait]
+impl BlockSource for GrpcSource {
+    async fn head_height(&self, context: Arc<IndexerContext>) -> Result<u64, anyhow::Error> {
+        grpc::fetch_last_block_height(context, self.channel.clone()).await
+    }
+
+    async fn txs(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<Tx>, anyhow::Error> {
+        grpc::tx_search(context, self.channel.clone(), from_height, to_height).await
+    }
+
+    async fn block_metas(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<BlockMeta>, anyhow::Error> {
+        grpc::fetch_block_metas(context, self.channel.clone(), from_height, to_height).await
+    }
+}
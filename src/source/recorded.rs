@@ -0,0 +1,280 @@
+use async_trait::async_trait;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+use super::BlockSource;
+use crate::rpc::blockchain::{self, BlockMeta, BlockchainResponse};
+use crate::rpc::status;
+use crate::rpc::txs::{self, Tx, TxSearchOptions, TxSearchResponse};
+use crate::IndexerContext;
+
+/// A line of a recording, the raw rpc response with the request it answered.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedResponse {
+    /// `/status`, `/blockchain` or `/tx_search`.
+    pub method: String,
+    /// Heights and page of the request.
+    #[serde(default)]
+    pub params: serde_json::Value,
+    pub response: serde_json::Value,
+}
+
+/// Blocks and txs of a recording, read back offline. Attributes are kept as the node returned
+/// them and decoded with the encoding of the context, like `rpc::txs::tx_search` does.
+#[derive(Default)]
+pub struct RecordedSource {
+    /// Version of the recorded node, decides the attribute encoding of a replay.
+    pub node_version: Option<String>,
+    blocks: BTreeMap<u64, BlockMeta>,
+    txs: BTreeMap<u64, Vec<Tx>>,
+}
+
+impl RecordedSource {
+    /// Reads a gzip compressed JSONL recording.
+    pub fn open(path: &str) -> Result<Self, anyhow::Error> {
+        let file = File::open(path)?;
+
+        Self::from_reader(BufReader::new(GzDecoder::new(file)))
+    }
+
+    /// Reads uncompressed JSONL, one `RecordedResponse` per line.
+    pub fn from_reader(reader: impl BufRead) -> Result<Self, anyhow::Error> {
+        let mut source = RecordedSource::default();
+        let mut hashes = HashSet::new();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let recorded: RecordedResponse = serde_json::from_str(&line)
+                .map_err(|err| anyhow::anyhow!("Line {}: {}", number + 1, err))?;
+
+            match recorded.method.as_str() {
+                "/status" => source.node_version = Some(status::node_version(recorded.response)?),
+                "/blockchain" => {
+                    let response: BlockchainResponse = serde_json::from_value(recorded.response)?;
+                    for block_meta in response.result.block_metas {
+                        let block = BlockMeta::from(block_meta);
+                        source.blocks.insert(block.height, block);
+                    }
+                }
+                "/tx_search" => {
+                    let response: TxSearchResponse = serde_json::from_value(recorded.response)?;
+                    // Pages of a range can overlap when blocks arrive while paginating.
+                    for tx in response.result.txs {
+                        if hashes.insert(tx.hash.to_owned()) {
+                            source.txs.entry(tx.height).or_default().push(tx);
+                        }
+                    }
+                }
+                method => {
+                    return Err(anyhow::anyhow!(
+                        "Line {}: unknown method: {}",
+                        number + 1,
+                        method
+                    ))
+                }
+            }
+        }
+
+        for txs in source.txs.values_mut() {
+            txs.sort_by_key(|tx| tx.index);
+        }
+
+        Ok(source)
+    }
+
+    /// First and last recorded heights.
+    pub fn range(&self) -> Option<(u64, u64)> {
+        let from_height = *self.blocks.keys().next()?;
+        let to_height = *self.blocks.keys().next_back()?;
+
+        Some((from_height, to_height))
+    }
+
+    fn check_recorded(&self, from_height: u64, to_height: u64) -> Result<(), anyhow::Error> {
+        match (from_height..=to_height).find(|height| !self.blocks.contains_key(height)) {
+            Some(height) => Err(anyhow::anyhow!("Height: {} isn't recorded", height)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl BlockSource for RecordedSource {
+    async fn head_height(&self, _context: Arc<IndexerContext>) -> Result<u64, anyhow::Error> {
+        Ok(self.range().map_or(0, |(_, to_height)| to_height))
+    }
+
+    async fn txs(
+        &self,
+        context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<Tx>, anyhow::Error> {
+        self.check_recorded(from_height, to_height)?;
+
+        let mut txs: Vec<Tx> = self
+            .txs
+            .range(from_height..=to_height)
+            .flat_map(|(_, txs)| txs.iter().cloned())
+            .collect();
+        txs::decode_attributes(&mut txs, context.attribute_encoding);
+
+        Ok(txs)
+    }
+
+    async fn block_metas(
+        &self,
+        _context: Arc<IndexerContext>,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<BlockMeta>, anyhow::Error> {
+        self.check_recorded(from_height, to_height)?;
+
+        Ok(self
+            .blocks
+            .range(from_height..=to_height)
+            .map(|(_, block)| block.clone())
+            .collect())
+    }
+}
+
+/// Records the raw `/status`, `/blockchain` and `/tx_search` responses of
+/// `from_height..=to_height` to `path` as gzip compressed JSONL, requested in batches of
+/// `block_lag_batch_size` and paginated like the indexer does. Returns the number of responses.
+pub async fn record(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+    path: &str,
+) -> Result<usize, anyhow::Error> {
+    let mut writer = GzEncoder::new(BufWriter::new(File::create(path)?), Compression::default());
+    let mut recorded = 0;
+
+    let status =
+        status::fetch_status_json(&context.indexer_config.rpc_endpoint, &context.metrics).await?;
+    recorded += write_responses(
+        &mut writer,
+        vec![RecordedResponse {
+            method: "/status".to_string(),
+            params: serde_json::json!({}),
+            response: status,
+        }],
+    )?;
+
+    let batch_size = context.indexer_config.block_lag_batch_size.max(1);
+    let mut batch_from_height = from_height;
+    while batch_from_height <= to_height {
+        let batch_to_height = (batch_from_height + batch_size - 1).min(to_height);
+
+        let mut responses =
+            record_block_metas(context.clone(), batch_from_height, batch_to_height).await?;
+        responses.extend(record_txs(context.clone(), batch_from_height, batch_to_height).await?);
+        recorded += write_responses(&mut writer, responses)?;
+        info!(
+            "Recorded: from_height: {}, to_height: {}, responses: {}",
+            batch_from_height, batch_to_height, recorded
+        );
+
+        batch_from_height = batch_to_height + 1;
+    }
+
+    writer.finish()?.flush()?;
+
+    Ok(recorded)
+}
+
+async fn record_block_metas(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+) -> Result<Vec<RecordedResponse>, anyhow::Error> {
+    let mut responses = Vec::new();
+
+    let mut min_height = from_height;
+    while min_height <= to_height {
+        let max_height = (min_height + blockchain::MAX_BLOCK_METAS_PER_REQUEST - 1).min(to_height);
+        let response =
+            blockchain::request_blockchain_json(context.as_ref(), Some((min_height, max_height)))
+                .await?;
+        // Error responses fail the recording instead of the replay.
+        serde_json::from_value::<BlockchainResponse>(response.clone())?;
+        responses.push(RecordedResponse {
+            method: "/blockchain".to_string(),
+            params: serde_json::json!({ "minHeight": min_height, "maxHeight": max_height }),
+            response,
+        });
+        min_height = max_height + 1;
+    }
+
+    Ok(responses)
+}
+
+async fn record_txs(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+) -> Result<Vec<RecordedResponse>, anyhow::Error> {
+    let options = TxSearchOptions {
+        per_page: context.indexer_config.tx_search_per_page,
+        max_results: context.indexer_config.tx_search_max_results,
+    };
+    let responses = Mutex::new(Vec::new());
+
+    txs::paginate_tx_search(
+        |from_height, to_height, page, per_page| {
+            let context = context.clone();
+            let responses = &responses;
+            async move {
+                let response = txs::tx_search_page_json(
+                    context.as_ref(),
+                    from_height,
+                    to_height,
+                    page,
+                    per_page,
+                )
+                .await?;
+                let parsed: TxSearchResponse = serde_json::from_value(response.clone())?;
+                responses.lock().unwrap().push(RecordedResponse {
+                    method: "/tx_search".to_string(),
+                    params: serde_json::json!({
+                        "fromHeight": from_height,
+                        "toHeight": to_height,
+                        "page": page,
+                        "perPage": per_page,
+                    }),
+                    response,
+                });
+
+                Ok(parsed.result)
+            }
+        },
+        from_height,
+        to_height,
+        &options,
+    )
+    .await?;
+
+    Ok(responses.into_inner().unwrap())
+}
+
+fn write_responses(
+    writer: &mut impl Write,
+    responses: Vec<RecordedResponse>,
+) -> Result<usize, anyhow::Error> {
+    for response in responses.iter() {
+        serde_json::to_writer(&mut *writer, response)?;
+        writer.write_all(b"\n")?;
+    }
+
+    Ok(responses.len())
+}
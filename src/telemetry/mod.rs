@@ -0,0 +1,55 @@
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+const SERVICE_NAME: &str = "cosmos-indexer";
+
+pub struct TelemetryOptions {
+    /// `text` or `json`.
+    pub log_format: String,
+    /// OTLP gRPC collector endpoint, spans are only exported when set.
+    pub otlp_endpoint: Option<String>,
+}
+
+/// Installs the global subscriber, reading the log level from `RUST_LOG` and defaulting to `info`.
+/// Records emitted through `log` by dependencies are forwarded as well.
+pub fn init(options: &TelemetryOptions) -> Result<(), anyhow::Error> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or(EnvFilter::new("info"));
+    let json = options.log_format == "json";
+
+    let otlp_layer = match options.otlp_endpoint.as_ref() {
+        Some(endpoint) => {
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(endpoint),
+                )
+                .with_trace_config(trace::config().with_resource(Resource::new(vec![
+                    KeyValue::new("service.name", SERVICE_NAME),
+                ])))
+                .install_batch(runtime::Tokio)?;
+            Some(tracing_opentelemetry::layer().with_tracer(tracer))
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(json.then(|| tracing_subscriber::fmt::layer().json()))
+        .with((!json).then(tracing_subscriber::fmt::layer))
+        .with(otlp_layer)
+        .try_init()?;
+
+    Ok(())
+}
+
+/// Flushes spans still buffered by the exporter. The flush blocks until the batch processor,
+/// which runs on the tokio runtime, drains its queue, so it can't run on a runtime thread.
+pub async fn shutdown() {
+    let _ = tokio::task::spawn_blocking(opentelemetry::global::shutdown_tracer_provider).await;
+}
@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use cosmos_indexer::api;
+use cosmos_indexer::health::ProbeCache;
+use cosmos_indexer::source::RecordedSource;
+
+mod common;
+
+/// Serves the api of a context without a reachable database or node, returns its url.
+async fn serve(api_enabled: bool) -> String {
+    let context = common::replay_context(RecordedSource::default(), |indexer_config| {
+        indexer_config.api_enabled = api_enabled;
+    })
+    .await;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(
+        axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(api::router(vec![context]).into_make_service()),
+    );
+
+    format!("http://{}", address)
+}
+
+async fn status(url: String) -> u16 {
+    reqwest::get(url).await.unwrap().status().as_u16()
+}
+
+#[tokio::test]
+async fn serves_metrics_and_probes_without_the_read_api() {
+    let url = serve(false).await;
+
+    assert_eq!(status(format!("{}/metrics", url)).await, 200);
+    assert_eq!(status(format!("{}/healthz", url)).await, 200);
+    assert_eq!(status(format!("{}/events", url)).await, 404);
+    assert_eq!(status(format!("{}/graphql", url)).await, 404);
+}
+
+#[tokio::test]
+async fn serves_the_read_api_when_enabled() {
+    let url = serve(true).await;
+
+    assert_eq!(status(format!("{}/healthz", url)).await, 200);
+    assert_eq!(status(format!("{}/graphql", url)).await, 200);
+}
+
+#[tokio::test]
+async fn reuses_probe_results_until_they_expire() {
+    let cache = ProbeCache::new(Duration::from_millis(100));
+    let probes = AtomicU64::new(0);
+    let probe = || async {
+        probes.fetch_add(1, Ordering::Relaxed);
+        true
+    };
+
+    assert!(cache.get_or_probe(probe()).await);
+    assert!(cache.get_or_probe(probe()).await);
+    assert_eq!(probes.load(Ordering::Relaxed), 1);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(cache.get_or_probe(probe()).await);
+    assert_eq!(probes.load(Ordering::Relaxed), 2);
+}
+
+#[tokio::test]
+async fn rejects_subscriptions_to_unknown_chains() {
+    let url = serve(true).await;
+
+    assert_eq!(
+        status(format!(
+            "{}/events/subscribe?keys=swap&chain_id=other-1",
+            url
+        ))
+        .await,
+        404
+    );
+    assert_eq!(
+        status(format!("{}/events/subscribe?chain_id=test-1", url)).await,
+        400
+    );
+}
@@ -0,0 +1,106 @@
+use cosmos_indexer::event_matcher::matcher;
+use cosmos_indexer::event_matcher::matcher_config::MatcherConfig;
+use cosmos_indexer::rpc::txs::{self, AttributeEncoding, Tx, TxSearchResponse};
+
+mod common;
+
+use common::{fixture_path, MATCHERS};
+
+fn load_txs(fixture: &str, encoding: AttributeEncoding) -> Vec<Tx> {
+    let path = fixture_path(fixture);
+    let response: TxSearchResponse =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let mut txs = response.result.txs;
+    txs::decode_attributes(&mut txs, encoding);
+
+    txs
+}
+
+fn wasm_logs(txs: &[Tx]) -> Vec<(String, String)> {
+    matcher::group_wasm_logs(&txs[0])
+        .into_iter()
+        .flat_map(|(logs, _)| logs)
+        .collect()
+}
+
+fn match_swaps(txs: &[Tx]) -> Vec<matcher::MatchedEvent> {
+    let matcher_config: MatcherConfig = serde_yaml::from_str(MATCHERS).unwrap();
+
+    txs.iter()
+        .flat_map(|tx| matcher::match_tx(&matcher_config, tx))
+        .collect()
+}
+
+#[test]
+fn detects_encoding_from_node_version() {
+    assert_eq!(
+        AttributeEncoding::from_node_version("0.34.24"),
+        AttributeEncoding::Base64
+    );
+    assert_eq!(
+        AttributeEncoding::from_node_version("v0.34.28"),
+        AttributeEncoding::Base64
+    );
+    assert_eq!(
+        AttributeEncoding::from_node_version("0.37.2"),
+        AttributeEncoding::Plain
+    );
+    assert_eq!(
+        AttributeEncoding::from_node_version("0.38.12"),
+        AttributeEncoding::Plain
+    );
+    assert_eq!(
+        AttributeEncoding::from_node_version("1.0.0"),
+        AttributeEncoding::Plain
+    );
+    assert_eq!(
+        AttributeEncoding::from_node_version("unknown"),
+        AttributeEncoding::Auto
+    );
+}
+
+#[test]
+fn matches_base64_attributes_of_tendermint_0_34() {
+    let txs = load_txs("tx_search_v0_34.json", AttributeEncoding::Base64);
+    let events = match_swaps(&txs);
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].tx_height, 4200017);
+    assert!(events[0]
+        .logs
+        .contains(&("amount".to_string(), "1000".to_string())));
+}
+
+#[test]
+fn matches_plain_attributes_of_cometbft_0_37() {
+    let txs = load_txs("tx_search_v0_37.json", AttributeEncoding::Plain);
+    let events = match_swaps(&txs);
+
+    assert_eq!(events.len(), 1);
+    assert!(events[0]
+        .logs
+        .contains(&("sender".to_string(), "neutron1sender".to_string())));
+}
+
+#[test]
+fn auto_decodes_both_versions_alike() {
+    let base64 = wasm_logs(&load_txs("tx_search_v0_34.json", AttributeEncoding::Auto));
+    let plain = wasm_logs(&load_txs("tx_search_v0_37.json", AttributeEncoding::Auto));
+
+    assert_eq!(base64, plain);
+    assert_eq!(
+        wasm_logs(&load_txs("tx_search_v0_37.json", AttributeEncoding::Plain)),
+        plain
+    );
+    assert_eq!(
+        match_swaps(&load_txs("tx_search_v0_37.json", AttributeEncoding::Auto)).len(),
+        1
+    );
+}
+
+#[test]
+fn base64_drops_plain_attributes() {
+    let txs = load_txs("tx_search_v0_37.json", AttributeEncoding::Base64);
+
+    assert!(match_swaps(&txs).is_empty());
+}
@@ -0,0 +1,200 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use cosmos_indexer::backfill::{self, split_chunks};
+use cosmos_indexer::source::MemorySource;
+use cosmos_indexer::IndexerContext;
+
+mod common;
+
+type Indexed = Arc<Mutex<Vec<(u64, u64)>>>;
+
+/// Context backfilling chunks of 1000 blocks, its completed chunks are kept in memory.
+async fn context() -> Arc<IndexerContext> {
+    common::context(
+        Arc::new(MemorySource::new()),
+        |config| {
+            config.backfill_chunk_size = 1000;
+            config.backfill_workers = 2;
+        },
+        None,
+    )
+    .await
+}
+
+/// Backfills `from_height..=to_height` as the `backfill` command does, recording the indexed
+/// chunks in `indexed`. Indexing `failing` panics.
+async fn backfill(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+    indexed: Indexed,
+    failing: Option<(u64, u64)>,
+) -> Result<(), anyhow::Error> {
+    backfill::backfill_requested_range(context, from_height, to_height, move |_, from, to| {
+        let indexed = indexed.clone();
+        async move {
+            if failing == Some((from, to)) {
+                panic!("Failed to index chunk");
+            }
+            indexed.lock().unwrap().push((from, to));
+        }
+    })
+    .await
+}
+
+fn sorted(indexed: &Indexed) -> Vec<(u64, u64)> {
+    let mut chunks = indexed.lock().unwrap().clone();
+    chunks.sort();
+
+    chunks
+}
+
+#[test]
+fn splits_aligned_ranges_into_whole_chunks() {
+    assert_eq!(
+        split_chunks(1000, 2999, 1000),
+        vec![(1000, 1999), (2000, 2999)]
+    );
+}
+
+#[test]
+fn aligns_chunks_of_unaligned_ranges() {
+    assert_eq!(
+        split_chunks(1500, 3200, 1000),
+        vec![(1500, 1999), (2000, 2999), (3000, 3200)]
+    );
+    // Chunks of overlapping ranges share their boundaries, so completed ones are found again.
+    assert_eq!(
+        split_chunks(1999, 2000, 1000),
+        vec![(1999, 1999), (2000, 2000)]
+    );
+}
+
+#[test]
+fn splits_one_block_ranges() {
+    assert_eq!(split_chunks(1234, 1234, 1000), vec![(1234, 1234)]);
+    assert_eq!(split_chunks(0, 0, 1000), vec![(0, 0)]);
+}
+
+#[test]
+fn splits_nothing_from_empty_ranges() {
+    assert_eq!(split_chunks(2000, 1999, 1000), vec![]);
+}
+
+#[test]
+fn treats_a_zero_chunk_size_as_one() {
+    assert_eq!(split_chunks(5, 7, 0), vec![(5, 5), (6, 6), (7, 7)]);
+}
+
+#[tokio::test]
+async fn backfills_a_range_again_once_it_succeeded() {
+    let context = context().await;
+
+    for _ in 0..2 {
+        let indexed = Indexed::default();
+        backfill(context.clone(), 1500, 3200, indexed.clone(), None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            sorted(&indexed),
+            vec![(1500, 1999), (2000, 2999), (3000, 3200)]
+        );
+    }
+    let completed = context
+        .chunk_store
+        .completed_chunks(context.clone(), 1500, 3200)
+        .await
+        .unwrap();
+    assert_eq!(completed, vec![]);
+}
+
+#[tokio::test]
+async fn retries_only_the_failed_chunks_of_a_run() {
+    let context = context().await;
+
+    let indexed = Indexed::default();
+    let err = backfill(
+        context.clone(),
+        1500,
+        3200,
+        indexed.clone(),
+        Some((2000, 2999)),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.to_string(), "1 backfill chunks failed");
+    assert_eq!(sorted(&indexed), vec![(1500, 1999), (3000, 3200)]);
+
+    let indexed = Indexed::default();
+    backfill(context.clone(), 1500, 3200, indexed.clone(), None)
+        .await
+        .unwrap();
+    assert_eq!(sorted(&indexed), vec![(2000, 2999)]);
+
+    // The run succeeded, the next one starts over.
+    let indexed = Indexed::default();
+    backfill(context.clone(), 1500, 3200, indexed.clone(), None)
+        .await
+        .unwrap();
+    assert_eq!(sorted(&indexed).len(), 3);
+}
+
+#[tokio::test]
+async fn indexes_up_to_backfill_workers_chunks_at_once() {
+    let context = context().await;
+    let indexed = Indexed::default();
+    let (running, max_running) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+
+    backfill::backfill_chunks(context.clone(), 0, 4999, {
+        let (indexed, max_running) = (indexed.clone(), max_running.clone());
+        move |_, from, to| {
+            let (indexed, running, max_running) =
+                (indexed.clone(), running.clone(), max_running.clone());
+            async move {
+                let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now_running, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                indexed.lock().unwrap().push((from, to));
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(sorted(&indexed).len(), 5);
+    assert_eq!(max_running.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn keeps_the_chunks_of_the_live_loop_until_it_commits() {
+    let context = context().await;
+    context
+        .chunk_store
+        .save_completed_chunk(context.clone(), 2000, 2999)
+        .await
+        .unwrap();
+
+    let indexed = Indexed::default();
+    backfill::backfill_chunks(context.clone(), 1500, 3200, {
+        let indexed = indexed.clone();
+        move |_, from, to| {
+            let indexed = indexed.clone();
+            async move { indexed.lock().unwrap().push((from, to)) }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(sorted(&indexed), vec![(1500, 1999), (3000, 3200)]);
+    let mut completed = context
+        .chunk_store
+        .completed_chunks(context.clone(), 1500, 3200)
+        .await
+        .unwrap();
+    completed.sort();
+    assert_eq!(completed, vec![(1500, 1999), (2000, 2999), (3000, 3200)]);
+}
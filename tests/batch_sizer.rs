@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use cosmos_indexer::pipeline::batch_sizer::BatchSizer;
+use cosmos_indexer::{config, IndexerConfig};
+
+/// Sizes between 10 and 80 blocks, targeting 1s and 1000 txs per batch.
+fn batch_sizer(configure: impl FnOnce(&mut IndexerConfig)) -> BatchSizer {
+    let path = format!("{}/tests/fixtures/indexer.yaml", env!("CARGO_MANIFEST_DIR"));
+    let mut indexer_config = config::load_indexer_config(Some(&path)).unwrap();
+    indexer_config.adaptive_batch_size_enabled = true;
+    indexer_config.block_lag_batch_size = 20;
+    indexer_config.min_batch_size = 10;
+    indexer_config.max_batch_size = 80;
+    indexer_config.batch_target_duration = 1000;
+    indexer_config.batch_target_txs = 1000;
+    configure(&mut indexer_config);
+
+    BatchSizer::new(&indexer_config)
+}
+
+const FAST: Duration = Duration::from_millis(100);
+const SLOW: Duration = Duration::from_millis(1500);
+
+#[test]
+fn starts_from_the_configured_size_within_bounds() {
+    assert_eq!(batch_sizer(|_| {}).size(), 20);
+    assert_eq!(
+        batch_sizer(|config| config.block_lag_batch_size = 5).size(),
+        10
+    );
+    assert_eq!(
+        batch_sizer(|config| config.block_lag_batch_size = 500).size(),
+        80
+    );
+}
+
+#[test]
+fn doubles_after_fast_sparse_full_batches_up_to_the_max() {
+    let mut sizer = batch_sizer(|_| {});
+
+    sizer.record(20, 10, FAST);
+    assert_eq!(sizer.size(), 40);
+    sizer.record(40, 10, FAST);
+    assert_eq!(sizer.size(), 80);
+    sizer.record(80, 10, FAST);
+    assert_eq!(sizer.size(), 80);
+}
+
+#[test]
+fn halves_after_slow_or_dense_batches_down_to_the_min() {
+    let mut sizer = batch_sizer(|_| {});
+
+    sizer.record(20, 10, SLOW);
+    assert_eq!(sizer.size(), 10);
+    sizer.record(10, 2000, FAST);
+    assert_eq!(sizer.size(), 10);
+
+    let mut sizer = batch_sizer(|config| config.block_lag_batch_size = 80);
+    sizer.record(80, 2000, FAST);
+    assert_eq!(sizer.size(), 40);
+}
+
+#[test]
+fn keeps_the_size_after_partial_or_average_batches() {
+    let mut sizer = batch_sizer(|_| {});
+
+    // Fewer blocks than the size were available, a larger batch tells nothing new.
+    sizer.record(5, 10, FAST);
+    assert_eq!(sizer.size(), 20);
+    // Within the targets but not fast enough to grow.
+    sizer.record(20, 600, FAST);
+    sizer.record(20, 10, Duration::from_millis(700));
+    assert_eq!(sizer.size(), 20);
+}
+
+#[test]
+fn keeps_the_configured_size_when_disabled() {
+    let mut sizer = batch_sizer(|config| {
+        config.adaptive_batch_size_enabled = false;
+        config.block_lag_batch_size = 500;
+    });
+
+    sizer.record(500, 10, FAST);
+    sizer.record(500, 2000, SLOW);
+    assert_eq!(sizer.size(), 500);
+}
+
+#[test]
+fn clamps_sizes_that_do_not_halve_or_double_onto_the_bounds() {
+    let mut sizer = batch_sizer(|config| config.block_lag_batch_size = 50);
+    sizer.record(50, 10, FAST);
+    assert_eq!(sizer.size(), 80);
+
+    let mut sizer = batch_sizer(|config| config.block_lag_batch_size = 15);
+    sizer.record(15, 10, SLOW);
+    assert_eq!(sizer.size(), 10);
+}
+
+#[test]
+fn halves_slow_batches_even_when_full_and_sparse() {
+    let mut sizer = batch_sizer(|_| {});
+
+    sizer.record(40, 10, SLOW);
+    assert_eq!(sizer.size(), 10);
+}
+
+#[test]
+fn keeps_the_size_at_the_targets() {
+    let mut sizer = batch_sizer(|_| {});
+
+    // Only going over a target halves, only staying under half of both doubles.
+    sizer.record(20, 1000, Duration::from_millis(1000));
+    assert_eq!(sizer.size(), 20);
+    sizer.record(20, 500, FAST);
+    sizer.record(20, 10, Duration::from_millis(500));
+    assert_eq!(sizer.size(), 20);
+}
@@ -0,0 +1,146 @@
+use std::io::ErrorKind;
+use std::net::TcpListener;
+use std::process::{Command, Output};
+
+mod common;
+
+use common::{fixture_path, MATCHERS};
+
+/// Writes `content` to a file unique to the test, returns its path.
+fn temp_file(name: &str, content: &str) -> String {
+    let path = std::env::temp_dir().join(format!("cli-{}-{}", std::process::id(), name));
+    std::fs::write(&path, content).unwrap();
+
+    path.to_str().unwrap().to_string()
+}
+
+/// Runs the indexer binary with `args`.
+fn indexer(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cosmos-indexer"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[test]
+fn validates_the_config_and_its_matchers() {
+    let config = fixture_path("indexer.yaml");
+    let matchers = temp_file("validate-matchers.yaml", MATCHERS);
+
+    let output = indexer(&[
+        "validate-config",
+        "--config",
+        &config,
+        "--matchers",
+        &matchers,
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "Config is valid: chain_id: test-1, matchers: 1\n"
+    );
+
+    let output = indexer(&[
+        "validate-config",
+        "--config",
+        &config,
+        "--matchers",
+        "/nonexistent/matchers.yaml",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("chain_id: test-1: Failed to open matcher config"));
+}
+
+#[test]
+fn refuses_to_backfill_inverted_ranges() {
+    let matchers = temp_file("backfill-matchers.yaml", MATCHERS);
+
+    let output = indexer(&[
+        "backfill",
+        "--config",
+        &fixture_path("indexer.yaml"),
+        "--matchers",
+        &matchers,
+        "--from",
+        "10",
+        "--to",
+        "5",
+    ]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("Failed to backfill: From height: 10 is above to height: 5"));
+}
+
+#[test]
+fn replays_a_recording_as_json_lines() {
+    let matchers = temp_file("replay-matchers.yaml", MATCHERS);
+
+    let output = indexer(&[
+        "replay",
+        "--config",
+        &fixture_path("indexer.yaml"),
+        "--matchers",
+        &matchers,
+        "--input",
+        &fixture_path("recording.jsonl.gz"),
+        "--from",
+        "102",
+        "--to",
+        "102",
+    ]);
+
+    assert!(output.status.success());
+    // Logs are written to stdout as well.
+    let events: Vec<(u64, String)> = stdout(&output)
+        .lines()
+        .filter(|line| line.starts_with('{'))
+        .map(|line| {
+            let event: serde_json::Value = serde_json::from_str(line).unwrap();
+            (
+                event["blockHeight"].as_u64().unwrap(),
+                event["key"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(events, vec![(102, "swap".to_string()); 4]);
+}
+
+#[test]
+fn reads_the_status_from_the_database_alone() {
+    // Nodes that accept connections but never answer, the status must not reach them.
+    let node = TcpListener::bind("127.0.0.1:0").unwrap();
+    node.set_nonblocking(true).unwrap();
+    let url = format!("http://{}", node.local_addr().unwrap());
+    let config = temp_file(
+        "status.yaml",
+        &format!(
+            "chain_id: \"test-1\"\n\
+             rpc_endpoint: \"{url}\"\n\
+             grpc_enabled: true\n\
+             grpc_endpoint: \"{url}\"\n\
+             database_uri: \"mongodb://127.0.0.1:1/?serverSelectionTimeoutMS=200\"\n\
+             database_name: \"cosmos_indexer_test\"\n"
+        ),
+    );
+
+    // Nor load the matchers.
+    let output = indexer(&[
+        "status",
+        "--config",
+        &config,
+        "--matchers",
+        "/nonexistent/matchers.yaml",
+    ]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("Failed to read the status"));
+    assert_eq!(node.accept().unwrap_err().kind(), ErrorKind::WouldBlock);
+}
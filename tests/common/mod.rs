@@ -0,0 +1,200 @@
+//! Fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use base64::{engine::general_purpose, Engine as _};
+use cosmos_sdk_proto::cosmos::base::v1beta1::Coin;
+use cosmos_sdk_proto::cosmos::tx::v1beta1::{AuthInfo, Fee, TxBody, TxRaw};
+use cosmos_sdk_proto::cosmwasm::wasm::v1::MsgExecuteContract;
+use cosmos_sdk_proto::prost::Message;
+use cosmos_sdk_proto::Any;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use cosmos_indexer::backfill::MemoryChunkStore;
+use cosmos_indexer::event_matcher::matcher::MatchedEvent;
+use cosmos_indexer::leader::Lease;
+use cosmos_indexer::rpc::blockchain::BlockMeta;
+use cosmos_indexer::rpc::txs::{AttributeEncoding, Tx, TxSearchResponse};
+use cosmos_indexer::source::{BlockSource, RecordedSource};
+use cosmos_indexer::{
+    config, database, health, metrics, IndexerConfig, IndexerContext, MatcherOptions,
+};
+
+pub mod node;
+
+/// Swaps of `neutron1contract`, the contract of the fixtures.
+pub const MATCHERS: &str = r#"
+events:
+  - name: "Swap"
+    key: "swap"
+    patterns:
+      - key: _contract_address
+        value: "neutron1contract"
+      - key: action
+        value: "swap"
+"#;
+
+pub fn fixture_path(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+pub fn indexer_config() -> IndexerConfig {
+    config::load_indexer_config(Some(&fixture_path("indexer.yaml"))).unwrap()
+}
+
+pub fn matcher_options() -> Option<MatcherOptions> {
+    Some(MatcherOptions {
+        matcher_file_path: None,
+        matcher_config: Some(serde_yaml::from_str(MATCHERS).unwrap()),
+    })
+}
+
+/// Context replaying `source`. The database client connects lazily, tests never reach it.
+pub async fn replay_context(
+    source: RecordedSource,
+    configure: impl FnOnce(&mut IndexerConfig),
+) -> Arc<IndexerContext> {
+    let mut indexer_config = indexer_config();
+    configure(&mut indexer_config);
+
+    cosmos_indexer::build_replay_context(indexer_config, matcher_options(), source)
+        .await
+        .unwrap()
+}
+
+/// Context indexing `source` with the plain attributes of the fixtures. The database client
+/// connects lazily, tests never reach it.
+pub async fn context(
+    source: Arc<dyn BlockSource>,
+    configure: impl FnOnce(&mut IndexerConfig),
+    lease: Option<Lease>,
+) -> Arc<IndexerContext> {
+    let mut indexer_config = indexer_config();
+    configure(&mut indexer_config);
+    let database = database::connect(
+        &indexer_config.database_driver,
+        &indexer_config.database_uri,
+        &indexer_config.database_name,
+    )
+    .await
+    .unwrap();
+
+    Arc::new(IndexerContext {
+        indexer_config,
+        matcher_config: serde_yaml::from_str(MATCHERS).unwrap(),
+        database,
+        sns: None,
+        kafka: None,
+        source,
+        metrics: metrics::Metrics::new().unwrap(),
+        loop_state: health::LoopState::new(),
+        attribute_encoding: AttributeEncoding::Plain,
+        event_stream: broadcast::channel(16).0,
+        lease,
+        chunk_store: Arc::new(MemoryChunkStore::new()),
+    })
+}
+
+/// Block `height` of the fixture chain, its hash is `HASH<height>`.
+pub fn block(height: u64) -> BlockMeta {
+    BlockMeta {
+        height,
+        hash: format!("HASH{}", height),
+        parent_hash: format!("HASH{}", height - 1),
+        time: "2024-01-01T00:00:00Z".to_string(),
+        proposer_address: "PROPOSER".to_string(),
+    }
+}
+
+/// Txs of the recorded `tx_search` range, blocks 100 to 103.
+pub fn fixture_txs() -> Vec<Tx> {
+    let response: TxSearchResponse = serde_json::from_str(
+        &std::fs::read_to_string(fixture_path("tx_search_range.json")).unwrap(),
+    )
+    .unwrap();
+
+    response.result.txs
+}
+
+/// Height and amount of every matched swap.
+pub fn swaps(events: &[MatchedEvent]) -> Vec<(u64, String)> {
+    events
+        .iter()
+        .map(|event| (event.tx_height, amount(event)))
+        .collect()
+}
+
+pub fn amount(event: &MatchedEvent) -> String {
+    event
+        .logs
+        .iter()
+        .find(|(key, _)| key == "amount")
+        .map(|(_, value)| value.to_owned())
+        .unwrap()
+}
+
+pub fn swap(height: u64, amount: &str) -> (u64, String) {
+    (height, amount.to_string())
+}
+
+pub fn coin(amount: &str) -> Coin {
+    Coin {
+        denom: "untrn".to_string(),
+        amount: amount.to_string(),
+    }
+}
+
+pub fn any(type_url: &str, message: &impl Message) -> Any {
+    Any {
+        type_url: type_url.to_string(),
+        value: message.encode_to_vec(),
+    }
+}
+
+pub fn execute(sender: &str, msg: &[u8]) -> Any {
+    any(
+        "/cosmwasm.wasm.v1.MsgExecuteContract",
+        &MsgExecuteContract {
+            sender: sender.to_string(),
+            contract: "neutron1contract".to_string(),
+            msg: msg.to_vec(),
+            funds: vec![coin("5")],
+        },
+    )
+}
+
+pub fn tx_with_bytes(bytes: &[u8]) -> Tx {
+    serde_json::from_value(serde_json::json!({
+        "hash": "HASH",
+        "height": "100",
+        "index": 0,
+        "tx_result": { "code": 0 },
+        "tx": general_purpose::STANDARD.encode(bytes),
+    }))
+    .unwrap()
+}
+
+pub fn tx(messages: Vec<Any>) -> Tx {
+    let body = TxBody {
+        messages,
+        memo: "memo".to_string(),
+        ..Default::default()
+    };
+    let auth_info = AuthInfo {
+        fee: Some(Fee {
+            amount: vec![coin("1000")],
+            gas_limit: 200_000,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    tx_with_bytes(
+        &TxRaw {
+            body_bytes: body.encode_to_vec(),
+            auth_info_bytes: auth_info.encode_to_vec(),
+            signatures: Vec::new(),
+        }
+        .encode_to_vec(),
+    )
+}
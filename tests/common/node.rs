@@ -0,0 +1,189 @@
+use axum::extract::{Query, State};
+use axum::routing::get;
+use axum::{Json, Router};
+use flate2::read::GzDecoder;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use cosmos_indexer::rpc::txs::TxSearchResult;
+use cosmos_indexer::source::recorded::RecordedResponse;
+
+use super::fixture_path;
+
+/// Serves the responses of the fixture recording the way a node does, capping `per_page`, over
+/// http or straight to `paginate_tx_search`.
+pub struct FakeNode {
+    status: Value,
+    block_metas: Vec<Value>,
+    txs: Vec<Value>,
+    max_per_page: u64,
+    requests: Mutex<Vec<(u64, u64, u64, u64)>>,
+    /// Reports one extra tx on the first page of this many requests.
+    pub drifting_requests: AtomicU64,
+    /// Returns no txs for pages from this one on.
+    pub empty_from_page: Option<u64>,
+}
+
+impl FakeNode {
+    pub fn new(max_per_page: u64) -> Self {
+        let file = std::fs::File::open(fixture_path("recording.jsonl.gz")).unwrap();
+        let mut node = FakeNode {
+            status: Value::Null,
+            block_metas: Vec::new(),
+            txs: Vec::new(),
+            max_per_page,
+            requests: Mutex::new(Vec::new()),
+            drifting_requests: AtomicU64::new(0),
+            empty_from_page: None,
+        };
+
+        for line in BufReader::new(GzDecoder::new(file)).lines() {
+            let recorded: RecordedResponse = serde_json::from_str(&line.unwrap()).unwrap();
+            let result = &recorded.response["result"];
+            match recorded.method.as_str() {
+                "/status" => node.status = recorded.response.clone(),
+                "/blockchain" => node
+                    .block_metas
+                    .extend(result["block_metas"].as_array().unwrap().iter().cloned()),
+                _ => node
+                    .txs
+                    .extend(result["txs"].as_array().unwrap().iter().cloned()),
+            }
+        }
+
+        node
+    }
+
+    /// Txs of a page and the total count of the range, as `tx_search` returns them.
+    fn page(
+        &self,
+        from_height: u64,
+        to_height: u64,
+        page: u64,
+        per_page: u64,
+    ) -> (Vec<Value>, u64) {
+        self.requests
+            .lock()
+            .unwrap()
+            .push((from_height, to_height, page, per_page));
+
+        let per_page = per_page.min(self.max_per_page);
+        let range_txs: Vec<&Value> = self
+            .txs
+            .iter()
+            .filter(|tx| (from_height..=to_height).contains(&height(&tx["height"])))
+            .collect();
+        let mut total_count = range_txs.len() as u64;
+        if page == 1
+            && self
+                .drifting_requests
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                    left.checked_sub(1)
+                })
+                .is_ok()
+        {
+            total_count += 1;
+        }
+
+        let txs = match self.empty_from_page {
+            Some(empty_from_page) if page >= empty_from_page => Vec::new(),
+            _ => range_txs
+                .into_iter()
+                .skip(((page - 1) * per_page) as usize)
+                .take(per_page as usize)
+                .cloned()
+                .collect(),
+        };
+
+        (txs, total_count)
+    }
+
+    pub async fn fetch_page(
+        &self,
+        from_height: u64,
+        to_height: u64,
+        page: u64,
+        per_page: u64,
+    ) -> Result<TxSearchResult, anyhow::Error> {
+        let (txs, total_count) = self.page(from_height, to_height, page, per_page);
+
+        Ok(TxSearchResult {
+            txs: serde_json::from_value(Value::Array(txs))?,
+            total_count,
+        })
+    }
+
+    /// Heights, page and `per_page` of every `tx_search` request.
+    pub fn requests(&self) -> Vec<(u64, u64, u64, u64)> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Serves the node on a free local port, returns its url.
+    pub fn serve(self) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let router = Router::new()
+            .route("/status", get(status))
+            .route("/blockchain", get(blockchain))
+            .route("/tx_search", get(tx_search))
+            .with_state(Arc::new(self));
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(router.into_make_service()),
+        );
+
+        format!("http://{}", address)
+    }
+}
+
+fn height(value: &Value) -> u64 {
+    value.as_str().unwrap().parse().unwrap()
+}
+
+fn param(params: &HashMap<String, String>, name: &str) -> u64 {
+    params[name].parse().unwrap()
+}
+
+async fn status(State(node): State<Arc<FakeNode>>) -> Json<Value> {
+    Json(node.status.clone())
+}
+
+async fn blockchain(
+    State(node): State<Arc<FakeNode>>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    let (min_height, max_height) = (param(&params, "minHeight"), param(&params, "maxHeight"));
+    let block_metas: Vec<&Value> = node
+        .block_metas
+        .iter()
+        .filter(|block_meta| {
+            (min_height..=max_height).contains(&height(&block_meta["header"]["height"]))
+        })
+        .collect();
+    let last_height = &node.status["result"]["sync_info"]["latest_block_height"];
+
+    Json(json!({ "result": { "last_height": last_height, "block_metas": block_metas } }))
+}
+
+/// Takes the `tx.height = a` and `tx.height >= a AND tx.height <= b` queries of the indexer.
+async fn tx_search(
+    State(node): State<Arc<FakeNode>>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    let heights: Vec<u64> = params["query"]
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|part| part.parse().ok())
+        .collect();
+    let (txs, total_count) = node.page(
+        heights[0],
+        *heights.last().unwrap(),
+        param(&params, "page"),
+        param(&params, "per_page"),
+    );
+
+    Json(json!({ "result": { "txs": txs, "total_count": total_count.to_string() } }))
+}
@@ -0,0 +1,121 @@
+use base64::{engine::general_purpose, Engine as _};
+use cosmos_sdk_proto::cosmos::authz::v1beta1::MsgExec;
+use cosmos_sdk_proto::cosmos::bank::v1beta1::MsgSend;
+use cosmos_sdk_proto::cosmwasm::wasm::v1::MsgInstantiateContract;
+use cosmos_sdk_proto::prost::Message;
+
+use cosmos_indexer::rpc::decode::decode_tx;
+
+mod common;
+
+use common::{any, coin, execute, tx, tx_with_bytes};
+
+#[test]
+fn decodes_the_body_and_fee() {
+    let decoded = decode_tx(&tx(vec![execute("neutron1sender", br#"{"swap":{}}"#)])).unwrap();
+
+    assert_eq!(decoded.memo, "memo");
+    assert_eq!(decoded.gas_limit, 200_000);
+    assert_eq!(decoded.fee.len(), 1);
+    assert_eq!(decoded.fee[0].amount, "1000");
+    assert_eq!(decoded.sender.as_deref(), Some("neutron1sender"));
+
+    let message = &decoded.messages[0];
+    assert_eq!(message.type_url, "/cosmwasm.wasm.v1.MsgExecuteContract");
+    assert_eq!(message.contract.as_deref(), Some("neutron1contract"));
+    assert_eq!(message.msg, Some(serde_json::json!({ "swap": {} })));
+    assert_eq!(message.funds[0].amount, "5");
+    assert!(message.value.is_none());
+}
+
+#[test]
+fn decodes_instantiations_and_keeps_other_messages_encoded() {
+    let instantiate = MsgInstantiateContract {
+        sender: "neutron1sender".to_string(),
+        code_id: 42,
+        label: "pool".to_string(),
+        msg: br#"{"owner":"neutron1owner"}"#.to_vec(),
+        ..Default::default()
+    };
+    let send = MsgSend {
+        from_address: "neutron1sender".to_string(),
+        to_address: "neutron1receiver".to_string(),
+        amount: vec![coin("1")],
+    };
+    let decoded = decode_tx(&tx(vec![
+        any("/cosmwasm.wasm.v1.MsgInstantiateContract", &instantiate),
+        any("/cosmos.bank.v1beta1.MsgSend", &send),
+    ]))
+    .unwrap();
+
+    let instantiated = &decoded.messages[0];
+    assert_eq!(instantiated.code_id, Some(42));
+    assert_eq!(instantiated.label.as_deref(), Some("pool"));
+    assert_eq!(
+        instantiated.msg,
+        Some(serde_json::json!({ "owner": "neutron1owner" }))
+    );
+
+    let other = &decoded.messages[1];
+    assert_eq!(other.type_url, "/cosmos.bank.v1beta1.MsgSend");
+    assert!(other.msg.is_none());
+    assert_eq!(
+        other.value.as_deref(),
+        Some(
+            general_purpose::STANDARD
+                .encode(send.encode_to_vec())
+                .as_str()
+        )
+    );
+}
+
+#[test]
+fn leaves_contract_msgs_that_arent_json_unparsed() {
+    let decoded = decode_tx(&tx(vec![execute("neutron1sender", b"\x00binary")])).unwrap();
+
+    assert!(decoded.messages[0].msg.is_none());
+}
+
+#[test]
+fn fails_on_undecodable_tx_bytes() {
+    let mut not_base64 = tx_with_bytes(b"");
+    not_base64.tx = "not base64!".to_string();
+    assert!(decode_tx(&not_base64).is_err());
+
+    // A truncated varint isn't a valid protobuf `TxRaw`.
+    assert!(decode_tx(&tx_with_bytes(&[0x0a, 0xff])).is_err());
+
+    let mut malformed = execute("neutron1sender", b"{}");
+    malformed.value = vec![0x0a, 0xff];
+    assert!(decode_tx(&tx(vec![malformed])).is_err());
+}
+
+#[test]
+fn derives_signers_from_every_message_with_a_known_signer() {
+    let send = MsgSend {
+        from_address: "neutron1sender".to_string(),
+        to_address: "neutron1receiver".to_string(),
+        amount: vec![coin("1")],
+    };
+    let exec = MsgExec {
+        grantee: "neutron1grantee".to_string(),
+        msgs: vec![execute("neutron1granter", br#"{"swap":{}}"#)],
+    };
+    let decoded = decode_tx(&tx(vec![
+        any("/cosmos.bank.v1beta1.MsgSend", &send),
+        any("/cosmos.authz.v1beta1.MsgExec", &exec),
+        any("/cosmos.bank.v1beta1.MsgSend", &send),
+        any("/unknown.v1.Msg", &send),
+    ]))
+    .unwrap();
+
+    assert_eq!(decoded.sender.as_deref(), Some("neutron1sender"));
+    assert_eq!(decoded.signers, vec!["neutron1sender", "neutron1grantee"]);
+    assert!(decoded.messages[3].sender.is_none());
+
+    // The messages run by the exec are decoded, their senders are the granters.
+    let executed = &decoded.messages[1].messages[0];
+    assert_eq!(executed.sender.as_deref(), Some("neutron1granter"));
+    assert_eq!(executed.msg, Some(serde_json::json!({ "swap": {} })));
+    assert_eq!(decoded.all_messages().len(), 5);
+}
@@ -0,0 +1,87 @@
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{doc, Document};
+
+use cosmos_indexer::database::events::EventsQuery;
+
+fn log_filter(key: &str, value: &str) -> Document {
+    doc! { "$elemMatch": { "key": key, "value": value } }
+}
+
+#[test]
+fn empty_query_matches_every_event() {
+    assert_eq!(EventsQuery::default().to_filter(), doc! {});
+}
+
+#[test]
+fn filters_on_every_field() {
+    let query = EventsQuery {
+        chain_id: Some("neutron-1".to_string()),
+        keys: vec!["swap".to_string(), "provide".to_string()],
+        contract_address: Some("neutron1contract".to_string()),
+        tx_hash: Some("ABC".to_string()),
+        success: Some(false),
+        from_height: Some(100),
+        to_height: Some(200),
+        attributes: vec![("action".to_string(), "swap".to_string())],
+        ..EventsQuery::default()
+    };
+
+    assert_eq!(
+        query.to_filter(),
+        doc! {
+            "chainId": "neutron-1",
+            "key": { "$in": ["swap", "provide"] },
+            "logs": { "$all": [log_filter("action", "swap")] },
+            "txHash": "ABC",
+            "success": false,
+            "blockHeight": { "$gte": 100_i64, "$lte": 200_i64 },
+            // Failed events have no logs, their contract is the one of the failed message.
+            "$or": [
+                { "logs": log_filter("_contract_address", "neutron1contract") },
+                { "failedMessage.contract": "neutron1contract" },
+            ],
+        }
+    );
+}
+
+#[test]
+fn successful_events_include_the_ones_stored_without_success() {
+    let query = EventsQuery {
+        success: Some(true),
+        ..EventsQuery::default()
+    };
+
+    assert_eq!(query.to_filter(), doc! { "success": { "$ne": false } });
+}
+
+#[test]
+fn resumes_after_the_cursor_in_the_sort_order() {
+    let id = ObjectId::new();
+    let ascending = EventsQuery {
+        after: Some((150, id)),
+        ..EventsQuery::default()
+    };
+    let descending = EventsQuery {
+        descending: true,
+        ..ascending.clone()
+    };
+
+    assert_eq!(
+        ascending.to_filter(),
+        doc! {
+            "$or": [
+                { "blockHeight": { "$gt": 150_i64 } },
+                { "blockHeight": 150_i64, "_id": { "$gt": id } },
+            ]
+        }
+    );
+    assert_eq!(
+        descending.to_filter(),
+        doc! {
+            "$or": [
+                { "blockHeight": { "$lt": 150_i64 } },
+                { "blockHeight": 150_i64, "_id": { "$lt": id } },
+            ]
+        }
+    );
+}
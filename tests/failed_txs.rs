@@ -0,0 +1,175 @@
+use cosmos_sdk_proto::cosmos::authz::v1beta1::MsgExec;
+use cosmos_sdk_proto::cosmos::bank::v1beta1::MsgSend;
+use cosmos_sdk_proto::cosmwasm::wasm::v1::MsgExecuteContract;
+use mongodb::bson::doc;
+use mongodb::bson::oid::ObjectId;
+
+use cosmos_indexer::database::events::EventsQuery;
+use cosmos_indexer::event_matcher::matcher::match_tx;
+use cosmos_indexer::event_matcher::matcher_config::{validate_matcher_config, MatcherConfig};
+use cosmos_indexer::rpc::txs::Tx;
+
+mod common;
+
+use common::{any, coin, execute, tx};
+
+const MATCHERS: &str = r#"
+events:
+  - name: "Swap"
+    key: "swap"
+    patterns:
+      - key: action
+        value: "swap"
+    include_failed: true
+    failed_patterns:
+      - key: contract
+        value: "neutron1contract"
+      - key: msg.swap.max_spread
+        value: "0.01"
+  - name: "Transfer"
+    key: "transfer"
+    patterns:
+      - key: action
+        value: "transfer"
+"#;
+
+fn matcher_config() -> MatcherConfig {
+    serde_yaml::from_str(MATCHERS).unwrap()
+}
+
+fn failed(mut tx: Tx) -> Tx {
+    tx.tx_result.code = 5;
+    tx.tx_result.codespace = "wasm".to_string();
+    tx.tx_result.log = "Max spread assertion".to_string();
+    tx
+}
+
+#[test]
+fn matches_failed_txs_on_their_decoded_messages() {
+    let send = MsgSend {
+        from_address: "neutron1sender".to_string(),
+        to_address: "neutron1receiver".to_string(),
+        amount: vec![coin("1")],
+    };
+    let tx = failed(tx(vec![
+        any("/cosmos.bank.v1beta1.MsgSend", &send),
+        execute("neutron1sender", br#"{"swap":{"max_spread":"0.01"}}"#),
+        execute("neutron1sender", br#"{"swap":{"max_spread":"0.5"}}"#),
+    ]));
+
+    let matched = match_tx(&matcher_config(), &tx);
+    assert_eq!(matched.len(), 1);
+
+    let event = &matched[0];
+    assert_eq!(event.key, "swap");
+    assert!(!event.success());
+    assert_eq!(event.codespace, "wasm");
+    assert_eq!(event.log, "Max spread assertion");
+    // Nothing is made up in place of the events the tx didn't emit.
+    assert!(event.logs.is_empty() && event.full_logs.is_empty());
+
+    let message = event.failed_message.as_ref().unwrap();
+    assert_eq!(message.contract.as_deref(), Some("neutron1contract"));
+    assert_eq!(
+        message.msg,
+        Some(serde_json::json!({ "swap": { "max_spread": "0.01" } }))
+    );
+}
+
+#[test]
+fn matches_messages_executed_through_authz() {
+    let exec = MsgExec {
+        grantee: "neutron1grantee".to_string(),
+        msgs: vec![execute(
+            "neutron1granter",
+            br#"{"swap":{"max_spread":"0.01"}}"#,
+        )],
+    };
+    let tx = failed(tx(vec![any("/cosmos.authz.v1beta1.MsgExec", &exec)]));
+
+    let matched = match_tx(&matcher_config(), &tx);
+    assert_eq!(matched.len(), 1);
+    assert_eq!(
+        matched[0]
+            .failed_message
+            .as_ref()
+            .unwrap()
+            .sender
+            .as_deref(),
+        Some("neutron1granter")
+    );
+}
+
+#[test]
+fn skips_failed_txs_without_matching_messages() {
+    let config = matcher_config();
+
+    let other_contract = failed(tx(vec![any(
+        "/cosmwasm.wasm.v1.MsgExecuteContract",
+        &MsgExecuteContract {
+            sender: "neutron1sender".to_string(),
+            contract: "neutron1other".to_string(),
+            msg: br#"{"swap":{"max_spread":"0.01"}}"#.to_vec(),
+            funds: Vec::new(),
+        },
+    )]));
+    assert!(match_tx(&config, &other_contract).is_empty());
+
+    let mut undecodable = failed(tx(Vec::new()));
+    undecodable.tx = "not base64!".to_string();
+    assert!(match_tx(&config, &undecodable).is_empty());
+}
+
+#[test]
+fn requires_failed_patterns_with_include_failed() {
+    let mut config = matcher_config();
+    config.events[0].failed_patterns.clear();
+    assert_eq!(
+        validate_matcher_config(&config).unwrap_err().to_string(),
+        "Matcher swap includes failed txs without failed_patterns"
+    );
+
+    let mut config = matcher_config();
+    config.events[1].failed_patterns = config.events[0].failed_patterns.clone();
+    assert_eq!(
+        validate_matcher_config(&config).unwrap_err().to_string(),
+        "Matcher transfer has failed_patterns but doesn't include failed txs"
+    );
+}
+
+#[test]
+fn queries_failed_events_by_the_contract_of_their_message() {
+    let id = ObjectId::new();
+    let query = EventsQuery {
+        contract_address: Some("neutron1contract".to_string()),
+        after: Some((150, id)),
+        ..EventsQuery::default()
+    };
+
+    assert_eq!(
+        query.to_filter(),
+        doc! {
+            "$and": [
+                {
+                    "$or": [
+                        {
+                            "logs": {
+                                "$elemMatch": {
+                                    "key": "_contract_address",
+                                    "value": "neutron1contract",
+                                }
+                            }
+                        },
+                        { "failedMessage.contract": "neutron1contract" },
+                    ]
+                },
+                {
+                    "$or": [
+                        { "blockHeight": { "$gt": 150_i64 } },
+                        { "blockHeight": 150_i64, "_id": { "$gt": id } },
+                    ]
+                },
+            ]
+        }
+    );
+}
@@ -0,0 +1,156 @@
+use cosmos_indexer::api::graphql::{self, field_name, type_name};
+use cosmos_indexer::event_matcher::matcher_config::{validate_matcher_config, MatcherConfig};
+use cosmos_indexer::source::RecordedSource;
+use cosmos_indexer::{config, MatcherOptions};
+
+fn matcher_config(key: &str, attributes: &[&str]) -> MatcherConfig {
+    let patterns: Vec<String> = attributes
+        .iter()
+        .map(|attribute| format!("      - key: \"{}\"\n        value: \"1\"\n", attribute))
+        .collect();
+
+    serde_yaml::from_str(&format!(
+        "events:\n  - name: \"Event\"\n    key: \"{}\"\n    patterns:\n{}",
+        key,
+        patterns.concat()
+    ))
+    .unwrap()
+}
+
+fn validation_error(matcher_config: &MatcherConfig) -> String {
+    validate_matcher_config(matcher_config)
+        .unwrap_err()
+        .to_string()
+}
+
+#[test]
+fn normalizes_keys_to_graphql_names() {
+    assert_eq!(type_name("example_event"), "ExampleEvent");
+    assert_eq!(type_name("token-swap.v2"), "TokenSwapV2");
+    assert_eq!(field_name("_contract_address"), "contractAddress");
+    assert_eq!(field_name("Pool-ID"), "poolId");
+    assert_eq!(field_name("1st_asset"), "_1stAsset");
+    assert_eq!(field_name("--"), "");
+}
+
+#[test]
+fn accepts_distinct_names() {
+    let mut matcher_config = matcher_config("swap", &["_contract_address", "action"]);
+    matcher_config
+        .events
+        .extend(self::matcher_config("provide_liquidity", &["action", "key"]).events);
+
+    validate_matcher_config(&matcher_config).unwrap();
+}
+
+#[test]
+fn rejects_keys_without_a_type_name() {
+    assert_eq!(
+        validation_error(&matcher_config("1swap", &["action"])),
+        "Matcher key 1swap isn't a valid graphql type name once normalized to: \"1swap\""
+    );
+    assert_eq!(
+        validation_error(&matcher_config("--", &["action"])),
+        "Matcher key -- isn't a valid graphql type name once normalized to: \"\""
+    );
+}
+
+#[test]
+fn rejects_keys_clashing_with_schema_types() {
+    for (key, type_name) in [
+        ("event_log", "EventLog"),
+        ("query", "Query"),
+        ("attribute-filter", "AttributeFilter"),
+        ("sort_order", "SortOrder"),
+        ("big_int", "BigInt"),
+        ("string", "String"),
+    ] {
+        assert_eq!(
+            validation_error(&matcher_config(key, &["action"])),
+            format!(
+                "Matcher key {} clashes with the graphql type {}",
+                key, type_name
+            )
+        );
+    }
+}
+
+#[test]
+fn rejects_keys_colliding_after_normalization() {
+    let mut colliding = matcher_config("foo-bar", &["action"]);
+    colliding
+        .events
+        .extend(matcher_config("foo_bar", &["action"]).events);
+    assert_eq!(
+        validation_error(&colliding),
+        "Matcher keys foo-bar and foo_bar both map to the graphql type FooBar"
+    );
+
+    let mut connection = matcher_config("foo", &["action"]);
+    connection
+        .events
+        .extend(matcher_config("foo_connection", &["action"]).events);
+    assert_eq!(
+        validation_error(&connection),
+        "Matcher keys foo and foo_connection both map to the graphql type FooConnection"
+    );
+}
+
+#[test]
+fn rejects_attributes_colliding_after_normalization() {
+    assert_eq!(
+        validation_error(&matcher_config("swap", &["pool-id", "pool_id"])),
+        "Attributes pool-id and pool_id of matcher swap both map to the graphql field poolId"
+    );
+    assert_eq!(
+        validation_error(&matcher_config("swap", &["--"])),
+        "Attribute \"--\" of matcher swap isn't a valid graphql field name"
+    );
+}
+
+#[tokio::test]
+async fn serves_block_heights_as_big_int_strings() {
+    let path = format!("{}/tests/fixtures/indexer.yaml", env!("CARGO_MANIFEST_DIR"));
+    let indexer_config = config::load_indexer_config(Some(&path)).unwrap();
+    let matcher_config = matcher_config("swap", &["_contract_address", "action"]);
+    let context = cosmos_indexer::build_replay_context(
+        indexer_config,
+        Some(MatcherOptions {
+            matcher_file_path: None,
+            matcher_config: Some(matcher_config.clone()),
+        }),
+        RecordedSource::default(),
+    )
+    .await
+    .unwrap();
+
+    let schema = graphql::build_schema(context, &matcher_config).unwrap();
+    let response = schema
+        .execute(
+            r#"{
+                __type(name: "Swap") {
+                    fields { name type { ofType { name } } }
+                }
+            }"#,
+        )
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+    let data = response.data.into_json().unwrap();
+    let fields = data["__type"]["fields"].as_array().unwrap();
+    let block_height = fields
+        .iter()
+        .find(|field| field["name"] == "blockHeight")
+        .unwrap();
+    assert_eq!(block_height["type"]["ofType"]["name"], "BigInt");
+    assert!(fields
+        .iter()
+        .any(|field| field["name"] == "contractAddress"));
+
+    // Heights that aren't unsigned integers are rejected before the database is queried.
+    let response = schema
+        .execute(r#"{ swap(fromHeight: "-1") { nextCursor } }"#)
+        .await;
+    assert_eq!(response.errors.len(), 1);
+    assert!(response.errors[0].message.contains("fromHeight"));
+}
@@ -0,0 +1,413 @@
+use base64::{engine::general_purpose, Engine as _};
+use cosmos_sdk_proto::cosmos::base::abci::v1beta1::TxResponse;
+use cosmos_sdk_proto::cosmos::base::query::v1beta1::PageResponse;
+use cosmos_sdk_proto::cosmos::base::tendermint::v1beta1::service_server::{
+    Service as TendermintService, ServiceServer as TendermintServiceServer,
+};
+use cosmos_sdk_proto::cosmos::base::tendermint::v1beta1::{
+    AbciQueryRequest, AbciQueryResponse, GetBlockByHeightRequest, GetBlockByHeightResponse,
+    GetLatestBlockRequest, GetLatestBlockResponse, GetLatestValidatorSetRequest,
+    GetLatestValidatorSetResponse, GetNodeInfoRequest, GetNodeInfoResponse, GetSyncingRequest,
+    GetSyncingResponse, GetValidatorSetByHeightRequest, GetValidatorSetByHeightResponse,
+};
+use cosmos_sdk_proto::cosmos::tx::v1beta1::service_server::{
+    Service as TxService, ServiceServer as TxServiceServer,
+};
+use cosmos_sdk_proto::cosmos::tx::v1beta1::{
+    BroadcastTxRequest, BroadcastTxResponse, GetBlockWithTxsRequest, GetBlockWithTxsResponse,
+    GetTxRequest, GetTxResponse, GetTxsEventRequest, GetTxsEventResponse, SimulateRequest,
+    SimulateResponse,
+};
+use cosmos_sdk_proto::tendermint::serializers::timestamp::Rfc3339;
+use cosmos_sdk_proto::tendermint::v0_34::abci::{Event, EventAttribute};
+use cosmos_sdk_proto::tendermint::v0_34::types::{Block, BlockId, Header};
+use cosmos_sdk_proto::Any;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use tonic::{Request, Response, Status};
+
+use cosmos_indexer::rpc::blockchain::BlockMeta;
+use cosmos_indexer::rpc::grpc;
+use cosmos_indexer::rpc::txs::Tx;
+use cosmos_indexer::source::{BlockSource, GrpcSource, MemorySource, RecordedSource};
+use cosmos_indexer::{commands, IndexerContext};
+
+mod common;
+
+use common::{fixture_path, swap, swaps};
+
+/// Serves the blocks and txs of the fixture recording over the cosmos sdk grpc services. A
+/// `legacy` node predates sdk 0.46: it pages and counts txs in `pagination` only.
+struct FakeGrpcNode {
+    block_metas: Vec<BlockMeta>,
+    tx_responses: Vec<TxResponse>,
+    legacy: bool,
+    /// `page` and `limit` that every `GetTxsEvent` request was served with.
+    pages: Mutex<Vec<(u64, u64)>>,
+}
+
+impl FakeGrpcNode {
+    async fn new(legacy: bool) -> Self {
+        let (block_metas, txs) = recorded(100, 103).await;
+
+        FakeGrpcNode {
+            block_metas,
+            tx_responses: txs.into_iter().map(tx_response).collect(),
+            legacy,
+            pages: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn block(&self, height: u64) -> Option<(BlockId, Block)> {
+        let block_meta = self
+            .block_metas
+            .iter()
+            .find(|block_meta| block_meta.height == height)?;
+        let time: Rfc3339 = serde_json::from_value(json!(block_meta.time)).unwrap();
+        let block_id = |hash: &str| BlockId {
+            hash: hash.as_bytes().to_vec(),
+            part_set_header: None,
+        };
+
+        Some((
+            block_id(&block_meta.hash),
+            Block {
+                header: Some(Header {
+                    height: height as i64,
+                    time: Some(time.into()),
+                    last_block_id: Some(block_id(&block_meta.parent_hash)),
+                    proposer_address: block_meta.proposer_address.as_bytes().to_vec(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ))
+    }
+
+    /// Serves the node on a free local port, returns its url.
+    async fn serve(self) -> (Arc<Self>, String) {
+        let node = Arc::new(self);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let incoming = futures::stream::unfold(listener, |listener| async move {
+            let stream = listener.accept().await.map(|(stream, _)| stream);
+            Some((stream, listener))
+        });
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(TendermintServiceServer::from_arc(node.clone()))
+                .add_service(TxServiceServer::from_arc(node.clone()))
+                .serve_with_incoming(incoming),
+        );
+
+        (node, format!("http://{}", address))
+    }
+}
+
+#[tonic::async_trait]
+impl TendermintService for FakeGrpcNode {
+    async fn get_latest_block(
+        &self,
+        _request: Request<GetLatestBlockRequest>,
+    ) -> Result<Response<GetLatestBlockResponse>, Status> {
+        let (block_id, block) = self.block(self.block_metas.last().unwrap().height).unwrap();
+
+        Ok(Response::new(GetLatestBlockResponse {
+            block_id: Some(block_id),
+            block: Some(block),
+            sdk_block: None,
+        }))
+    }
+
+    async fn get_node_info(
+        &self,
+        _request: Request<GetNodeInfoRequest>,
+    ) -> Result<Response<GetNodeInfoResponse>, Status> {
+        Err(Status::unimplemented("GetNodeInfo"))
+    }
+
+    async fn get_syncing(
+        &self,
+        _request: Request<GetSyncingRequest>,
+    ) -> Result<Response<GetSyncingResponse>, Status> {
+        Err(Status::unimplemented("GetSyncing"))
+    }
+
+    async fn get_block_by_height(
+        &self,
+        _request: Request<GetBlockByHeightRequest>,
+    ) -> Result<Response<GetBlockByHeightResponse>, Status> {
+        Err(Status::unimplemented("GetBlockByHeight"))
+    }
+
+    async fn get_latest_validator_set(
+        &self,
+        _request: Request<GetLatestValidatorSetRequest>,
+    ) -> Result<Response<GetLatestValidatorSetResponse>, Status> {
+        Err(Status::unimplemented("GetLatestValidatorSet"))
+    }
+
+    async fn get_validator_set_by_height(
+        &self,
+        _request: Request<GetValidatorSetByHeightRequest>,
+    ) -> Result<Response<GetValidatorSetByHeightResponse>, Status> {
+        Err(Status::unimplemented("GetValidatorSetByHeight"))
+    }
+
+    async fn abci_query(
+        &self,
+        _request: Request<AbciQueryRequest>,
+    ) -> Result<Response<AbciQueryResponse>, Status> {
+        Err(Status::unimplemented("ABCIQuery"))
+    }
+}
+
+#[tonic::async_trait]
+impl TxService for FakeGrpcNode {
+    /// Takes the `tx.height=a` and `tx.height>=a`, `tx.height<=b` events of the indexer.
+    #[allow(deprecated)]
+    async fn get_txs_event(
+        &self,
+        request: Request<GetTxsEventRequest>,
+    ) -> Result<Response<GetTxsEventResponse>, Status> {
+        let request = request.into_inner();
+        let heights: Vec<u64> = request
+            .events
+            .iter()
+            .map(|event| {
+                event
+                    .trim_start_matches(|c: char| !c.is_ascii_digit())
+                    .parse()
+                    .unwrap()
+            })
+            .collect();
+        let (offset, limit) = match request.pagination.as_ref() {
+            Some(pagination) if self.legacy => (pagination.offset, pagination.limit),
+            _ => ((request.page - 1) * request.limit, request.limit),
+        };
+        self.pages.lock().unwrap().push((offset / limit + 1, limit));
+
+        let range_txs: Vec<&TxResponse> = self
+            .tx_responses
+            .iter()
+            .filter(|tx| (heights[0]..=*heights.last().unwrap()).contains(&(tx.height as u64)))
+            .collect();
+        let total = range_txs.len() as u64;
+        let tx_responses = range_txs
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect();
+
+        Ok(Response::new(if self.legacy {
+            GetTxsEventResponse {
+                tx_responses,
+                pagination: Some(PageResponse {
+                    next_key: Vec::new(),
+                    total,
+                }),
+                ..Default::default()
+            }
+        } else {
+            GetTxsEventResponse {
+                tx_responses,
+                total,
+                ..Default::default()
+            }
+        }))
+    }
+
+    async fn get_block_with_txs(
+        &self,
+        request: Request<GetBlockWithTxsRequest>,
+    ) -> Result<Response<GetBlockWithTxsResponse>, Status> {
+        let height = request.into_inner().height as u64;
+        let (block_id, block) = self
+            .block(height)
+            .ok_or_else(|| Status::not_found(format!("Block at height: {}", height)))?;
+
+        Ok(Response::new(GetBlockWithTxsResponse {
+            block_id: Some(block_id),
+            block: Some(block),
+            ..Default::default()
+        }))
+    }
+
+    async fn simulate(
+        &self,
+        _request: Request<SimulateRequest>,
+    ) -> Result<Response<SimulateResponse>, Status> {
+        Err(Status::unimplemented("Simulate"))
+    }
+
+    async fn get_tx(
+        &self,
+        _request: Request<GetTxRequest>,
+    ) -> Result<Response<GetTxResponse>, Status> {
+        Err(Status::unimplemented("GetTx"))
+    }
+
+    async fn broadcast_tx(
+        &self,
+        _request: Request<BroadcastTxRequest>,
+    ) -> Result<Response<BroadcastTxResponse>, Status> {
+        Err(Status::unimplemented("BroadcastTx"))
+    }
+}
+
+/// Block metas and txs of the fixture recording, as the rpc serves them.
+async fn recorded(from_height: u64, to_height: u64) -> (Vec<BlockMeta>, Vec<Tx>) {
+    let source = RecordedSource::open(&fixture_path("recording.jsonl.gz")).unwrap();
+    let context = common::context(Arc::new(MemorySource::new()), |_| {}, None).await;
+
+    (
+        source
+            .block_metas(context.clone(), from_height, to_height)
+            .await
+            .unwrap(),
+        source.txs(context, from_height, to_height).await.unwrap(),
+    )
+}
+
+/// The grpc response of a tx recorded with plain attributes.
+fn tx_response(tx: Tx) -> TxResponse {
+    let events = tx.tx_result.events.unwrap_or_default();
+
+    TxResponse {
+        height: tx.height as i64,
+        txhash: tx.hash,
+        code: tx.tx_result.code as u32,
+        codespace: tx.tx_result.codespace,
+        raw_log: tx.tx_result.log,
+        events: events
+            .into_iter()
+            .map(|event| Event {
+                r#type: event.type_str.unwrap_or_default(),
+                attributes: event
+                    .attributes
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|attribute| EventAttribute {
+                        key: attribute.key.unwrap_or_default().into(),
+                        value: attribute.value.unwrap_or_default().into(),
+                        index: true,
+                    })
+                    .collect(),
+            })
+            .collect(),
+        tx: Some(Any {
+            type_url: "/cosmos.tx.v1beta1.Tx".to_string(),
+            value: general_purpose::STANDARD.decode(&tx.tx).unwrap(),
+        }),
+        ..Default::default()
+    }
+}
+
+/// Upper case hex of the bytes of `text`, as the source returns hashes and addresses.
+fn hex(text: &str) -> String {
+    text.bytes().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+/// `time` as nodes format it, which drops trailing zeros from the nanoseconds.
+fn canonical_time(time: &str) -> String {
+    let time: Rfc3339 = serde_json::from_value(json!(time)).unwrap();
+
+    serde_json::to_value(time)
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Context reading from the node at `url` with `tx_search_per_page` txs per request.
+async fn grpc_context(url: String, tx_search_per_page: u64) -> Arc<IndexerContext> {
+    let channel = grpc::connect(&url).unwrap();
+
+    common::context(
+        Arc::new(GrpcSource::new(channel)),
+        |config| {
+            config.grpc_enabled = true;
+            config.grpc_endpoint = Some(url);
+            config.tx_search_per_page = tx_search_per_page;
+        },
+        None,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn reads_the_head_and_the_headers_of_a_range() {
+    let (_node, url) = FakeGrpcNode::new(false).await.serve().await;
+    let context = grpc_context(url, 100).await;
+
+    assert_eq!(
+        context.source.head_height(context.clone()).await.unwrap(),
+        103
+    );
+
+    let (recorded_block_metas, _) = recorded(100, 102).await;
+    let block_metas = context
+        .source
+        .block_metas(context.clone(), 100, 102)
+        .await
+        .unwrap();
+    let expected: Vec<BlockMeta> = recorded_block_metas
+        .into_iter()
+        .map(|block_meta| BlockMeta {
+            hash: hex(&block_meta.hash),
+            parent_hash: hex(&block_meta.parent_hash),
+            proposer_address: hex(&block_meta.proposer_address),
+            time: canonical_time(&block_meta.time),
+            ..block_meta
+        })
+        .collect();
+    assert_eq!(block_metas, expected);
+
+    let err = context
+        .source
+        .block_metas(context.clone(), 103, 104)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Block at height: 104"));
+}
+
+#[tokio::test]
+async fn pages_through_the_txs_of_old_and_new_nodes() {
+    for legacy in [false, true] {
+        let (node, url) = FakeGrpcNode::new(legacy).await.serve().await;
+        let context = grpc_context(url, 2).await;
+
+        let txs = context.source.txs(context.clone(), 100, 103).await.unwrap();
+
+        // Same txs, events and indexes as the rpc recorded, paged 2 by 2.
+        let (_, recorded_txs) = recorded(100, 103).await;
+        assert_eq!(
+            serde_json::to_value(&txs).unwrap(),
+            serde_json::to_value(&recorded_txs).unwrap()
+        );
+        assert_eq!(
+            *node.pages.lock().unwrap(),
+            vec![(1, 2), (2, 2), (3, 2), (4, 2)]
+        );
+    }
+}
+
+#[tokio::test]
+async fn matches_the_same_events_as_the_rpc() {
+    let (_node, url) = FakeGrpcNode::new(false).await.serve().await;
+    let context = grpc_context(url, 100).await;
+
+    assert_eq!(
+        swaps(&commands::replay(context, 100, 103).await.unwrap()),
+        vec![
+            swap(100, "2000"),
+            swap(100, "2001"),
+            swap(102, "2020"),
+            swap(102, "2021"),
+            swap(102, "2022"),
+            swap(102, "2023"),
+            swap(103, "2030"),
+        ]
+    );
+}
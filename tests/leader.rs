@@ -0,0 +1,105 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+use cosmos_indexer::leader::{self, Lease, MemoryLeaseStore};
+use cosmos_indexer::source::MemorySource;
+use cosmos_indexer::IndexerContext;
+
+mod common;
+
+const DURATION: Duration = Duration::from_millis(300);
+const RENEW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A replica named `holder` with its lease in `store`.
+async fn replica(store: Arc<MemoryLeaseStore>, holder: &str) -> Arc<IndexerContext> {
+    let lease = Lease::new(holder.to_string(), DURATION, RENEW_INTERVAL, store);
+
+    common::context(Arc::new(MemorySource::new()), |_| {}, Some(lease)).await
+}
+
+fn lease(context: &IndexerContext) -> &Lease {
+    context.lease.as_ref().unwrap()
+}
+
+#[tokio::test]
+async fn is_held_until_the_renew_interval_before_it_expires() {
+    let store = Arc::new(MemoryLeaseStore::new());
+    let context = replica(store.clone(), "a").await;
+    assert!(!lease(&context).is_held());
+
+    assert!(lease(&context).try_acquire(context.clone()).await.unwrap());
+    assert!(lease(&context).is_held());
+
+    // Stops writing while the lease is still its own in the store.
+    sleep(DURATION - RENEW_INTERVAL / 2).await;
+    assert!(!lease(&context).is_held());
+    assert_eq!(store.holder("test-1").as_deref(), Some("a"));
+
+    assert!(lease(&context).try_acquire(context.clone()).await.unwrap());
+    lease(&context).release(context.clone()).await.unwrap();
+    assert!(!lease(&context).is_held());
+    assert_eq!(store.holder("test-1"), None);
+}
+
+#[tokio::test]
+async fn takes_over_expired_leases_only() {
+    let store = Arc::new(MemoryLeaseStore::new());
+    let a = replica(store.clone(), "a").await;
+    let b = replica(store.clone(), "b").await;
+
+    assert!(lease(&a).try_acquire(a.clone()).await.unwrap());
+    assert!(!lease(&b).try_acquire(b.clone()).await.unwrap());
+    assert!(!lease(&b).is_held());
+
+    sleep(DURATION + RENEW_INTERVAL).await;
+    assert!(lease(&b).try_acquire(b.clone()).await.unwrap());
+    assert!(!lease(&a).try_acquire(a.clone()).await.unwrap());
+    assert!(!lease(&a).is_held());
+    assert_eq!(store.holder("test-1").as_deref(), Some("b"));
+}
+
+#[tokio::test]
+async fn hold_renews_the_lease_until_it_is_taken_over() {
+    let store = Arc::new(MemoryLeaseStore::new());
+    let a = replica(store.clone(), "a").await;
+    let b = replica(store.clone(), "b").await;
+
+    assert!(lease(&a).try_acquire(a.clone()).await.unwrap());
+    let holding = tokio::spawn(leader::hold(a.clone()));
+
+    sleep(DURATION * 3).await;
+    assert!(!holding.is_finished());
+    assert!(lease(&a).is_held());
+    assert!(!lease(&b).try_acquire(b.clone()).await.unwrap());
+
+    // A replica that stopped renewing loses the lease once another one took it over.
+    holding.abort();
+    sleep(DURATION + RENEW_INTERVAL).await;
+    assert!(lease(&b).try_acquire(b.clone()).await.unwrap());
+    timeout(Duration::from_secs(1), leader::hold(a.clone()))
+        .await
+        .unwrap();
+    assert!(!lease(&a).is_held());
+}
+
+#[tokio::test]
+async fn acquire_stands_by_until_the_lease_is_released() {
+    let store = Arc::new(MemoryLeaseStore::new());
+    let a = replica(store.clone(), "a").await;
+    let b = replica(store.clone(), "b").await;
+
+    assert!(lease(&a).try_acquire(a.clone()).await.unwrap());
+    let acquiring = tokio::spawn(leader::acquire(b.clone()));
+
+    sleep(RENEW_INTERVAL * 2).await;
+    assert!(!acquiring.is_finished());
+
+    lease(&a).release(a.clone()).await.unwrap();
+    timeout(Duration::from_secs(1), acquiring)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(lease(&b).is_held());
+    assert_eq!(store.holder("test-1").as_deref(), Some("b"));
+}
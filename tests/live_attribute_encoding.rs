@@ -0,0 +1,149 @@
+use axum::routing::get;
+use axum::{Json, Router};
+use base64::{engine::general_purpose, Engine as _};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use cosmos_indexer::event_matcher::matcher::match_tx;
+use cosmos_indexer::rpc::txs::{decode_attributes, AttributeEncoding};
+use cosmos_indexer::rpc::websocket::{handle_event, LiveTxs};
+use cosmos_indexer::{IndexerConfig, IndexerContext};
+
+mod common;
+
+use common::{block, swap, swaps};
+
+/// Serves the `/status` of a node running `version`, returns its url and the count of requests.
+fn serve_status(version: &'static str) -> (String, Arc<AtomicU64>) {
+    let requests = Arc::new(AtomicU64::new(0));
+    let counted = requests.clone();
+    let router = Router::new().route(
+        "/status",
+        get(move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+            async move { Json(json!({ "result": { "node_info": { "version": version } } })) }
+        }),
+    );
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(
+        axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(router.into_make_service()),
+    );
+
+    (format!("http://{}", address), requests)
+}
+
+/// Context fetching blocks over grpc from a node that is never reached, the database client
+/// connects lazily.
+async fn grpc_context(
+    rpc_endpoint: String,
+    configure: impl FnOnce(&mut IndexerConfig),
+) -> Arc<IndexerContext> {
+    let mut indexer_config = common::indexer_config();
+    indexer_config.rpc_endpoint = rpc_endpoint;
+    indexer_config.grpc_enabled = true;
+    indexer_config.grpc_endpoint = Some("http://127.0.0.1:1".to_string());
+    configure(&mut indexer_config);
+
+    cosmos_indexer::build_context(indexer_config, common::matcher_options())
+        .await
+        .unwrap()
+}
+
+fn base64(value: &str) -> String {
+    general_purpose::STANDARD.encode(value)
+}
+
+/// Swap tx of the fixture contract, as a 0.34 node sends it over the websocket.
+fn base64_swap_event(height: u64) -> String {
+    let attributes: Vec<Value> = [
+        ("_contract_address", "neutron1contract"),
+        ("action", "swap"),
+        ("amount", "100"),
+    ]
+    .iter()
+    .map(|(key, value)| json!({ "key": base64(key), "value": base64(value) }))
+    .collect();
+
+    json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "result": {
+            "query": "tm.event='Tx'",
+            "data": {
+                "type": "tendermint/event/Tx",
+                "value": {
+                    "TxResult": {
+                        "height": height.to_string(),
+                        "index": 0,
+                        "tx": "dHg=",
+                        "result": {
+                            "events": [{ "type": "wasm", "attributes": attributes }],
+                        },
+                    },
+                },
+            },
+            "events": { "tx.hash": [format!("TX{}-0", height)] },
+        },
+    })
+    .to_string()
+}
+
+fn new_block_event(height: u64) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "result": {
+            "query": "tm.event='NewBlock'",
+            "data": {
+                "type": "tendermint/event/NewBlock",
+                "value": {
+                    "block": {
+                        "header": {
+                            "height": height.to_string(),
+                            "last_block_id": { "hash": format!("HASH{}", height - 1) },
+                        },
+                        "data": { "txs": ["dHg="] },
+                    },
+                },
+            },
+        },
+    })
+    .to_string()
+}
+
+#[tokio::test]
+async fn decodes_the_live_txs_of_a_grpc_indexer_with_the_rpc_encoding() {
+    let (rpc_endpoint, requests) = serve_status("0.34.27");
+    let context = grpc_context(rpc_endpoint, |indexer_config| {
+        indexer_config.websocket_enabled = true;
+    })
+    .await;
+    assert_eq!(context.attribute_encoding, AttributeEncoding::Base64);
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    let live_txs = LiveTxs::new();
+    handle_event(&base64_swap_event(100), &live_txs).unwrap();
+    handle_event(&new_block_event(100), &live_txs).unwrap();
+    let mut txs = live_txs.take(100, 100, &[block(100)]).unwrap();
+    decode_attributes(&mut txs, context.attribute_encoding);
+
+    let events = match_tx(&context.matcher_config, &txs[0]);
+    assert_eq!(swaps(&events), vec![swap(100, "100")]);
+}
+
+#[tokio::test]
+async fn skips_the_node_version_of_a_grpc_indexer_without_websocket() {
+    let (rpc_endpoint, requests) = serve_status("0.34.27");
+    let context = grpc_context(rpc_endpoint, |indexer_config| {
+        indexer_config.websocket_enabled = false;
+    })
+    .await;
+
+    assert_eq!(context.attribute_encoding, AttributeEncoding::Plain);
+    assert_eq!(requests.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,118 @@
+use serde_json::json;
+
+use cosmos_indexer::event_matcher::matcher::{group_wasm_logs, match_tx};
+use cosmos_indexer::event_matcher::matcher_config::{load_matcher_config_from_file, MatcherConfig};
+use cosmos_indexer::rpc::txs::Tx;
+use cosmos_indexer::source::RecordedSource;
+use cosmos_indexer::MatcherOptions;
+
+mod common;
+
+/// Writes `content` to a matchers file unique to the test, returns its path.
+fn matchers_file(name: &str, content: &str) -> String {
+    let path = std::env::temp_dir().join(format!("matchers-{}-{}.yaml", name, std::process::id()));
+    std::fs::write(&path, content).unwrap();
+
+    path.to_str().unwrap().to_string()
+}
+
+/// A successful tx with one wasm event holding `attributes`.
+fn wasm_tx(attributes: &[(&str, &str)]) -> Tx {
+    let attributes: Vec<_> = attributes
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "value": value }))
+        .collect();
+
+    serde_json::from_value(json!({
+        "hash": "HASH",
+        "height": "100",
+        "index": 0,
+        "tx_result": {
+            "code": 0,
+            "events": [{ "type": "wasm", "attributes": attributes }],
+        },
+    }))
+    .unwrap()
+}
+
+fn pairs(logs: &[(String, String)]) -> Vec<(&str, &str)> {
+    logs.iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect()
+}
+
+#[test]
+fn loads_matchers_without_patterns() {
+    let path = matchers_file(
+        "no-patterns",
+        "events:\n  - name: \"Any\"\n    key: \"any\"\n    patterns: []\n",
+    );
+
+    let matcher_config = load_matcher_config_from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(matcher_config.events[0].patterns.is_empty());
+}
+
+#[test]
+fn returns_errors_instead_of_panicking() {
+    let err = load_matcher_config_from_file("/nonexistent/matchers.yaml").unwrap_err();
+    assert!(err.to_string().starts_with("Failed to open matcher config"));
+
+    let path = matchers_file("invalid", "events: [");
+    let err = load_matcher_config_from_file(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert!(err
+        .to_string()
+        .starts_with("Failed to parse matcher config"));
+
+    let path = matchers_file(
+        "duplicated",
+        "events:\n  - name: \"A\"\n    key: \"swap\"\n    patterns: []\n  - name: \"B\"\n    key: \"swap\"\n    patterns: []\n",
+    );
+    let err = load_matcher_config_from_file(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(err.to_string(), "Matcher key swap is duplicated");
+}
+
+#[tokio::test]
+async fn fails_to_build_a_context_with_invalid_matchers() {
+    let result = cosmos_indexer::build_replay_context(
+        common::indexer_config(),
+        Some(MatcherOptions {
+            matcher_file_path: Some("/nonexistent/matchers.yaml".to_string()),
+            matcher_config: None,
+        }),
+        RecordedSource::default(),
+    )
+    .await;
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn starts_a_group_at_every_contract_address() {
+    let tx = wasm_tx(&[
+        ("_contract_address", "neutron1a"),
+        ("action", "swap"),
+        ("_contract_address", "neutron1b"),
+        ("action", "transfer"),
+    ]);
+
+    let groups = group_wasm_logs(&tx);
+    let groups: Vec<Vec<(&str, &str)>> = groups.iter().map(|(logs, _)| pairs(logs)).collect();
+    // The attributes before the first contract address form a group, even when there are none.
+    assert_eq!(
+        groups,
+        vec![
+            vec![],
+            vec![("_contract_address", "neutron1a"), ("action", "swap")],
+            vec![("_contract_address", "neutron1b"), ("action", "transfer")],
+        ]
+    );
+
+    // A matcher without patterns matches every group.
+    let matcher_config: MatcherConfig =
+        serde_yaml::from_str("events:\n  - name: \"Any\"\n    key: \"any\"\n    patterns: []\n")
+            .unwrap();
+    assert_eq!(match_tx(&matcher_config, &tx).len(), 3);
+}
@@ -0,0 +1,149 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use cosmos_indexer::pipeline::{self, MatchedRange};
+use cosmos_indexer::rpc::txs::Tx;
+use cosmos_indexer::source::MemorySource;
+use cosmos_indexer::IndexerContext;
+
+mod common;
+
+use common::{block, fixture_txs, swap, swaps};
+
+/// Blocks 100 to 103 holding the txs of the recorded `tx_search` range.
+fn source() -> Arc<MemorySource> {
+    let fixture_txs = fixture_txs();
+
+    let source = MemorySource::new();
+    for height in 100..=103 {
+        let txs: Vec<Tx> = fixture_txs
+            .iter()
+            .filter(|tx| tx.height == height)
+            .cloned()
+            .collect();
+        source.insert_block(block(height), txs);
+    }
+
+    Arc::new(source)
+}
+
+/// The fetch and match stages never use the database.
+async fn context(
+    source: Arc<MemorySource>,
+    configure: impl FnOnce(&mut cosmos_indexer::IndexerConfig),
+) -> Arc<IndexerContext> {
+    common::context(source, configure, None).await
+}
+
+/// Runs the fetch and match stages of the live loop from `last_indexed_height`.
+fn start(context: Arc<IndexerContext>, last_indexed_height: u64) -> mpsc::Receiver<MatchedRange> {
+    let (fetched_sender, fetched_receiver) = mpsc::channel(4);
+    let (matched_sender, matched_receiver) = mpsc::channel(4);
+
+    tokio::spawn(pipeline::fetch_stage(
+        context.clone(),
+        last_indexed_height,
+        fetched_sender,
+    ));
+    tokio::spawn(pipeline::match_stage(
+        context,
+        fetched_receiver,
+        matched_sender,
+    ));
+
+    matched_receiver
+}
+
+struct Range {
+    from_height: u64,
+    to_height: u64,
+    /// Height and amount of every matched swap.
+    swaps: Vec<(u64, String)>,
+}
+
+async fn next_range(receiver: &mut mpsc::Receiver<MatchedRange>) -> Range {
+    let range = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("no range within 5 seconds")
+        .unwrap();
+
+    match range {
+        MatchedRange::Blocks {
+            from_height,
+            to_height,
+            events,
+            headers,
+            ..
+        } => {
+            for event in events.iter() {
+                let header = headers.get(event.tx_height).unwrap();
+                assert_eq!(header.hash, format!("HASH{}", event.tx_height));
+            }
+
+            Range {
+                from_height,
+                to_height,
+                swaps: swaps(&events),
+            }
+        }
+        _ => panic!("expected a range of blocks"),
+    }
+}
+
+#[tokio::test]
+async fn indexes_the_source_in_batches() {
+    let context = context(source(), |_| {}).await;
+    let mut receiver = start(context.clone(), 99);
+
+    let first = next_range(&mut receiver).await;
+    assert_eq!((first.from_height, first.to_height), (100, 101));
+    assert_eq!(first.swaps, vec![swap(100, "2000"), swap(100, "2001")]);
+
+    let second = next_range(&mut receiver).await;
+    assert_eq!((second.from_height, second.to_height), (102, 103));
+    assert_eq!(
+        second.swaps,
+        vec![
+            swap(102, "2020"),
+            swap(102, "2021"),
+            swap(102, "2022"),
+            swap(102, "2023"),
+            swap(103, "2030")
+        ]
+    );
+    assert_eq!(context.loop_state.head_height(), 103);
+}
+
+#[tokio::test]
+async fn follows_new_blocks() {
+    let source = source();
+    let context = context(source.clone(), |_| {}).await;
+    let mut receiver = start(context, 103);
+
+    let mut tx = fixture_txs().pop().unwrap();
+    tx.hash = "NEWTX".to_string();
+    source.insert_block(block(104), vec![tx]);
+
+    let range = next_range(&mut receiver).await;
+    assert_eq!((range.from_height, range.to_height), (104, 104));
+    assert_eq!(range.swaps, vec![swap(104, "2030")]);
+}
+
+#[tokio::test]
+async fn stays_the_confirmation_depth_below_the_head() {
+    let context = context(source(), |indexer_config| {
+        indexer_config.confirmation_depth = 2;
+        indexer_config.block_lag_batch_size = 10;
+    })
+    .await;
+    let mut receiver = start(context, 99);
+
+    let range = next_range(&mut receiver).await;
+    assert_eq!((range.from_height, range.to_height), (100, 101));
+    assert!(
+        tokio::time::timeout(Duration::from_millis(200), receiver.recv())
+            .await
+            .is_err()
+    );
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use cosmos_indexer::reorg::{ForkTooDeep, ReorgCheck, ReorgGuard, Rollback};
+use cosmos_indexer::rpc::blockchain::BlockMeta;
+use cosmos_indexer::source::MemorySource;
+use cosmos_indexer::IndexerContext;
+
+mod common;
+
+fn block(height: u64, branch: &str) -> BlockMeta {
+    BlockMeta {
+        height,
+        hash: format!("{}{}", branch, height),
+        parent_hash: format!("{}{}", branch, height - 1),
+        time: "2024-01-01T00:00:00Z".to_string(),
+        proposer_address: "PROPOSER".to_string(),
+    }
+}
+
+/// Replaces the blocks of `from_height..=to_height` by a branch forked from `from_height - 1`.
+fn fork(source: &MemorySource, from_height: u64, to_height: u64) {
+    for height in from_height..=to_height {
+        let mut forked = block(height, "FORK");
+        if height == from_height {
+            forked.parent_hash = format!("HASH{}", height - 1);
+        }
+        source.insert_block(forked, Vec::new());
+    }
+}
+
+/// A source serving blocks 1 to 8 and a guard that indexed up to block 5. The guard only reads
+/// blocks from the source.
+async fn setup(max_depth: u64) -> (Arc<MemorySource>, Arc<IndexerContext>, ReorgGuard) {
+    let source = Arc::new(MemorySource::new());
+    for height in 1..=8 {
+        source.insert_block(block(height, "HASH"), Vec::new());
+    }
+    let guard = ReorgGuard::new(max_depth, (1..=5).map(|height| block(height, "HASH")));
+
+    (
+        source.clone(),
+        common::context(source, |_| {}, None).await,
+        guard,
+    )
+}
+
+#[tokio::test]
+async fn accepts_blocks_extending_the_indexed_chain() {
+    let (_, context, mut guard) = setup(10).await;
+
+    match guard.check_range(context, 6, 8).await.unwrap() {
+        ReorgCheck::Valid(blocks) => {
+            let heights: Vec<u64> = blocks.iter().map(|block| block.height).collect();
+            assert_eq!(heights, vec![6, 7, 8]);
+        }
+        ReorgCheck::Forked { .. } => panic!("Unexpected fork"),
+    }
+}
+
+#[tokio::test]
+async fn finds_the_highest_common_block_of_a_fork() {
+    let (source, context, mut guard) = setup(10).await;
+    fork(&source, 4, 8);
+
+    match guard.check_range(context.clone(), 6, 8).await.unwrap() {
+        ReorgCheck::Forked { fork_height } => assert_eq!(fork_height, 3),
+        ReorgCheck::Valid(_) => panic!("Fork not detected"),
+    }
+
+    // The stale hashes are dropped, so the new branch is valid from the fork on.
+    match guard.check_range(context, 4, 8).await.unwrap() {
+        ReorgCheck::Valid(blocks) => assert_eq!(blocks[0].hash, "FORK4"),
+        ReorgCheck::Forked { .. } => panic!("Unexpected fork"),
+    }
+}
+
+#[tokio::test]
+async fn detects_a_fork_of_the_last_indexed_block() {
+    let (source, context, mut guard) = setup(10).await;
+    fork(&source, 5, 8);
+
+    match guard.check_range(context, 6, 6).await.unwrap() {
+        ReorgCheck::Forked { fork_height } => assert_eq!(fork_height, 4),
+        ReorgCheck::Valid(_) => panic!("Fork not detected"),
+    }
+}
+
+#[tokio::test]
+async fn fails_on_forks_deeper_than_the_known_blocks() {
+    let (source, context, mut guard) = setup(2).await;
+    fork(&source, 2, 8);
+
+    let err = guard.check_range(context, 6, 8).await.err().unwrap();
+    let too_deep = err.downcast_ref::<ForkTooDeep>().unwrap();
+    assert_eq!(too_deep.max_depth, 2);
+    assert_eq!(too_deep.lowest_known_height, 3);
+}
+
+#[tokio::test]
+async fn rejects_ranges_that_changed_while_fetching() {
+    let (source, context, mut guard) = setup(10).await;
+    let mut changed = block(7, "HASH");
+    changed.parent_hash = "OTHER6".to_string();
+    source.insert_block(changed, Vec::new());
+
+    let err = guard.check_range(context, 6, 8).await.err().unwrap();
+    assert!(!err.is::<ForkTooDeep>());
+}
+
+#[test]
+fn serializes_rollbacks_with_their_type() {
+    let rollback = Rollback {
+        chain_id: "neutron-1".to_string(),
+        fork_height: 3,
+    };
+
+    assert_eq!(
+        serde_json::to_value(&rollback).unwrap(),
+        serde_json::json!({ "type": "rollback", "chainId": "neutron-1", "forkHeight": 3 })
+    );
+}
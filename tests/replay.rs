@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use cosmos_indexer::rpc::txs::AttributeEncoding;
+use cosmos_indexer::source::RecordedSource;
+use cosmos_indexer::{commands, IndexerContext};
+
+mod common;
+
+use common::node::FakeNode;
+use common::{fixture_path, indexer_config, matcher_options, swap, swaps};
+
+async fn replay_context(path: &str) -> Arc<IndexerContext> {
+    common::replay_context(RecordedSource::open(path).unwrap(), |_| {}).await
+}
+
+/// Height and amount of every swap matched in `from_height..=to_height`.
+async fn replay_swaps(
+    context: Arc<IndexerContext>,
+    from_height: u64,
+    to_height: u64,
+) -> Vec<(u64, String)> {
+    swaps(
+        &commands::replay(context, from_height, to_height)
+            .await
+            .unwrap(),
+    )
+}
+
+#[test]
+fn reads_the_recorded_range() {
+    let source = RecordedSource::open(&fixture_path("recording.jsonl.gz")).unwrap();
+
+    assert_eq!(source.range(), Some((100, 103)));
+    assert_eq!(source.node_version.as_deref(), Some("0.37.2"));
+}
+
+#[tokio::test]
+async fn replays_the_recording_through_the_matchers() {
+    let context = replay_context(&fixture_path("recording.jsonl.gz")).await;
+    assert_eq!(context.attribute_encoding, AttributeEncoding::Plain);
+
+    assert_eq!(
+        replay_swaps(context, 100, 103).await,
+        vec![
+            swap(100, "2000"),
+            swap(100, "2001"),
+            swap(102, "2020"),
+            swap(102, "2021"),
+            swap(102, "2022"),
+            swap(102, "2023"),
+            swap(103, "2030"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn stops_at_the_requested_height() {
+    let context = replay_context(&fixture_path("recording.jsonl.gz")).await;
+
+    assert_eq!(
+        replay_swaps(context, 101, 102).await,
+        vec![
+            swap(102, "2020"),
+            swap(102, "2021"),
+            swap(102, "2022"),
+            swap(102, "2023"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn fails_outside_the_recorded_range() {
+    let context = replay_context(&fixture_path("recording.jsonl.gz")).await;
+
+    let err = commands::replay(context, 102, 104).await.unwrap_err();
+    assert_eq!(err.to_string(), "Height: 104 isn't recorded");
+}
+
+#[tokio::test]
+async fn replays_what_it_records() {
+    let mut indexer_config = indexer_config();
+    indexer_config.rpc_endpoint = FakeNode::new(u64::MAX).serve();
+    // Pages smaller than the ranges so the recording holds several pages of a range.
+    indexer_config.tx_search_per_page = 2;
+    let context = cosmos_indexer::build_context(indexer_config, matcher_options())
+        .await
+        .unwrap();
+
+    let path = std::env::temp_dir().join(format!("recording-{}.jsonl.gz", std::process::id()));
+    let path = path.to_str().unwrap();
+    let recorded = cosmos_indexer::source::recorded::record(context, 100, 103, path)
+        .await
+        .unwrap();
+    // The status, a /blockchain per batch and 1 + 3 /tx_search pages.
+    assert_eq!(recorded, 7);
+
+    let replayed = replay_swaps(replay_context(path).await, 100, 103).await;
+    let expected = replay_swaps(
+        replay_context(&fixture_path("recording.jsonl.gz")).await,
+        100,
+        103,
+    )
+    .await;
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(replayed, expected);
+}
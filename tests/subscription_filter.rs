@@ -0,0 +1,326 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+use cosmos_indexer::api::subscriptions::{
+    self, EventHistory, SubscriptionFilter, SubscriptionMessage,
+};
+use cosmos_indexer::api::Chains;
+use cosmos_indexer::database::events::{EventLog, EventsDocument, EventsQuery};
+use cosmos_indexer::reorg::Rollback;
+use cosmos_indexer::source::MemorySource;
+use cosmos_indexer::{IndexerContext, StreamMessage};
+
+mod common;
+
+fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+fn event(chain_id: &str, key: &str, action: &str) -> EventsDocument {
+    EventsDocument {
+        _id: mongodb::bson::oid::ObjectId::new(),
+        chain_id: chain_id.to_string(),
+        block_height: 100,
+        block_time: None,
+        block_hash: None,
+        proposer_address: None,
+        tx_hash: "ABC".to_string(),
+        transaction_id: None,
+        key: key.to_string(),
+        logs: vec![EventLog {
+            key: "action".to_string(),
+            value: action.to_string(),
+        }],
+        full_logs: Vec::new(),
+        failed_message: None,
+        success: true,
+        code: 0,
+        codespace: String::new(),
+        log: String::new(),
+        created_at: mongodb::bson::DateTime::now(),
+    }
+}
+
+#[test]
+fn parses_every_filter() {
+    let filter = SubscriptionFilter::from_params(params(&[
+        ("keys", "swap,,provide"),
+        ("chain_id", "neutron-1"),
+        ("from_height", "100"),
+        ("attr.action", "swap"),
+        ("unknown", "ignored"),
+    ]))
+    .ok()
+    .unwrap();
+
+    assert_eq!(filter.keys, vec!["swap", "provide"]);
+    assert_eq!(filter.chain_id.as_deref(), Some("neutron-1"));
+    assert_eq!(filter.from_height, Some(100));
+    assert_eq!(
+        filter.attributes,
+        vec![("action".to_string(), "swap".to_string())]
+    );
+}
+
+#[test]
+fn rejects_missing_keys_and_invalid_heights() {
+    assert!(SubscriptionFilter::from_params(params(&[("chain_id", "neutron-1")])).is_err());
+    assert!(
+        SubscriptionFilter::from_params(params(&[("keys", "swap"), ("from_height", "-1")]))
+            .is_err()
+    );
+}
+
+#[test]
+fn matches_events_of_the_filtered_chain() {
+    let any_chain = SubscriptionFilter::from_params(params(&[("keys", "swap")]))
+        .ok()
+        .unwrap();
+    let one_chain =
+        SubscriptionFilter::from_params(params(&[("keys", "swap"), ("chain_id", "neutron-1")]))
+            .ok()
+            .unwrap();
+
+    assert!(any_chain.matches(&event("neutron-1", "swap", "swap")));
+    assert!(any_chain.matches(&event("osmosis-1", "swap", "swap")));
+    assert!(one_chain.matches(&event("neutron-1", "swap", "swap")));
+    assert!(!one_chain.matches(&event("osmosis-1", "swap", "swap")));
+    assert!(!one_chain.matches(&event("neutron-1", "provide", "swap")));
+    assert!(one_chain.matches_chain("neutron-1") && !one_chain.matches_chain("osmosis-1"));
+}
+
+#[test]
+fn matches_every_attribute() {
+    let filter =
+        SubscriptionFilter::from_params(params(&[("keys", "swap"), ("attr.action", "swap")]))
+            .ok()
+            .unwrap();
+
+    assert!(filter.matches(&event("neutron-1", "swap", "swap")));
+    assert!(!filter.matches(&event("neutron-1", "swap", "withdraw")));
+}
+
+type Hook = Box<dyn FnOnce(&MemoryHistory) + Send>;
+
+/// Events and committed heights kept in memory. `on_committed_height` runs when the first
+/// committed height is read, to index a block right at that moment.
+#[derive(Default)]
+struct MemoryHistory {
+    events: Mutex<Vec<EventsDocument>>,
+    committed_heights: Mutex<HashMap<String, u64>>,
+    on_committed_height: Mutex<Option<Hook>>,
+}
+
+impl MemoryHistory {
+    fn new(events: Vec<EventsDocument>, committed_heights: &[(&str, u64)]) -> Self {
+        let history = MemoryHistory::default();
+        *history.events.lock().unwrap() = events;
+        for (chain_id, height) in committed_heights {
+            history.commit(chain_id, *height);
+        }
+
+        history
+    }
+
+    fn commit(&self, chain_id: &str, height: u64) {
+        self.committed_heights
+            .lock()
+            .unwrap()
+            .insert(chain_id.to_string(), height);
+    }
+}
+
+#[async_trait]
+impl EventHistory for MemoryHistory {
+    async fn committed_height(&self, chain: Arc<IndexerContext>) -> Result<u64, anyhow::Error> {
+        let on_committed_height = self.on_committed_height.lock().unwrap().take();
+        if let Some(on_committed_height) = on_committed_height {
+            on_committed_height(self);
+        }
+
+        Ok(self.committed_heights.lock().unwrap()[&chain.indexer_config.chain_id])
+    }
+
+    async fn events(
+        &self,
+        _context: Arc<IndexerContext>,
+        query: &EventsQuery,
+    ) -> Result<Vec<EventsDocument>, anyhow::Error> {
+        let mut events: Vec<EventsDocument> = self
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| {
+                query
+                    .chain_id
+                    .as_ref()
+                    .is_none_or(|chain_id| *chain_id == event.chain_id)
+                    && query.keys.contains(&event.key)
+                    && query
+                        .from_height
+                        .is_none_or(|from_height| event.block_height >= from_height)
+                    && query
+                        .after
+                        .is_none_or(|after| (event.block_height, event._id) > after)
+            })
+            .cloned()
+            .collect();
+        events.sort_by_key(|event| (event.block_height, event._id));
+        events.truncate(query.limit as usize);
+
+        Ok(events)
+    }
+}
+
+fn stored(chain_id: &str, height: u64) -> EventsDocument {
+    EventsDocument {
+        block_height: height,
+        ..event(chain_id, "swap", "swap")
+    }
+}
+
+async fn chain(chain_id: &str) -> Arc<IndexerContext> {
+    let chain_id = chain_id.to_string();
+
+    common::context(
+        Arc::new(MemorySource::new()),
+        |config| config.chain_id = chain_id,
+        None,
+    )
+    .await
+}
+
+/// Subscribes to `chains` with `params`, the first chain publishes the live events.
+fn subscribe(
+    chains: &[Arc<IndexerContext>],
+    history: MemoryHistory,
+    params: &[(&str, &str)],
+) -> mpsc::Receiver<SubscriptionMessage> {
+    let filter = SubscriptionFilter::from_params(self::params(params))
+        .ok()
+        .unwrap();
+    let (sender, receiver) = mpsc::channel(16);
+    tokio::spawn(subscriptions::forward_events(
+        chains[0].clone(),
+        Chains(chains.to_vec()),
+        Arc::new(history),
+        filter,
+        sender,
+    ));
+
+    receiver
+}
+
+fn publish(context: &IndexerContext, event: &EventsDocument) {
+    let _ = context
+        .event_stream
+        .send(StreamMessage::Event(Box::new(event.clone())));
+}
+
+/// Waits for the subscription to send its next message.
+async fn next(receiver: &mut mpsc::Receiver<SubscriptionMessage>) -> SubscriptionMessage {
+    timeout(Duration::from_secs(1), receiver.recv())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+async fn next_event(receiver: &mut mpsc::Receiver<SubscriptionMessage>) -> EventsDocument {
+    match next(receiver).await {
+        SubscriptionMessage::Event(event) => *event,
+        message => panic!("Expected an event, got {:?}", message),
+    }
+}
+
+#[tokio::test]
+async fn replays_then_forwards_the_events_of_the_filtered_chain() {
+    let chains = vec![chain("test-1").await, chain("other-1").await];
+    let (old, committed, uncommitted) = (
+        stored("test-1", 99),
+        stored("test-1", 100),
+        stored("test-1", 101),
+    );
+    let history = MemoryHistory::new(
+        vec![
+            old,
+            committed.clone(),
+            uncommitted.clone(),
+            stored("other-1", 100),
+        ],
+        &[("test-1", 100), ("other-1", 100)],
+    );
+    let mut receiver = subscribe(
+        &chains,
+        history,
+        &[
+            ("keys", "swap"),
+            ("chain_id", "test-1"),
+            ("from_height", "100"),
+        ],
+    );
+
+    assert_eq!(next_event(&mut receiver).await._id, committed._id);
+    assert_eq!(next_event(&mut receiver).await._id, uncommitted._id);
+
+    // Replayed events come after the subscription to the stream. The live copy of the one above
+    // the committed height is skipped, so are the events and rollbacks of other chains.
+    let live = stored("test-1", 102);
+    publish(&chains[0], &uncommitted);
+    publish(&chains[0], &stored("other-1", 102));
+    for chain_id in ["other-1", "test-1"] {
+        let _ = chains[0]
+            .event_stream
+            .send(StreamMessage::Rollback(Rollback {
+                chain_id: chain_id.to_string(),
+                fork_height: 101,
+            }));
+    }
+    publish(&chains[0], &live);
+
+    match next(&mut receiver).await {
+        SubscriptionMessage::Rollback(rollback) => {
+            assert_eq!(
+                (rollback.chain_id.as_str(), rollback.fork_height),
+                ("test-1", 101)
+            )
+        }
+        message => panic!("Expected a rollback, got {:?}", message),
+    }
+    assert_eq!(next_event(&mut receiver).await._id, live._id);
+}
+
+#[tokio::test]
+async fn sends_an_event_indexed_while_subscribing_once() {
+    let chains = vec![chain("test-1").await];
+    let indexed = stored("test-1", 101);
+    let history = MemoryHistory::new(vec![stored("test-1", 100)], &[("test-1", 100)]);
+
+    // Block 101 is saved, published and committed while the subscription reads the committed
+    // height: it has to come from the replay only.
+    let (context, event) = (chains[0].clone(), indexed.clone());
+    *history.on_committed_height.lock().unwrap() = Some(Box::new(move |history| {
+        history.events.lock().unwrap().push(event.clone());
+        publish(&context, &event);
+        history.commit("test-1", 101);
+    }));
+    let mut receiver = subscribe(
+        &chains,
+        history,
+        &[("keys", "swap"), ("from_height", "100")],
+    );
+
+    assert_eq!(next_event(&mut receiver).await.block_height, 100);
+    assert_eq!(next_event(&mut receiver).await._id, indexed._id);
+
+    let live = stored("test-1", 102);
+    publish(&chains[0], &live);
+    assert_eq!(next_event(&mut receiver).await._id, live._id);
+}
@@ -0,0 +1,126 @@
+use serde_json::json;
+
+use cosmos_indexer::event_matcher::matcher::{json_path, matches_tx_filter};
+use cosmos_indexer::event_matcher::matcher_config::{Pattern, TxFilter};
+use cosmos_indexer::rpc::decode::{DecodedMessage, DecodedTx};
+
+fn message(type_url: &str, msg: Option<serde_json::Value>) -> DecodedMessage {
+    DecodedMessage {
+        type_url: type_url.to_string(),
+        sender: None,
+        contract: None,
+        code_id: None,
+        label: None,
+        msg,
+        funds: Vec::new(),
+        value: None,
+        messages: Vec::new(),
+    }
+}
+
+fn pattern(key: &str, value: &str) -> Pattern {
+    Pattern {
+        key: key.to_string(),
+        value: value.to_string(),
+    }
+}
+
+/// A liquidation signed by `neutron1bot`, executed through an authz exec.
+fn decoded_tx() -> DecodedTx {
+    let execute = message(
+        "/cosmwasm.wasm.v1.MsgExecuteContract",
+        Some(json!({
+            "liquidate": { "user": "neutron1user", "amount": 42, "partial": false },
+            "routes": [{ "pool": "1" }, { "pool": "2" }],
+        })),
+    );
+    let mut exec = message("/cosmos.authz.v1beta1.MsgExec", None);
+    exec.messages.push(execute);
+
+    DecodedTx {
+        signers: vec!["neutron1bot".to_string()],
+        messages: vec![message("/cosmos.bank.v1beta1.MsgSend", None), exec],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn looks_up_nested_fields_and_array_items() {
+    let value = json!({ "a": { "b": [{ "c": 1 }, { "c": "two" }] } });
+
+    assert_eq!(json_path(&value, "a.b.1.c"), Some(&json!("two")));
+    assert_eq!(json_path(&value, "a.b.0"), Some(&json!({ "c": 1 })));
+    assert_eq!(json_path(&value, "a.b.2.c"), None);
+    assert_eq!(json_path(&value, "a.b.first"), None);
+    assert_eq!(json_path(&value, "a.missing"), None);
+}
+
+#[test]
+fn empty_filter_matches_every_tx() {
+    assert!(matches_tx_filter(
+        &TxFilter::default(),
+        &DecodedTx::default()
+    ));
+}
+
+#[test]
+fn matches_signers_and_message_types() {
+    let decoded_tx = decoded_tx();
+    let filter = |signers: &[&str], message_types: &[&str]| TxFilter {
+        signers: signers.iter().map(|signer| signer.to_string()).collect(),
+        message_types: message_types.iter().map(|url| url.to_string()).collect(),
+        ..Default::default()
+    };
+
+    assert!(matches_tx_filter(
+        &filter(&["neutron1bot"], &[]),
+        &decoded_tx
+    ));
+    assert!(!matches_tx_filter(
+        &filter(&["neutron1user"], &[]),
+        &decoded_tx
+    ));
+    assert!(matches_tx_filter(
+        &filter(&[], &["/cosmwasm.wasm.v1.MsgExecuteContract"]),
+        &decoded_tx
+    ));
+    assert!(!matches_tx_filter(
+        &filter(&["neutron1bot"], &["/cosmos.gov.v1.MsgVote"]),
+        &decoded_tx
+    ));
+}
+
+#[test]
+fn matches_contract_msg_values_of_any_json_type() {
+    let decoded_tx = decoded_tx();
+    let filter = |patterns: Vec<Pattern>| TxFilter {
+        contract_msg: patterns,
+        ..Default::default()
+    };
+
+    assert!(matches_tx_filter(
+        &filter(vec![
+            pattern("liquidate.user", "neutron1user"),
+            pattern("liquidate.amount", "42"),
+            pattern("liquidate.partial", "false"),
+            pattern("routes.1.pool", "2"),
+        ]),
+        &decoded_tx
+    ));
+    assert!(!matches_tx_filter(
+        &filter(vec![
+            pattern("liquidate.user", "neutron1user"),
+            pattern("liquidate.amount", "43"),
+        ]),
+        &decoded_tx
+    ));
+    // Objects and missing paths never match.
+    assert!(!matches_tx_filter(
+        &filter(vec![pattern("liquidate", "neutron1user")]),
+        &decoded_tx
+    ));
+    assert!(!matches_tx_filter(
+        &filter(vec![pattern("provide.user", "neutron1user")]),
+        &decoded_tx
+    ));
+}
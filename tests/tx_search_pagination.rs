@@ -0,0 +1,115 @@
+use std::sync::atomic::Ordering;
+
+use cosmos_indexer::rpc::txs::{paginate_tx_search, Tx, TxSearchOptions};
+
+mod common;
+
+use common::node::FakeNode;
+
+fn options(max_results: u64) -> TxSearchOptions {
+    TxSearchOptions {
+        per_page: 100,
+        max_results,
+    }
+}
+
+async fn search(node: &FakeNode, max_results: u64) -> Result<Vec<Tx>, anyhow::Error> {
+    paginate_tx_search(
+        |from_height, to_height, page, per_page| {
+            node.fetch_page(from_height, to_height, page, per_page)
+        },
+        100,
+        103,
+        &options(max_results),
+    )
+    .await
+}
+
+fn positions(txs: &[Tx]) -> Vec<(u64, u64)> {
+    txs.iter().map(|tx| (tx.height, tx.index)).collect()
+}
+
+#[tokio::test]
+async fn fetches_every_page_when_the_node_caps_per_page() {
+    let node = FakeNode::new(3);
+
+    let txs = search(&node, 1000).await.unwrap();
+
+    assert_eq!(
+        positions(&txs),
+        vec![
+            (100, 0),
+            (100, 1),
+            (102, 0),
+            (102, 1),
+            (102, 2),
+            (102, 3),
+            (103, 0)
+        ]
+    );
+    // 7 txs in pages of 3, later pages ask for the capped size.
+    assert_eq!(
+        node.requests(),
+        vec![(100, 103, 1, 100), (100, 103, 2, 3), (100, 103, 3, 3)]
+    );
+}
+
+#[tokio::test]
+async fn fetches_a_single_page() {
+    let node = FakeNode::new(100);
+
+    let txs = search(&node, 1000).await.unwrap();
+
+    assert_eq!(txs.len(), 7);
+    assert_eq!(node.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn fails_on_empty_pages_instead_of_spinning() {
+    let mut node = FakeNode::new(3);
+    node.empty_from_page = Some(2);
+
+    let result = search(&node, 1000).await;
+
+    assert!(result.is_err());
+    assert_eq!(node.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn splits_ranges_with_too_many_txs() {
+    let node = FakeNode::new(3);
+
+    let txs = search(&node, 3).await.unwrap();
+
+    assert_eq!(txs.len(), 7);
+    // 100-103 holds 7 txs, 100-101 holds 2 and 102-103 holds 5 so it is split again, 102 alone
+    // holds 4 but can't be split further.
+    let first_pages: Vec<(u64, u64)> = node
+        .requests()
+        .into_iter()
+        .filter(|(_, _, page, _)| *page == 1)
+        .map(|(from_height, to_height, _, _)| (from_height, to_height))
+        .collect();
+    assert_eq!(
+        first_pages,
+        vec![(100, 103), (100, 101), (102, 103), (102, 102), (103, 103)]
+    );
+}
+
+#[tokio::test]
+async fn retries_ranges_whose_count_drifts() {
+    let node = FakeNode::new(3);
+    node.drifting_requests.store(2, Ordering::SeqCst);
+
+    let txs = search(&node, 1000).await.unwrap();
+
+    assert_eq!(txs.len(), 7);
+}
+
+#[tokio::test]
+async fn gives_up_when_the_count_keeps_drifting() {
+    let node = FakeNode::new(3);
+    node.drifting_requests.store(100, Ordering::SeqCst);
+
+    assert!(search(&node, 1000).await.is_err());
+}
@@ -0,0 +1,248 @@
+use futures::{SinkExt, StreamExt};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio_tungstenite::tungstenite::Message;
+
+use cosmos_indexer::pipeline::wait_for_new_block;
+use cosmos_indexer::rpc::websocket::{self, handle_event, websocket_endpoint, LiveTxs};
+use cosmos_indexer::source::RecordedSource;
+use cosmos_indexer::{IndexerConfig, IndexerContext};
+
+mod common;
+
+use common::block;
+
+async fn context(configure: impl FnOnce(&mut IndexerConfig)) -> Arc<IndexerContext> {
+    common::replay_context(RecordedSource::default(), |indexer_config| {
+        indexer_config.fetch_single_timeout = 100;
+        indexer_config.websocket_poll_interval = 5000;
+        configure(indexer_config);
+    })
+    .await
+}
+
+fn new_block_event(height: u64, tx_count: usize) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "result": {
+            "query": "tm.event='NewBlock'",
+            "data": {
+                "type": "tendermint/event/NewBlock",
+                "value": {
+                    "block": {
+                        "header": {
+                            "height": height.to_string(),
+                            "last_block_id": { "hash": format!("HASH{}", height - 1) },
+                        },
+                        "data": { "txs": vec!["dHg="; tx_count] },
+                    },
+                },
+            },
+        },
+    })
+    .to_string()
+}
+
+/// Successful txs leave out `code`, as CometBFT does.
+fn tx_event(height: u64, index: u64) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "result": {
+            "query": "tm.event='Tx'",
+            "data": {
+                "type": "tendermint/event/Tx",
+                "value": {
+                    "TxResult": {
+                        "height": height.to_string(),
+                        "index": index,
+                        "tx": "dHg=",
+                        "result": {
+                            "events": [{
+                                "type": "wasm",
+                                "attributes": [{ "key": "action", "value": "swap" }],
+                            }],
+                        },
+                    },
+                },
+            },
+            "events": { "tx.hash": [format!("TX{}-{}", height, index)] },
+        },
+    })
+    .to_string()
+}
+
+fn hashes(txs: &[cosmos_indexer::rpc::txs::Tx]) -> Vec<&str> {
+    txs.iter().map(|tx| tx.hash.as_str()).collect()
+}
+
+#[tokio::test]
+async fn derives_the_endpoint_from_the_rpc_endpoint() {
+    let endpoint = |rpc_endpoint: &'static str| async move {
+        let context = context(|config| {
+            config.rpc_endpoint = rpc_endpoint.to_string();
+            config.websocket_endpoint = None;
+        })
+        .await;
+        websocket_endpoint(context.as_ref())
+    };
+
+    assert_eq!(
+        endpoint("https://rpc.example.com/").await,
+        "wss://rpc.example.com/websocket"
+    );
+    assert_eq!(
+        endpoint("http://localhost:26657").await,
+        "ws://localhost:26657/websocket"
+    );
+
+    let context = context(|config| {
+        config.websocket_endpoint = Some("ws://node:26657/websocket".to_string());
+    })
+    .await;
+    assert_eq!(
+        websocket_endpoint(context.as_ref()),
+        "ws://node:26657/websocket"
+    );
+}
+
+#[tokio::test]
+async fn polls_while_the_websocket_is_disabled_or_disconnected() {
+    let context = context(|_| {}).await;
+
+    let started_at = Instant::now();
+    wait_for_new_block(context.as_ref(), None).await;
+    assert!(started_at.elapsed() >= Duration::from_millis(100));
+
+    let (_sender, mut disconnected) = watch::channel(None);
+    let started_at = Instant::now();
+    wait_for_new_block(context.as_ref(), Some(&mut disconnected)).await;
+    let elapsed = started_at.elapsed();
+    assert!(elapsed >= Duration::from_millis(100) && elapsed < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn wakes_up_on_new_blocks_while_connected() {
+    let context = context(|_| {}).await;
+    let (sender, mut new_blocks) = watch::channel(Some(100));
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        sender.send(Some(101)).unwrap();
+        // Keeps the channel open past the wake up.
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    });
+
+    let started_at = Instant::now();
+    wait_for_new_block(context.as_ref(), Some(&mut new_blocks)).await;
+    assert!(started_at.elapsed() < Duration::from_millis(100));
+}
+
+#[test]
+fn takes_the_txs_of_complete_blocks() {
+    let live_txs = LiveTxs::new();
+
+    // Tx events may be handled before the event of their block.
+    assert_eq!(handle_event(&tx_event(101, 1), &live_txs).unwrap(), None);
+    assert_eq!(
+        handle_event(&new_block_event(101, 2), &live_txs).unwrap(),
+        Some(101)
+    );
+    handle_event(&tx_event(101, 0), &live_txs).unwrap();
+    handle_event(&new_block_event(102, 0), &live_txs).unwrap();
+
+    let txs = live_txs.take(101, 102, &[block(101), block(102)]).unwrap();
+    assert_eq!(hashes(&txs), vec!["TX101-0", "TX101-1"]);
+    assert_eq!(txs[0].tx_result.code, 0);
+    assert_eq!(txs[0].tx, "dHg=");
+
+    // Taken heights are dropped.
+    assert!(live_txs.take(101, 101, &[]).is_none());
+}
+
+#[test]
+fn falls_back_to_the_source_for_incomplete_or_forked_blocks() {
+    let live_txs = LiveTxs::new();
+
+    // Missed the block event.
+    handle_event(&tx_event(101, 0), &live_txs).unwrap();
+    assert!(live_txs.take(101, 101, &[]).is_none());
+
+    // Missed one of the txs.
+    handle_event(&new_block_event(102, 2), &live_txs).unwrap();
+    handle_event(&tx_event(102, 0), &live_txs).unwrap();
+    assert!(live_txs.take(102, 102, &[]).is_none());
+
+    // The fetched block doesn't extend the same parent.
+    handle_event(&new_block_event(103, 0), &live_txs).unwrap();
+    let mut forked = block(103);
+    forked.parent_hash = "FORK102".to_string();
+    assert!(live_txs.take(103, 103, &[forked]).is_none());
+
+    // Gaps in the range.
+    handle_event(&new_block_event(105, 0), &live_txs).unwrap();
+    assert!(live_txs.take(104, 105, &[]).is_none());
+}
+
+#[test]
+fn ignores_subscription_confirmations_and_other_events() {
+    let live_txs = LiveTxs::new();
+
+    let confirmation = json!({ "jsonrpc": "2.0", "id": 1, "result": {} }).to_string();
+    assert_eq!(handle_event(&confirmation, &live_txs).unwrap(), None);
+    assert!(handle_event("not json", &live_txs).is_err());
+}
+
+#[tokio::test]
+async fn subscribes_to_blocks_and_txs() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let (queries_sender, mut queries) = tokio::sync::mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+        for _ in 0..2 {
+            let message = stream.next().await.unwrap().unwrap();
+            let request: serde_json::Value = serde_json::from_str(&message.to_string()).unwrap();
+            queries_sender
+                .send(request["params"]["query"].as_str().unwrap().to_string())
+                .unwrap();
+        }
+        // The block is notified once both events were handled.
+        for event in [tx_event(101, 0), new_block_event(101, 1)] {
+            stream.send(Message::Text(event)).await.unwrap();
+        }
+        // Keeps the connection open.
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    });
+
+    let context = context(|config| {
+        config.websocket_endpoint = Some(format!("ws://{}", address));
+    })
+    .await;
+    let (sender, mut new_blocks) = watch::channel(None);
+    let live_txs = Arc::new(LiveTxs::new());
+    tokio::spawn(websocket::watch_new_blocks(
+        context,
+        sender,
+        live_txs.clone(),
+    ));
+
+    assert_eq!(queries.recv().await.unwrap(), "tm.event='NewBlock'");
+    assert_eq!(queries.recv().await.unwrap(), "tm.event='Tx'");
+
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        new_blocks.wait_for(|height| *height == Some(101)),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    let txs = live_txs.take(101, 101, &[block(101)]).unwrap();
+    assert_eq!(hashes(&txs), vec!["TX101-0"]);
+}